@@ -9,7 +9,8 @@ use sframe::{receiver::Receiver, sender::Sender, CipherSuiteVariant};
 const KEY_MATERIAL: &str = "THIS_IS_SOME_MATERIAL";
 const PARTICIPANT_ID: u64 = 42;
 const SKIP: usize = 0;
-const PAYLOAD_SIZES: [usize; 4] = [512, 5120, 51200, 512000];
+// small payloads (e.g. audio frames) are dominated by the per frame key setup
+const PAYLOAD_SIZES: [usize; 5] = [64, 512, 5120, 51200, 512000];
 
 fn create_random_payload(size: usize) -> Vec<u8> {
     let mut unencrypted_payload = vec![0; size];
@@ -65,6 +66,27 @@ impl CryptoBenches {
             },
         );
 
+        bench_over_payload_sizes(
+            c,
+            &format!("encrypt into buffer with {:?}", self.variant),
+            |b, &payload_size| {
+                let mut buffer = Vec::new();
+                b.iter_batched(
+                    || create_random_payload(payload_size),
+                    |unencrypted_payload| {
+                        // the header grows with the frame count
+                        buffer.resize(self.sender.encrypted_frame_len(payload_size), 0);
+                        let frame_len = self
+                            .sender
+                            .encrypt_into(&unencrypted_payload, SKIP, &mut buffer)
+                            .unwrap();
+                        black_box(frame_len);
+                    },
+                    BatchSize::SmallInput,
+                );
+            },
+        );
+
         bench_over_payload_sizes(
             c,
             &format!("decrypt with {:?}", self.variant),
@@ -106,8 +128,10 @@ where
 
 fn crypto_benches(c: &mut Criterion) {
     for variant in [
+        CipherSuiteVariant::AesCtr128HmacSha256_80,
         CipherSuiteVariant::AesGcm128Sha256,
         CipherSuiteVariant::AesGcm256Sha512,
+        CipherSuiteVariant::ChaCha20Poly1305Sha256,
     ] {
         let mut ctx = CryptoBenches::from(variant);
         ctx.run_benches(c);
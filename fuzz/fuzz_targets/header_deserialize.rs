@@ -0,0 +1,18 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sframe::header::{Deserialization, Header, HeaderFields};
+
+fuzz_target!(|data: &[u8]| {
+    let is_valid = Header::is_valid(data);
+    match Header::deserialize(data) {
+        Ok(header) => {
+            assert!(is_valid);
+            assert!(header.size() <= data.len());
+        }
+        Err(_) => assert!(!is_valid),
+    }
+});
@@ -0,0 +1,34 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sframe::{
+    error::SframeError,
+    header::{Deserialization, Header, HeaderFields, Serialization},
+};
+
+fuzz_target!(|header: Header| {
+    let mut buffer = vec![0_u8; header.size()];
+    header.serialize(&mut buffer).unwrap();
+
+    let deserialized = Header::deserialize(&buffer).unwrap();
+    assert_eq!(deserialized.is_extended(), header.is_extended());
+    assert_eq!(deserialized.key_id(), header.key_id());
+    assert_eq!(deserialized.frame_count(), header.frame_count());
+    assert_eq!(deserialized.size(), buffer.len());
+
+    // trailing bytes are not part of the header
+    buffer.push(0xff);
+    assert_eq!(Header::deserialize(&buffer).unwrap().size(), header.size());
+
+    let too_small = header.size() - 1;
+    assert_eq!(
+        header.serialize(&mut buffer[..too_small]),
+        Err(SframeError::BufferTooSmall {
+            needed: header.size(),
+            available: too_small,
+        })
+    );
+});
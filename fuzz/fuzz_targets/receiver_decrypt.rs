@@ -0,0 +1,40 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sframe::{receiver::Receiver, CipherSuiteVariant};
+
+// base key and KID of the RFC 9605 test vectors, which the seed corpus is built from
+const KEY_MATERIAL: [u8; 16] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+];
+const KEY_ID: u64 = 0x123;
+
+const VARIANTS: [CipherSuiteVariant; 6] = [
+    CipherSuiteVariant::AesCtr128HmacSha256_80,
+    CipherSuiteVariant::AesCtr128HmacSha256_64,
+    CipherSuiteVariant::AesCtr128HmacSha256_32,
+    CipherSuiteVariant::AesGcm128Sha256,
+    CipherSuiteVariant::AesGcm256Sha512,
+    CipherSuiteVariant::ChaCha20Poly1305Sha256,
+];
+
+// the first byte selects the cipher suite and the nof bytes to skip, the rest is the frame
+fuzz_target!(|data: &[u8]| {
+    let Some((&config, frame)) = data.split_first() else {
+        return;
+    };
+    let variant = VARIANTS[usize::from(config) % VARIANTS.len()];
+    let skip = usize::from(config >> 3);
+
+    let mut receiver = Receiver::with_cipher_suite(variant);
+    // a basic and an extended KID, so both header types reach the decryption
+    for key_id in [0, KEY_ID] {
+        receiver.set_encryption_key(key_id, &KEY_MATERIAL).unwrap();
+    }
+
+    let _ = receiver.decrypt(frame, skip);
+    let _ = receiver.decrypt_in_place(&mut frame.to_vec(), skip);
+});
@@ -0,0 +1,68 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use sframe::{
+    header::{Header, HeaderFields, Serialization},
+    receiver::Receiver,
+    CipherSuiteVariant,
+};
+
+// base key and KID of the RFC 9605 test vectors, which the seed corpus is built from
+const KEY_MATERIAL: [u8; 16] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+];
+const KEY_ID: u64 = 0x123;
+
+#[derive(Arbitrary, Debug)]
+enum Variant {
+    AesCtr128HmacSha256_80,
+    AesCtr128HmacSha256_64,
+    AesCtr128HmacSha256_32,
+    AesGcm128Sha256,
+    AesGcm256Sha512,
+    ChaCha20Poly1305Sha256,
+}
+
+impl From<Variant> for CipherSuiteVariant {
+    fn from(variant: Variant) -> Self {
+        match variant {
+            Variant::AesCtr128HmacSha256_80 => CipherSuiteVariant::AesCtr128HmacSha256_80,
+            Variant::AesCtr128HmacSha256_64 => CipherSuiteVariant::AesCtr128HmacSha256_64,
+            Variant::AesCtr128HmacSha256_32 => CipherSuiteVariant::AesCtr128HmacSha256_32,
+            Variant::AesGcm128Sha256 => CipherSuiteVariant::AesGcm128Sha256,
+            Variant::AesGcm256Sha512 => CipherSuiteVariant::AesGcm256Sha512,
+            Variant::ChaCha20Poly1305Sha256 => CipherSuiteVariant::ChaCha20Poly1305Sha256,
+        }
+    }
+}
+
+/// A frame with a well-formed header, so the fuzzer reaches the decryption more often
+#[derive(Arbitrary, Debug)]
+struct Frame<'a> {
+    variant: Variant,
+    skip: u8,
+    header: Header,
+    encrypted_payload: &'a [u8],
+}
+
+fuzz_target!(|frame: Frame| {
+    let mut receiver = Receiver::with_cipher_suite(CipherSuiteVariant::from(frame.variant));
+    for key_id in [0, KEY_ID] {
+        receiver.set_encryption_key(key_id, &KEY_MATERIAL).unwrap();
+    }
+
+    let skip = usize::from(frame.skip);
+    let mut encrypted_frame = vec![0; skip + frame.header.size()];
+    frame
+        .header
+        .serialize(&mut encrypted_frame[skip..])
+        .unwrap();
+    encrypted_frame.extend_from_slice(frame.encrypted_payload);
+
+    let _ = receiver.decrypt(&encrypted_frame, skip);
+    let _ = receiver.decrypt_in_place(&mut encrypted_frame, skip);
+});
@@ -0,0 +1,35 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+//! Time source for the expiry of keys, which can be replaced e.g. in tests
+
+use core::time::Duration;
+
+/// A monotonic clock, measuring the time since an arbitrary origin
+pub trait Clock: Send + Sync {
+    /// the time since the origin of the clock
+    fn now(&self) -> Duration;
+}
+
+/// [`Clock`] based on [`std::time::Instant`], measuring the time since its creation
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    origin: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock {
+            origin: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
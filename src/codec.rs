@@ -0,0 +1,849 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+//! Computes the nof leading bytes of a video frame, which are left unencrypted (`skip`),
+//! so SFUs and packetizers can still read the codec specific payload header.
+
+use crate::error::{Result, SframeError};
+
+/// Determines the unencrypted prefix of the frames of a codec.
+/// The prefix is the same for the unencrypted and the encrypted frame, so senders and receivers use the same implementation.
+pub trait UnencryptedPrefix {
+    /// nof leading bytes of the frame which are left unencrypted, without changing the state of the codec
+    fn unencrypted_len(&self, frame: &[u8]) -> Result<usize>;
+
+    /// Updates the state of the codec with an unencrypted frame, once it has been encrypted or decrypted successfully.
+    /// Hence frames which fail to decrypt, e.g. replayed or forged frames, cannot change the state.
+    fn update(&mut self, _unencrypted_frame: &[u8]) {}
+}
+
+/// VP8 frames, see [RFC 6386 9.1](https://www.rfc-editor.org/rfc/rfc6386.html#section-9.1):
+/// the frame tag of 3 bytes is left unencrypted, for key frames also the start code and the frame size (10 bytes in total)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Vp8;
+
+impl Vp8 {
+    const FRAME_TAG_LEN: usize = 3;
+    const KEY_FRAME_HEADER_LEN: usize = 10;
+    const START_CODE: [u8; 3] = [0x9d, 0x01, 0x2a];
+}
+
+impl UnencryptedPrefix for Vp8 {
+    fn unencrypted_len(&self, frame: &[u8]) -> Result<usize> {
+        let is_key_frame = frame.first().ok_or(SframeError::InvalidCodecFrame)? & 0x01 == 0;
+        let len = if is_key_frame {
+            Vp8::KEY_FRAME_HEADER_LEN
+        } else {
+            Vp8::FRAME_TAG_LEN
+        };
+
+        let is_valid = frame.len() >= len
+            && (!is_key_frame || frame[Vp8::FRAME_TAG_LEN..6] == Vp8::START_CODE);
+        if is_valid {
+            Ok(len)
+        } else {
+            Err(SframeError::InvalidCodecFrame)
+        }
+    }
+}
+
+/// VP9 frames, see the [VP9 bitstream specification 6.2](https://storage.googleapis.com/downloads.webmproject.org/docs/vp9/vp9-bitstream-specification-v0.6-20160331-draft.pdf):
+/// the uncompressed header is left unencrypted.
+/// As the size of inter frames may be inherited from their reference frames, the frame sizes are tracked,
+/// hence each sender and receiver needs its own instance, which sees all frames of the stream.
+/// Of a superframe, only the uncompressed header of the first frame is left unencrypted,
+/// the frame sizes are tracked for all of its frames.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Vp9 {
+    reference_frame_widths: [Option<u32>; Vp9::NUM_REF_FRAMES],
+}
+
+impl Vp9 {
+    const NUM_REF_FRAMES: usize = 8;
+    const FRAME_MARKER: u32 = 2;
+    const SYNC_CODE: u32 = 0x49_83_42;
+    const CS_RGB: u32 = 7;
+    const MIN_TILE_WIDTH_B64: u32 = 4;
+    const MAX_TILE_WIDTH_B64: u32 = 64;
+    const SEGMENTATION_FEATURE_BITS: [u8; 4] = [8, 6, 2, 0];
+    const SEGMENTATION_FEATURE_SIGNED: [bool; 4] = [true, true, false, false];
+
+    fn parse_uncompressed_header(&self, frame: &[u8]) -> Result<UncompressedHeader> {
+        let mut reader = BitReader::new(frame);
+
+        if reader.read(2)? != Self::FRAME_MARKER {
+            return Err(SframeError::InvalidCodecFrame);
+        }
+        let profile_low_bit = reader.read(1)?;
+        let profile = (reader.read(1)? << 1) | profile_low_bit;
+        if profile == 3 {
+            reader.read(1)?;
+        }
+
+        let show_existing_frame = reader.read_bool()?;
+        if show_existing_frame {
+            reader.read(3)?;
+            return Ok(UncompressedHeader {
+                len: reader.byte_len(),
+                refresh: None,
+            });
+        }
+
+        let is_key_frame = !reader.read_bool()?;
+        let show_frame = reader.read_bool()?;
+        let error_resilient_mode = reader.read_bool()?;
+
+        let (width, refresh_frame_flags) = if is_key_frame {
+            Self::read_sync_code(&mut reader)?;
+            Self::read_color_config(&mut reader, profile)?;
+            let width = Self::read_frame_size(&mut reader)?;
+            Self::read_render_size(&mut reader)?;
+            (width, 0xff)
+        } else {
+            let intra_only = !show_frame && reader.read_bool()?;
+            if !error_resilient_mode {
+                // reset_frame_context
+                reader.read(2)?;
+            }
+
+            if intra_only {
+                Self::read_sync_code(&mut reader)?;
+                if profile > 0 {
+                    Self::read_color_config(&mut reader, profile)?;
+                }
+                let refresh_frame_flags = reader.read(8)?;
+                let width = Self::read_frame_size(&mut reader)?;
+                Self::read_render_size(&mut reader)?;
+                (width, refresh_frame_flags)
+            } else {
+                let refresh_frame_flags = reader.read(8)?;
+                let mut ref_frame_idx = [0; 3];
+                for idx in &mut ref_frame_idx {
+                    *idx = reader.read(3)?;
+                    // ref_frame_sign_bias
+                    reader.read(1)?;
+                }
+                let width = self.read_frame_size_with_refs(&mut reader, &ref_frame_idx)?;
+                // allow_high_precision_mv
+                reader.read(1)?;
+                let is_filter_switchable = reader.read_bool()?;
+                if !is_filter_switchable {
+                    reader.read(2)?;
+                }
+                (width, refresh_frame_flags)
+            }
+        };
+
+        if !error_resilient_mode {
+            // refresh_frame_context, frame_parallel_decoding_mode
+            reader.read(2)?;
+        }
+        // frame_context_idx
+        reader.read(2)?;
+
+        Self::read_loop_filter_params(&mut reader)?;
+        Self::read_quantization_params(&mut reader)?;
+        Self::read_segmentation_params(&mut reader)?;
+        Self::read_tile_info(&mut reader, width)?;
+
+        let header_size_in_bytes = reader.read(16)?;
+        if header_size_in_bytes == 0 {
+            return Err(SframeError::InvalidCodecFrame);
+        }
+
+        Ok(UncompressedHeader {
+            len: reader.byte_len(),
+            refresh: Some((width, refresh_frame_flags)),
+        })
+    }
+
+    fn refresh_reference_frames(&mut self, frame: &[u8]) {
+        if let Ok(UncompressedHeader {
+            refresh: Some((width, refresh_frame_flags)),
+            ..
+        }) = self.parse_uncompressed_header(frame)
+        {
+            for (index, reference_frame_width) in self.reference_frame_widths.iter_mut().enumerate()
+            {
+                if refresh_frame_flags & (1 << index) != 0 {
+                    *reference_frame_width = Some(width);
+                }
+            }
+        }
+    }
+
+    fn read_sync_code(reader: &mut BitReader) -> Result<()> {
+        if reader.read(24)? == Self::SYNC_CODE {
+            Ok(())
+        } else {
+            Err(SframeError::InvalidCodecFrame)
+        }
+    }
+
+    fn read_color_config(reader: &mut BitReader, profile: u32) -> Result<()> {
+        if profile >= 2 {
+            // ten_or_twelve_bit
+            reader.read(1)?;
+        }
+        let color_space = reader.read(3)?;
+        if color_space != Self::CS_RGB {
+            // color_range
+            reader.read(1)?;
+            if profile == 1 || profile == 3 {
+                // subsampling_x, subsampling_y, reserved_zero
+                reader.read(3)?;
+            }
+        } else if profile == 1 || profile == 3 {
+            // reserved_zero
+            reader.read(1)?;
+        }
+        Ok(())
+    }
+
+    /// returns the frame width
+    fn read_frame_size(reader: &mut BitReader) -> Result<u32> {
+        let width = reader.read(16)? + 1;
+        // frame_height_minus_1
+        reader.read(16)?;
+        Ok(width)
+    }
+
+    fn read_render_size(reader: &mut BitReader) -> Result<()> {
+        let render_and_frame_size_different = reader.read_bool()?;
+        if render_and_frame_size_different {
+            reader.read(32)?;
+        }
+        Ok(())
+    }
+
+    fn read_frame_size_with_refs(
+        &self,
+        reader: &mut BitReader,
+        ref_frame_idx: &[u32; 3],
+    ) -> Result<u32> {
+        let mut width = None;
+        for &idx in ref_frame_idx {
+            let found_ref = reader.read_bool()?;
+            if found_ref {
+                width = Some(
+                    self.reference_frame_widths[idx as usize]
+                        .ok_or(SframeError::InvalidCodecFrame)?,
+                );
+                break;
+            }
+        }
+        let width = match width {
+            Some(width) => width,
+            None => Self::read_frame_size(reader)?,
+        };
+        Self::read_render_size(reader)?;
+        Ok(width)
+    }
+
+    fn read_loop_filter_params(reader: &mut BitReader) -> Result<()> {
+        // loop_filter_level, loop_filter_sharpness
+        reader.read(9)?;
+        let loop_filter_delta_enabled = reader.read_bool()?;
+        if loop_filter_delta_enabled {
+            let loop_filter_delta_update = reader.read_bool()?;
+            if loop_filter_delta_update {
+                // 4 ref deltas and 2 mode deltas, each su(6) if updated
+                for _ in 0..6 {
+                    if reader.read_bool()? {
+                        reader.read(7)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_quantization_params(reader: &mut BitReader) -> Result<()> {
+        // base_q_idx
+        reader.read(8)?;
+        // delta_q_y_dc, delta_q_uv_dc, delta_q_uv_ac, each su(4) if coded
+        for _ in 0..3 {
+            if reader.read_bool()? {
+                reader.read(5)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_segmentation_params(reader: &mut BitReader) -> Result<()> {
+        let segmentation_enabled = reader.read_bool()?;
+        if !segmentation_enabled {
+            return Ok(());
+        }
+
+        let segmentation_update_map = reader.read_bool()?;
+        if segmentation_update_map {
+            for _ in 0..7 {
+                Self::read_prob(reader)?;
+            }
+            let segmentation_temporal_update = reader.read_bool()?;
+            if segmentation_temporal_update {
+                for _ in 0..3 {
+                    Self::read_prob(reader)?;
+                }
+            }
+        }
+
+        let segmentation_update_data = reader.read_bool()?;
+        if segmentation_update_data {
+            // segmentation_abs_or_delta_update
+            reader.read(1)?;
+            for _ in 0..8 {
+                for (bits, signed) in Self::SEGMENTATION_FEATURE_BITS
+                    .into_iter()
+                    .zip(Self::SEGMENTATION_FEATURE_SIGNED)
+                {
+                    let feature_enabled = reader.read_bool()?;
+                    if feature_enabled {
+                        reader.read(bits + u8::from(signed))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_prob(reader: &mut BitReader) -> Result<()> {
+        let prob_coded = reader.read_bool()?;
+        if prob_coded {
+            reader.read(8)?;
+        }
+        Ok(())
+    }
+
+    fn read_tile_info(reader: &mut BitReader, width: u32) -> Result<()> {
+        let mi_cols = (width + 7) >> 3;
+        let sb64_cols = (mi_cols + 7) >> 3;
+
+        let mut min_log2_tile_cols = 0;
+        while (Self::MAX_TILE_WIDTH_B64 << min_log2_tile_cols) < sb64_cols {
+            min_log2_tile_cols += 1;
+        }
+        let mut max_log2_tile_cols = 1;
+        while (sb64_cols >> max_log2_tile_cols) >= Self::MIN_TILE_WIDTH_B64 {
+            max_log2_tile_cols += 1;
+        }
+        max_log2_tile_cols -= 1;
+
+        let mut tile_cols_log2 = min_log2_tile_cols;
+        while tile_cols_log2 < max_log2_tile_cols {
+            let increment_tile_cols_log2 = reader.read_bool()?;
+            if !increment_tile_cols_log2 {
+                break;
+            }
+            tile_cols_log2 += 1;
+        }
+
+        let tile_rows_log2 = reader.read_bool()?;
+        if tile_rows_log2 {
+            // increment_tile_rows_log2
+            reader.read(1)?;
+        }
+        Ok(())
+    }
+}
+
+impl UnencryptedPrefix for Vp9 {
+    fn unencrypted_len(&self, frame: &[u8]) -> Result<usize> {
+        self.parse_uncompressed_header(frame)
+            .map(|uncompressed_header| uncompressed_header.len)
+    }
+
+    fn update(&mut self, unencrypted_frame: &[u8]) {
+        match SuperframeIndex::parse(unencrypted_frame) {
+            Some(index) => {
+                let mut frames = unencrypted_frame;
+                for frame_size in index.frame_sizes() {
+                    let Some((frame, remaining_frames)) = frames.split_at_checked(frame_size)
+                    else {
+                        break;
+                    };
+                    self.refresh_reference_frames(frame);
+                    frames = remaining_frames;
+                }
+            }
+            None => self.refresh_reference_frames(unencrypted_frame),
+        }
+    }
+}
+
+/// The uncompressed header of a VP9 frame
+struct UncompressedHeader {
+    /// size in bytes
+    len: usize,
+    /// the frame width and the reference frames it is stored in (`refresh_frame_flags`)
+    refresh: Option<(u32, u32)>,
+}
+
+/// The index at the end of a VP9 superframe, which lists the sizes of its frames,
+/// see Annex B of the VP9 bitstream specification
+struct SuperframeIndex<'a> {
+    frame_sizes: &'a [u8],
+    bytes_per_frame_size: usize,
+}
+
+impl<'a> SuperframeIndex<'a> {
+    const MARKER_MASK: u8 = 0xe0;
+    const MARKER: u8 = 0xc0;
+
+    fn parse(frame: &'a [u8]) -> Option<Self> {
+        let marker = *frame.last()?;
+        if marker & Self::MARKER_MASK != Self::MARKER {
+            return None;
+        }
+        let frames_in_superframe = usize::from(marker & 0x07) + 1;
+        let bytes_per_frame_size = usize::from((marker >> 3) & 0x03) + 1;
+        // the marker byte is repeated at the start of the index
+        let index_len = 2 + bytes_per_frame_size * frames_in_superframe;
+        let index = &frame[frame.len().checked_sub(index_len)?..];
+
+        (index[0] == marker).then(|| SuperframeIndex {
+            frame_sizes: &index[1..index_len - 1],
+            bytes_per_frame_size,
+        })
+    }
+
+    /// the frame sizes are encoded in little-endian
+    fn frame_sizes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.frame_sizes
+            .chunks(self.bytes_per_frame_size)
+            .map(|frame_size| {
+                frame_size
+                    .iter()
+                    .rev()
+                    .fold(0, |size, byte| (size << 8) | usize::from(*byte))
+            })
+    }
+}
+
+/// Reads big-endian bit fields, as used by the VP9 uncompressed header
+struct BitReader<'a> {
+    data: &'a [u8],
+    bit_pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader { data, bit_pos: 0 }
+    }
+
+    fn read(&mut self, n_bits: u8) -> Result<u32> {
+        let mut value = 0;
+        for _ in 0..n_bits {
+            let byte = self
+                .data
+                .get(self.bit_pos / 8)
+                .ok_or(SframeError::InvalidCodecFrame)?;
+            let bit = (byte >> (7 - self.bit_pos % 8)) & 1;
+            value = (value << 1) | u32::from(bit);
+            self.bit_pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn read_bool(&mut self) -> Result<bool> {
+        Ok(self.read(1)? == 1)
+    }
+
+    /// nof bytes read, including the trailing bits up to the next byte boundary
+    fn byte_len(&self) -> usize {
+        self.bit_pos.div_ceil(8)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const PAYLOAD: [u8; 4] = [0xde, 0xad, 0xbe, 0xef];
+
+    #[test]
+    fn leave_frame_tag_of_vp8_delta_frames_unencrypted() {
+        let frame = [[0x31, 0x02, 0x00].as_slice(), &PAYLOAD].concat();
+        assert_eq!(Vp8.unencrypted_len(&frame), Ok(3));
+    }
+
+    #[test]
+    fn leave_vp8_key_frame_header_unencrypted() {
+        let frame = [
+            [0x50, 0x42, 0x00, 0x9d, 0x01, 0x2a, 0x80, 0x02, 0xe0, 0x01].as_slice(),
+            &PAYLOAD,
+        ]
+        .concat();
+        assert_eq!(Vp8.unencrypted_len(&frame), Ok(10));
+    }
+
+    #[test]
+    fn fail_on_invalid_vp8_frames() {
+        assert_eq!(
+            Vp8.unencrypted_len(&[]),
+            Err(SframeError::InvalidCodecFrame)
+        );
+        assert_eq!(
+            Vp8.unencrypted_len(&[0x31, 0x02]),
+            Err(SframeError::InvalidCodecFrame)
+        );
+        // key frames without start code
+        assert_eq!(
+            Vp8.unencrypted_len(&[0x50, 0x42, 0x00, 0x9d, 0x01, 0x2b, 0x80, 0x02, 0xe0, 0x01]),
+            Err(SframeError::InvalidCodecFrame)
+        );
+        assert_eq!(
+            Vp8.unencrypted_len(&[0x50, 0x42, 0x00, 0x9d, 0x01, 0x2a]),
+            Err(SframeError::InvalidCodecFrame)
+        );
+    }
+
+    /// Writes the fields of a VP9 uncompressed header
+    #[derive(Default)]
+    struct BitWriter {
+        data: Vec<u8>,
+        bit_pos: usize,
+    }
+
+    impl BitWriter {
+        fn write(mut self, n_bits: u8, value: u32) -> Self {
+            for bit in (0..n_bits).rev() {
+                if self.bit_pos % 8 == 0 {
+                    self.data.push(0);
+                }
+                let bit = ((value >> bit) & 1) as u8;
+                *self.data.last_mut().unwrap() |= bit << (7 - self.bit_pos % 8);
+                self.bit_pos += 1;
+            }
+            self
+        }
+
+        /// the uncompressed header, followed by the compressed header and frame data
+        fn frame(self) -> (usize, Vec<u8>) {
+            (self.data.len(), [self.data.as_slice(), &PAYLOAD].concat())
+        }
+    }
+
+    fn vp9_key_frame(width: u32) -> (usize, Vec<u8>) {
+        let writer = BitWriter::default()
+            // frame_marker, profile 0, show_existing_frame
+            .write(2, 2)
+            .write(2, 0)
+            .write(1, 0)
+            // frame_type, show_frame, error_resilient_mode
+            .write(1, 0)
+            .write(1, 1)
+            .write(1, 0)
+            .write(24, 0x49_83_42)
+            // color_space, color_range
+            .write(3, 1)
+            .write(1, 0)
+            // frame size, render_and_frame_size_different
+            .write(16, width - 1)
+            .write(16, 479)
+            .write(1, 0)
+            // refresh_frame_context, frame_parallel_decoding_mode, frame_context_idx
+            .write(2, 0)
+            .write(2, 0)
+            // loop filter level and sharpness, with a delta update of the first ref delta
+            .write(9, 0)
+            .write(1, 1)
+            .write(1, 1)
+            .write(1, 1)
+            .write(7, 0x42)
+            .write(5, 0)
+            // base_q_idx, delta_q_y_dc coded
+            .write(8, 60)
+            .write(1, 1)
+            .write(5, 3)
+            .write(2, 0)
+            // segmentation disabled
+            .write(1, 0);
+        // frames up to a width of 448 have a single tile col, otherwise there is no tile col increment
+        let writer = if width > 448 {
+            writer.write(1, 0)
+        } else {
+            writer
+        };
+        writer
+            // no tile rows, header_size_in_bytes
+            .write(1, 0)
+            .write(16, 42)
+            .frame()
+    }
+
+    fn vp9_inter_frame() -> (usize, Vec<u8>) {
+        BitWriter::default()
+            .write(2, 2)
+            .write(2, 0)
+            .write(1, 0)
+            // frame_type, show_frame, error_resilient_mode, reset_frame_context
+            .write(1, 1)
+            .write(1, 1)
+            .write(1, 0)
+            .write(2, 0)
+            // refresh_frame_flags, 3 times ref_frame_idx and ref_frame_sign_bias
+            .write(8, 0x01)
+            .write(12, 0)
+            // found_ref, render_and_frame_size_different
+            .write(1, 1)
+            .write(1, 0)
+            // allow_high_precision_mv, is_filter_switchable
+            .write(1, 0)
+            .write(1, 1)
+            .write(4, 0)
+            // loop filter without deltas, quantization without deltas
+            .write(10, 0)
+            .write(11, 0)
+            // segmentation enabled, update_map with one coded prob, no temporal update
+            .write(1, 1)
+            .write(1, 1)
+            .write(1, 1)
+            .write(8, 128)
+            .write(6, 0)
+            .write(1, 0)
+            // update_data, abs_or_delta, the first feature of segment 0 enabled
+            .write(1, 1)
+            .write(1, 0)
+            .write(1, 1)
+            .write(9, 0x1ff)
+            .write(31, 0)
+            // no tile col increment, tile rows with increment
+            .write(1, 0)
+            .write(1, 1)
+            .write(1, 1)
+            .write(16, 42)
+            .frame()
+    }
+
+    #[test]
+    fn leave_vp9_uncompressed_header_unencrypted() {
+        let mut vp9 = Vp9::default();
+
+        let (header_len, key_frame) = vp9_key_frame(640);
+        assert_eq!(header_len, 17);
+        assert_eq!(vp9.unencrypted_len(&key_frame), Ok(header_len));
+        vp9.update(&key_frame);
+
+        let (header_len, inter_frame) = vp9_inter_frame();
+        assert_eq!(header_len, 18);
+        assert_eq!(vp9.unencrypted_len(&inter_frame), Ok(header_len));
+    }
+
+    /// a superframe with an index of 1 byte frame sizes, see Annex B of the VP9 bitstream specification
+    fn vp9_superframe(frames: &[&[u8]]) -> Vec<u8> {
+        let marker = 0xc0 | (frames.len() as u8 - 1);
+        let frame_sizes = frames.iter().map(|frame| frame.len() as u8);
+        let index = [marker]
+            .into_iter()
+            .chain(frame_sizes)
+            .chain([marker])
+            .collect::<Vec<_>>();
+        [frames.concat(), index].concat()
+    }
+
+    /// an inter frame with an uncompressed header of 81 bits, the tile col increment is only read
+    /// for reference frames wider than 448, otherwise the header has 80 bits (10 bytes)
+    fn vp9_inter_frame_of_11_bytes() -> (usize, Vec<u8>) {
+        BitWriter::default()
+            .write(2, 2)
+            .write(2, 0)
+            .write(1, 0)
+            .write(1, 1)
+            .write(1, 1)
+            .write(1, 0)
+            .write(2, 0)
+            .write(8, 0x01)
+            .write(12, 0)
+            .write(1, 1)
+            .write(1, 0)
+            // allow_high_precision_mv, is_filter_switchable, interp_filter
+            .write(1, 0)
+            .write(1, 0)
+            .write(2, 0)
+            .write(4, 0)
+            .write(10, 0)
+            .write(11, 0)
+            .write(1, 0)
+            // no tile col increment, tile rows with increment
+            .write(1, 0)
+            .write(1, 1)
+            .write(1, 0)
+            .write(16, 42)
+            .frame()
+    }
+
+    #[test]
+    fn keep_vp9_frame_sizes_until_update() {
+        let mut vp9 = Vp9::default();
+        let (_, key_frame) = vp9_key_frame(640);
+        let (_, narrow_key_frame) = vp9_key_frame(256);
+        let (header_len, inter_frame) = vp9_inter_frame_of_11_bytes();
+        assert_eq!(header_len, 11);
+
+        vp9.unencrypted_len(&key_frame).unwrap();
+        assert_eq!(
+            vp9.unencrypted_len(&inter_frame),
+            Err(SframeError::InvalidCodecFrame)
+        );
+
+        vp9.update(&key_frame);
+        vp9.unencrypted_len(&narrow_key_frame).unwrap();
+        assert_eq!(vp9.unencrypted_len(&inter_frame), Ok(header_len));
+
+        // without tile col increment for the narrow reference frame
+        vp9.update(&narrow_key_frame);
+        assert_eq!(vp9.unencrypted_len(&inter_frame), Ok(header_len - 1));
+    }
+
+    #[test]
+    fn update_vp9_frame_sizes_of_all_frames_of_superframes() {
+        let mut vp9 = Vp9::default();
+        let (key_frame_header_len, key_frame) = vp9_key_frame(640);
+        let (_, narrow_key_frame) = vp9_key_frame(256);
+        let (header_len, inter_frame) = vp9_inter_frame_of_11_bytes();
+
+        let superframe = vp9_superframe(&[&key_frame, &narrow_key_frame]);
+        assert_eq!(vp9.unencrypted_len(&superframe), Ok(key_frame_header_len));
+        vp9.update(&superframe);
+        assert_eq!(vp9.unencrypted_len(&inter_frame), Ok(header_len - 1));
+
+        let superframe = vp9_superframe(&[&narrow_key_frame, &key_frame]);
+        vp9.update(&superframe);
+        assert_eq!(vp9.unencrypted_len(&inter_frame), Ok(header_len));
+    }
+
+    #[test]
+    fn read_tile_cols_of_wide_vp9_frames() {
+        let (header_len, key_frame) = BitWriter::default()
+            .write(8, 0x82)
+            .write(24, 0x49_83_42)
+            .write(4, 0)
+            .write(16, 4095)
+            .write(16, 2159)
+            .write(1, 0)
+            .write(4, 0)
+            .write(10, 0)
+            .write(11, 0)
+            .write(1, 0)
+            // min 0 and max 4 tile col bits for a width of 4096, incremented up to the max
+            .write(4, 0b1111)
+            .write(1, 0)
+            .write(16, 42)
+            .frame();
+        assert_eq!(Vp9::default().unencrypted_len(&key_frame), Ok(header_len));
+    }
+
+    #[test]
+    fn leave_vp9_show_existing_frame_unencrypted() {
+        let (header_len, frame) = BitWriter::default()
+            .write(2, 2)
+            .write(2, 0)
+            .write(1, 1)
+            .write(3, 5)
+            .frame();
+        assert_eq!(header_len, 1);
+        assert_eq!(Vp9::default().unencrypted_len(&frame), Ok(1));
+    }
+
+    #[test]
+    fn fail_on_invalid_vp9_frames() {
+        let vp9 = Vp9::default();
+        // the size of the reference frame is unknown without a key frame
+        let (_, inter_frame) = vp9_inter_frame();
+        assert_eq!(
+            vp9.unencrypted_len(&inter_frame),
+            Err(SframeError::InvalidCodecFrame)
+        );
+
+        let (header_len, key_frame) = vp9_key_frame(640);
+        assert_eq!(
+            vp9.unencrypted_len(&key_frame[..header_len - 1]),
+            Err(SframeError::InvalidCodecFrame)
+        );
+
+        let mut invalid_frame_marker = key_frame.clone();
+        invalid_frame_marker[0] ^= 0x80;
+        assert_eq!(
+            vp9.unencrypted_len(&invalid_frame_marker),
+            Err(SframeError::InvalidCodecFrame)
+        );
+
+        let mut invalid_sync_code = key_frame;
+        invalid_sync_code[1] ^= 0x01;
+        assert_eq!(
+            vp9.unencrypted_len(&invalid_sync_code),
+            Err(SframeError::InvalidCodecFrame)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn encrypt_and_decrypt_vp9_frames() {
+        use crate::{receiver::Receiver, sender::Sender};
+
+        let mut sender = Sender::new(42_u64);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+        let mut receiver = Receiver::default();
+        receiver
+            .set_encryption_key(42_u64, "foobar is unsafe")
+            .unwrap();
+        let (mut sender_vp9, mut receiver_vp9) = (Vp9::default(), Vp9::default());
+
+        for (header_len, frame) in [vp9_key_frame(640), vp9_inter_frame()] {
+            let encrypted = sender.encrypt_video_frame(&frame, &mut sender_vp9).unwrap();
+            assert_eq!(encrypted[..header_len], frame[..header_len]);
+            assert_ne!(encrypted[header_len..frame.len()], frame[header_len..]);
+
+            let decrypted = receiver
+                .decrypt_video_frame(encrypted, &mut receiver_vp9)
+                .unwrap();
+            assert_eq!(decrypted, frame);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn ignore_vp9_frames_which_fail_to_decrypt() {
+        use crate::{receiver::Receiver, sender::Sender};
+
+        let mut sender = Sender::new(42_u64);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+        let mut forger = Sender::new(42_u64).with_frame_count(5_u64);
+        forger.set_encryption_key("forged").unwrap();
+        let mut receiver = Receiver::default();
+        receiver
+            .set_encryption_key(42_u64, "foobar is unsafe")
+            .unwrap();
+        let (mut sender_vp9, mut receiver_vp9) = (Vp9::default(), Vp9::default());
+
+        let (_, key_frame) = vp9_key_frame(640);
+        let encrypted = sender
+            .encrypt_video_frame(&key_frame, &mut sender_vp9)
+            .unwrap();
+        receiver
+            .decrypt_video_frame(encrypted, &mut receiver_vp9)
+            .unwrap();
+
+        // a forged key frame with a different width must not change the frame sizes of the receiver
+        let (_, narrow_key_frame) = vp9_key_frame(256);
+        let forged = forger
+            .encrypt_video_frame(&narrow_key_frame, &mut Vp9::default())
+            .unwrap();
+        assert_eq!(
+            receiver.decrypt_video_frame(forged, &mut receiver_vp9),
+            Err(SframeError::DecryptionFailure)
+        );
+
+        let (_, inter_frame) = vp9_inter_frame_of_11_bytes();
+        let encrypted = sender
+            .encrypt_video_frame(&inter_frame, &mut sender_vp9)
+            .unwrap();
+        let decrypted = receiver
+            .decrypt_video_frame(encrypted, &mut receiver_vp9)
+            .unwrap();
+        assert_eq!(decrypted, inter_frame);
+    }
+}
@@ -1,135 +1,286 @@
 // Copyright (c) 2023 GoTo Group, Inc
 // SPDX-License-Identifier: Apache-2.0 AND MIT
 
-use super::secret::Secret;
+use alloc::vec::Vec;
+
+use super::{backend::CryptoBackend, cipher_suite::CipherSuite, secret::Secret};
 use crate::{error::Result, header::FrameCount};
 
 pub trait AeadEncrypt {
     type AuthTag: AsRef<[u8]>;
-    fn encrypt<IoBuffer, Aad>(
+    fn encrypt<IoBuffer, Aad, B>(
         &self,
         io_buffer: &mut IoBuffer,
-        secret: &Secret,
+        secret: &Secret<B>,
         aad_buffer: &Aad,
         frame_count: FrameCount,
     ) -> Result<Self::AuthTag>
     where
         IoBuffer: AsMut<[u8]> + ?Sized,
-        Aad: AsRef<[u8]> + ?Sized;
+        Aad: AsRef<[u8]> + ?Sized,
+        B: CryptoBackend;
 }
 
 pub trait AeadDecrypt {
-    fn decrypt<'a, IoBuffer, Aad>(
+    fn decrypt<'a, IoBuffer, Aad, B>(
+        &self,
+        io_buffer: &'a mut IoBuffer,
+        secret: &Secret<B>,
+        aad_buffer: &Aad,
+        frame_count: FrameCount,
+    ) -> Result<&'a mut [u8]>
+    where
+        IoBuffer: AsMut<[u8]> + ?Sized,
+        Aad: AsRef<[u8]> + ?Sized,
+        B: CryptoBackend;
+}
+
+impl AeadEncrypt for CipherSuite {
+    type AuthTag = Vec<u8>;
+    fn encrypt<IoBuffer, Aad, B>(
+        &self,
+        io_buffer: &mut IoBuffer,
+        secret: &Secret<B>,
+        aad_buffer: &Aad,
+        frame_count: FrameCount,
+    ) -> Result<Vec<u8>>
+    where
+        IoBuffer: AsMut<[u8]> + ?Sized,
+        Aad: AsRef<[u8]> + ?Sized,
+        B: CryptoBackend,
+    {
+        match secret.aead_key.as_ref() {
+            AeadKey::Aead(key) => full_tag::encrypt::<B>(
+                self,
+                key,
+                io_buffer.as_mut(),
+                secret,
+                aad_buffer.as_ref(),
+                frame_count,
+            ),
+            AeadKey::AesCtrHmac { enc_key, auth_key } => aes_ctr::encrypt::<B>(
+                self,
+                (enc_key, auth_key),
+                io_buffer.as_mut(),
+                secret,
+                aad_buffer.as_ref(),
+                frame_count,
+            ),
+        }
+    }
+}
+
+impl AeadDecrypt for CipherSuite {
+    fn decrypt<'a, IoBuffer, Aad, B>(
         &self,
         io_buffer: &'a mut IoBuffer,
-        secret: &Secret,
+        secret: &Secret<B>,
         aad_buffer: &Aad,
         frame_count: FrameCount,
     ) -> Result<&'a mut [u8]>
     where
         IoBuffer: AsMut<[u8]> + ?Sized,
-        Aad: AsRef<[u8]> + ?Sized;
+        Aad: AsRef<[u8]> + ?Sized,
+        B: CryptoBackend,
+    {
+        match secret.aead_key.as_ref() {
+            AeadKey::Aead(key) => full_tag::decrypt::<B>(
+                self,
+                key,
+                io_buffer.as_mut(),
+                secret,
+                aad_buffer.as_ref(),
+                frame_count,
+            ),
+            AeadKey::AesCtrHmac { enc_key, auth_key } => aes_ctr::decrypt::<B>(
+                self,
+                (enc_key, auth_key),
+                io_buffer.as_mut(),
+                secret,
+                aad_buffer.as_ref(),
+                frame_count,
+            ),
+        }
+    }
 }
 
-mod ring {
+/// The key of a [`Secret`] prepared by the crypto backend for the AEAD of a cipher suite,
+/// e.g. with the expanded AES key schedule
+pub(crate) enum AeadKey<B: CryptoBackend> {
+    Aead(B::AeadKey),
+    /// The encryption key and the HMAC key, split from a single secret key
+    AesCtrHmac {
+        enc_key: B::AesCtrKey,
+        auth_key: B::HmacKey,
+    },
+}
 
-    use ring::aead::{BoundKey, SealingKey, Tag};
+impl<B: CryptoBackend> AeadKey<B> {
+    pub fn new(cipher_suite: &CipherSuite, key: &[u8]) -> Result<Self> {
+        if cipher_suite.is_ctr_mode() {
+            aes_ctr::split_key::<B>(cipher_suite, key)
+        } else {
+            B::aead_key(cipher_suite.variant, key).map(AeadKey::Aead)
+        }
+    }
+}
+
+/// Encryption and decryption by the AEAD of the crypto backend, which always computes the full tag.
+/// A truncated tag consists of its leading bytes, which are verified by the backend.
+mod full_tag {
+    use alloc::vec::Vec;
 
     use crate::{
         crypto::{
-            cipher_suite::{CipherSuite, CipherSuiteVariant},
+            backend::{CryptoBackend, NONCE_LEN},
+            cipher_suite::CipherSuite,
             secret::Secret,
         },
         error::{Result, SframeError},
         header::FrameCount,
     };
 
-    use super::{AeadDecrypt, AeadEncrypt};
-    struct FrameNonceSequence {
-        buffer: [u8; ring::aead::NONCE_LEN],
-    }
+    pub fn encrypt<B: CryptoBackend>(
+        cipher_suite: &CipherSuite,
+        key: &B::AeadKey,
+        io_buffer: &mut [u8],
+        secret: &Secret<B>,
+        aad_buffer: &[u8],
+        frame_count: FrameCount,
+    ) -> Result<Vec<u8>> {
+        let nonce = secret.create_nonce::<NONCE_LEN>(&frame_count);
+        let mut auth_tag = B::seal(key, &nonce, aad_buffer, io_buffer)?;
 
-    impl From<[u8; ring::aead::NONCE_LEN]> for FrameNonceSequence {
-        fn from(buffer: [u8; ring::aead::NONCE_LEN]) -> Self {
-            Self { buffer }
+        // a truncated tag consists of the leading bytes of the full tag
+        if auth_tag.len() < cipher_suite.auth_tag_len {
+            return Err(SframeError::EncryptionFailure);
         }
+        auth_tag.truncate(cipher_suite.auth_tag_len);
+        Ok(auth_tag)
     }
 
-    impl ring::aead::NonceSequence for FrameNonceSequence {
-        fn advance(&mut self) -> std::result::Result<ring::aead::Nonce, ring::error::Unspecified> {
-            let nonce = ring::aead::Nonce::assume_unique_for_key(std::mem::take(&mut self.buffer));
-            Ok(nonce)
-        }
+    pub fn decrypt<'a, B: CryptoBackend>(
+        cipher_suite: &CipherSuite,
+        key: &B::AeadKey,
+        io_buffer: &'a mut [u8],
+        secret: &Secret<B>,
+        aad_buffer: &[u8],
+        frame_count: FrameCount,
+    ) -> Result<&'a mut [u8]> {
+        let nonce = secret.create_nonce::<NONCE_LEN>(&frame_count);
+        B::open(
+            key,
+            &nonce,
+            aad_buffer,
+            io_buffer,
+            cipher_suite.auth_tag_len,
+        )
     }
+}
 
-    impl From<CipherSuiteVariant> for &'static ring::aead::Algorithm {
-        fn from(variant: CipherSuiteVariant) -> Self {
-            use CipherSuiteVariant::*;
-            match variant {
-                AesGcm128Sha256 => &ring::aead::AES_128_GCM,
-                AesGcm256Sha512 => &ring::aead::AES_256_GCM,
-            }
+/// AES-CTR with a truncated HMAC-SHA256 tag, see [RFC 9605 4.5.1](https://www.rfc-editor.org/rfc/rfc9605.html#name-aes-ctr-with-sha2)
+///
+/// The [`Secret`] key is split into the AES encryption key (first `key_len - hash_len` bytes)
+/// and the HMAC authentication key (remaining `hash_len` bytes).
+mod aes_ctr {
+    use alloc::vec::Vec;
+
+    use super::AeadKey;
+    use crate::{
+        crypto::{
+            backend::{CryptoBackend, AES_BLOCK_LEN, NONCE_LEN},
+            cipher_suite::CipherSuite,
+            secret::Secret,
+        },
+        error::{Result, SframeError},
+        header::FrameCount,
+    };
+
+    pub fn split_key<B: CryptoBackend>(
+        cipher_suite: &CipherSuite,
+        key: &[u8],
+    ) -> Result<AeadKey<B>> {
+        if key.len() != cipher_suite.key_len {
+            return Err(SframeError::KeyExpansion);
         }
+        let (enc_key, auth_key) = key.split_at(cipher_suite.key_len - cipher_suite.hash_len);
+
+        Ok(AeadKey::AesCtrHmac {
+            enc_key: B::aes_ctr_key(enc_key)?,
+            auth_key: B::hmac_key(auth_key)?,
+        })
     }
 
-    impl CipherSuite {
-        fn unbound_encryption_key(&self, secret: &Secret) -> Result<ring::aead::UnboundKey> {
-            ring::aead::UnboundKey::new(self.variant.into(), secret.key.as_slice())
-                .map_err(|_| SframeError::KeyExpansion)
-        }
+    pub fn encrypt<B: CryptoBackend>(
+        cipher_suite: &CipherSuite,
+        (enc_key, auth_key): (&B::AesCtrKey, &B::HmacKey),
+        io_buffer: &mut [u8],
+        secret: &Secret<B>,
+        aad_buffer: &[u8],
+        frame_count: FrameCount,
+    ) -> Result<Vec<u8>> {
+        let nonce = secret.create_nonce::<NONCE_LEN>(&frame_count);
+
+        apply_key_stream::<B>(enc_key, &nonce, io_buffer)?;
+        compute_tag::<B>(cipher_suite, auth_key, &nonce, aad_buffer, io_buffer)
     }
 
-    impl AeadEncrypt for CipherSuite {
-        type AuthTag = Tag;
-        fn encrypt<IoBuffer, Aad>(
-            &self,
-            io_buffer: &mut IoBuffer,
-            secret: &Secret,
-            aad_buffer: &Aad,
-            frame_count: FrameCount,
-        ) -> Result<Tag>
-        where
-            IoBuffer: AsMut<[u8]> + ?Sized,
-            Aad: AsRef<[u8]> + ?Sized,
-        {
-            let mut sealing_key = SealingKey::<FrameNonceSequence>::new(
-                self.unbound_encryption_key(secret)?,
-                secret.create_nonce(&frame_count).into(),
-            );
+    pub fn decrypt<'a, B: CryptoBackend>(
+        cipher_suite: &CipherSuite,
+        (enc_key, auth_key): (&B::AesCtrKey, &B::HmacKey),
+        io_buffer: &'a mut [u8],
+        secret: &Secret<B>,
+        aad_buffer: &[u8],
+        frame_count: FrameCount,
+    ) -> Result<&'a mut [u8]> {
+        let nonce = secret.create_nonce::<NONCE_LEN>(&frame_count);
 
-            let aad = ring::aead::Aad::from(aad_buffer);
-            let auth_tag = sealing_key
-                .seal_in_place_separate_tag(aad, io_buffer.as_mut())
-                .map_err(|_| SframeError::EncryptionFailure)?;
+        let cipher_text_len = io_buffer
+            .len()
+            .checked_sub(cipher_suite.auth_tag_len)
+            .ok_or(SframeError::DecryptionFailure)?;
+        let (cipher_text, auth_tag) = io_buffer.split_at_mut(cipher_text_len);
 
-            // TODO implement auth tag shortening, see 4.4.1
+        let expected_tag =
+            compute_tag::<B>(cipher_suite, auth_key, &nonce, aad_buffer, cipher_text)?;
+        B::verify_tag(&expected_tag, auth_tag)?;
 
-            Ok(auth_tag)
-        }
+        apply_key_stream::<B>(enc_key, &nonce, cipher_text)?;
+        Ok(cipher_text)
     }
 
-    impl AeadDecrypt for CipherSuite {
-        fn decrypt<'a, IoBuffer, Aad>(
-            &self,
-            io_buffer: &'a mut IoBuffer,
-            secret: &Secret,
-            aad_buffer: &Aad,
-            frame_count: FrameCount,
-        ) -> Result<&'a mut [u8]>
-        where
-            IoBuffer: AsMut<[u8]> + ?Sized,
-            Aad: AsRef<[u8]> + ?Sized,
-        {
-            let aad = ring::aead::Aad::from(&aad_buffer);
-
-            let mut opening_key = ring::aead::OpeningKey::<FrameNonceSequence>::new(
-                self.unbound_encryption_key(secret)?,
-                secret.create_nonce(&frame_count).into(),
-            );
-            opening_key
-                .open_in_place(aad, io_buffer.as_mut())
-                .map_err(|_| SframeError::DecryptionFailure)
-        }
+    fn apply_key_stream<B: CryptoBackend>(
+        enc_key: &B::AesCtrKey,
+        nonce: &[u8; NONCE_LEN],
+        buffer: &mut [u8],
+    ) -> Result<()> {
+        // the initial counter block is the nonce followed by a 32 bit block counter starting at 0
+        let mut initial_counter = [0u8; AES_BLOCK_LEN];
+        initial_counter[..NONCE_LEN].copy_from_slice(nonce);
+
+        B::aes_ctr(enc_key, &initial_counter, buffer)
+    }
+
+    fn compute_tag<B: CryptoBackend>(
+        cipher_suite: &CipherSuite,
+        auth_key: &B::HmacKey,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        cipher_text: &[u8],
+    ) -> Result<Vec<u8>> {
+        let mut tag = B::hmac(
+            auth_key,
+            &[
+                &(aad.len() as u64).to_be_bytes(),
+                &(cipher_text.len() as u64).to_be_bytes(),
+                &(cipher_suite.auth_tag_len as u64).to_be_bytes(),
+                nonce,
+                aad,
+                cipher_text,
+            ],
+        )?;
+        tag.truncate(cipher_suite.auth_tag_len);
+        Ok(tag)
     }
 }
 
@@ -140,6 +291,7 @@ mod test {
         use crate::{
             crypto::{
                 aead::AeadEncrypt,
+                backend::{test_with_backends, CryptoBackend},
                 cipher_suite::{CipherSuite, CipherSuiteVariant},
                 key_expansion::KeyMaterial,
             },
@@ -148,14 +300,15 @@ mod test {
         use rand::{thread_rng, Rng};
         const KEY_MATERIAL: &str = "THIS_IS_RANDOM";
 
-        #[test]
-        fn encrypt_random_frame() {
+        test_with_backends!(encrypt_random_frame);
+
+        fn encrypt_random_frame<B: CryptoBackend>() {
             let mut data = vec![0u8; 1024];
             thread_rng().fill(data.as_mut_slice());
             let header = Header::default();
             let cipher_suite = CipherSuite::from(CipherSuiteVariant::AesGcm256Sha512);
             let secret = KeyMaterial(KEY_MATERIAL.as_bytes())
-                .expand_as_secret(&cipher_suite)
+                .expand_as_secret_with::<B>(&cipher_suite, header.key_id())
                 .unwrap();
 
             let _tag = cipher_suite
@@ -167,133 +320,369 @@ mod test {
                 )
                 .unwrap();
         }
+    }
 
-        #[cfg(feature = "verify-test-vectors")]
-        mod test_vectors {
-            use crate::{
-                crypto::{
-                    aead::{AeadDecrypt, AeadEncrypt},
-                    cipher_suite::{CipherSuite, CipherSuiteVariant},
-                    secret::Secret,
-                },
-                header::{FrameCount, Header, HeaderFields, KeyId},
-                test_vectors::{aes_gcm_128_sha256, *},
-                util::test::assert_bytes_eq,
-            };
-            #[test]
-            fn encrypt_test_vectors_aes_gcm_128_sha256() {
-                aes_gcm_128_sha256::get_test_vectors()
-                    .into_iter()
-                    .for_each(|test_vector| {
-                        let cipher_suite = CipherSuite::from(CipherSuiteVariant::AesGcm128Sha256);
+    mod chacha20_poly1305 {
+        use crate::{
+            crypto::{
+                aead::{AeadDecrypt, AeadEncrypt},
+                backend::{test_with_backends, CryptoBackend},
+                cipher_suite::{CipherSuite, CipherSuiteVariant},
+                key_expansion::KeyMaterial,
+            },
+            error::SframeError,
+            header::{Header, HeaderFields},
+        };
+        use rand::{thread_rng, Rng};
+        const KEY_MATERIAL: &str = "THIS_IS_RANDOM";
 
-                        let secret = Secret {
-                            key: test_vector.key,
-                            salt: test_vector.salt,
-                        };
+        test_with_backends!(
+            encrypt_and_decrypt_random_frame,
+            fail_to_decrypt_with_other_frame_count
+        );
+
+        fn encrypt_and_decrypt_random_frame<B: CryptoBackend>() {
+            let mut plain_text = vec![0u8; 1024];
+            thread_rng().fill(plain_text.as_mut_slice());
+            let header = Header::with_frame_count(42_u64, 1337);
+            let aad = Vec::from(&header);
+            let cipher_suite = CipherSuite::from(CipherSuiteVariant::ChaCha20Poly1305Sha256);
+            let secret = KeyMaterial(KEY_MATERIAL.as_bytes())
+                .expand_as_secret_with::<B>(&cipher_suite, header.key_id())
+                .unwrap();
 
+            let mut data = plain_text.clone();
+            let tag = cipher_suite
+                .encrypt(&mut data, &secret, &aad, header.frame_count())
+                .unwrap();
+            assert_eq!(tag.len(), cipher_suite.auth_tag_len);
+            assert_ne!(data, plain_text);
+
+            data.extend(tag);
+            let decrypted = cipher_suite
+                .decrypt(&mut data, &secret, &aad, header.frame_count())
+                .unwrap();
+            assert_eq!(decrypted, plain_text.as_slice());
+        }
+
+        fn fail_to_decrypt_with_other_frame_count<B: CryptoBackend>() {
+            let header = Header::with_frame_count(42_u64, 1337);
+            let aad = Vec::from(&header);
+            let cipher_suite = CipherSuite::from(CipherSuiteVariant::ChaCha20Poly1305Sha256);
+            let secret = KeyMaterial(KEY_MATERIAL.as_bytes())
+                .expand_as_secret_with::<B>(&cipher_suite, header.key_id())
+                .unwrap();
+
+            let mut data = vec![42u8; 64];
+            let tag = cipher_suite
+                .encrypt(&mut data, &secret, &aad, header.frame_count())
+                .unwrap();
+            data.extend(tag);
+
+            let decrypted =
+                cipher_suite.decrypt(&mut data, &secret, &aad, header.frame_count() + 1);
+            assert_eq!(decrypted, Err(SframeError::DecryptionFailure));
+        }
+    }
+
+    mod truncated_tag {
+        use crate::{
+            crypto::{
+                aead::{AeadDecrypt, AeadEncrypt},
+                backend::{test_with_backends, CryptoBackend},
+                cipher_suite::{CipherSuite, CipherSuiteVariant},
+                secret::Secret,
+            },
+            error::SframeError,
+            header::{FrameCount, Header, HeaderFields, KeyId},
+            test_vectors::sframe::get_test_vectors,
+            util::test::assert_bytes_eq,
+        };
+
+        const AEAD_VARIANTS: [CipherSuiteVariant; 3] = [
+            CipherSuiteVariant::AesGcm128Sha256,
+            CipherSuiteVariant::AesGcm256Sha512,
+            CipherSuiteVariant::ChaCha20Poly1305Sha256,
+        ];
+
+        test_with_backends!(
+            encrypt_with_leading_bytes_of_full_tag,
+            decrypt_test_vectors_with_truncated_tag,
+            fail_to_decrypt_with_modified_truncated_tag
+        );
+
+        fn encrypt_with_leading_bytes_of_full_tag<B: CryptoBackend>() {
+            get_test_vectors()
+                .into_iter()
+                .filter(|test_vector| AEAD_VARIANTS.contains(&test_vector.cipher_suite_variant))
+                .for_each(|test_vector| {
+                    let secret = Secret::<B>::new(
+                        &test_vector.cipher_suite_variant.into(),
+                        test_vector.key.clone().into(),
+                        test_vector.salt.clone().into(),
+                    )
+                    .unwrap();
+                    let header = Header::with_frame_count(
+                        KeyId::from(test_vector.key_id),
+                        FrameCount::from(test_vector.frame_count),
+                    );
+                    let mut aad = Vec::from(&header);
+                    aad.extend(&test_vector.metadata);
+                    let full_tag = &test_vector.cipher_text[test_vector.cipher_text.len() - 16..];
+
+                    for auth_tag_len in CipherSuite::MIN_AUTH_TAG_LEN..16 {
+                        let cipher_suite = CipherSuite::with_auth_tag_len(
+                            test_vector.cipher_suite_variant,
+                            auth_tag_len,
+                        )
+                        .unwrap();
                         let mut data = test_vector.plain_text.clone();
-                        let header = Header::with_frame_count(
-                            KeyId::from(test_vector.key_id),
-                            FrameCount::from(test_vector.frame_count),
-                        );
-                        let header_buffer = Vec::from(&header);
                         let tag = cipher_suite
-                            .encrypt(&mut data, &secret, &header_buffer, header.frame_count())
+                            .encrypt(
+                                &mut data,
+                                &secret,
+                                &aad,
+                                FrameCount::from(test_vector.frame_count),
+                            )
                             .unwrap();
-                        let full_frame: Vec<u8> = header_buffer
-                            .into_iter()
-                            .chain(data.into_iter())
-                            .chain(tag.as_ref().iter().cloned())
-                            .collect();
-
-                        assert_bytes_eq(&full_frame, &test_vector.cipher_text);
-                    });
-            }
 
-            #[test]
-            fn should_decrypt_test_vectors_aes_gcm_128_sha256() {
-                aes_gcm_128_sha256::get_test_vectors()
-                    .into_iter()
-                    .for_each(|test_vector| {
-                        let cipher_suite = CipherSuite::from(CipherSuiteVariant::AesGcm128Sha256);
-
-                        let secret = Secret {
-                            key: test_vector.key,
-                            salt: test_vector.salt,
-                        };
+                        assert_bytes_eq(&tag, &full_tag[..auth_tag_len]);
+                    }
+                });
+        }
 
-                        let header = Header::with_frame_count(
-                            KeyId::from(test_vector.key_id),
+        fn decrypt_test_vectors_with_truncated_tag<B: CryptoBackend>() {
+            get_test_vectors()
+                .into_iter()
+                .filter(|test_vector| AEAD_VARIANTS.contains(&test_vector.cipher_suite_variant))
+                .for_each(|test_vector| {
+                    let secret = Secret::<B>::new(
+                        &test_vector.cipher_suite_variant.into(),
+                        test_vector.key.clone().into(),
+                        test_vector.salt.clone().into(),
+                    )
+                    .unwrap();
+                    let header = Header::with_frame_count(
+                        KeyId::from(test_vector.key_id),
+                        FrameCount::from(test_vector.frame_count),
+                    );
+                    let mut aad = Vec::from(&header);
+                    aad.extend(&test_vector.metadata);
+                    let full_tag_begin = test_vector.cipher_text.len() - 16;
+
+                    for auth_tag_len in CipherSuite::MIN_AUTH_TAG_LEN..16 {
+                        let cipher_suite = CipherSuite::with_auth_tag_len(
+                            test_vector.cipher_suite_variant,
+                            auth_tag_len,
+                        )
+                        .unwrap();
+                        // strip the header and the trailing bytes of the full tag
+                        let mut data = test_vector.cipher_text
+                            [header.size()..full_tag_begin + auth_tag_len]
+                            .to_vec();
+                        let decrypted = cipher_suite.decrypt(
+                            &mut data,
+                            &secret,
+                            &aad,
                             FrameCount::from(test_vector.frame_count),
                         );
-                        let header_buffer = Vec::from(&header);
-                        let mut data = Vec::from(&test_vector.cipher_text[header.size()..]);
 
-                        let decrypted = cipher_suite
-                            .decrypt(&mut data, &secret, &header_buffer, header.frame_count())
-                            .unwrap();
+                        assert_bytes_eq(decrypted.unwrap(), &test_vector.plain_text);
+                    }
+                });
+        }
 
-                        assert_bytes_eq(decrypted, &test_vector.plain_text);
-                    });
+        fn fail_to_decrypt_with_modified_truncated_tag<B: CryptoBackend>() {
+            for variant in AEAD_VARIANTS {
+                let cipher_suite = CipherSuite::with_auth_tag_len(variant, 8).unwrap();
+                let secret = Secret::<B>::new(
+                    &cipher_suite,
+                    vec![42u8; cipher_suite.key_len].into(),
+                    vec![23u8; cipher_suite.nonce_len].into(),
+                )
+                .unwrap();
+                let frame_count = FrameCount::from(17);
+
+                let mut data = vec![1u8; 64];
+                let tag = cipher_suite
+                    .encrypt(&mut data, &secret, b"aad", frame_count)
+                    .unwrap();
+                assert_eq!(tag.len(), 8);
+                data.extend(tag);
+
+                let mut modified = data.clone();
+                *modified.last_mut().unwrap() ^= 1;
+                assert_eq!(
+                    cipher_suite.decrypt(&mut modified, &secret, b"aad", frame_count),
+                    Err(SframeError::DecryptionFailure)
+                );
+
+                let mut modified = data.clone();
+                modified[0] ^= 1;
+                assert_eq!(
+                    cipher_suite.decrypt(&mut modified, &secret, b"aad", frame_count),
+                    Err(SframeError::DecryptionFailure)
+                );
+
+                let decrypted = cipher_suite
+                    .decrypt(&mut data, &secret, b"aad", frame_count)
+                    .unwrap();
+                assert_eq!(decrypted, vec![1u8; 64].as_slice());
             }
-            #[test]
-            fn encrypt_test_vectors_aes_gcm_256_sha512() {
-                aes_gcm_256_sha512::get_test_vectors()
-                    .into_iter()
-                    .for_each(|test_vector| {
-                        let cipher_suite = CipherSuite::from(CipherSuiteVariant::AesGcm256Sha512);
+        }
+    }
 
-                        let secret = Secret {
-                            key: test_vector.key,
-                            salt: test_vector.salt,
-                        };
+    mod test_vectors {
+        use crate::{
+            crypto::{
+                aead::{AeadDecrypt, AeadEncrypt},
+                backend::{test_with_backends, CryptoBackend},
+                cipher_suite::CipherSuite,
+                secret::Secret,
+            },
+            header::{FrameCount, Header, HeaderFields, KeyId},
+            test_vectors::{sframe::get_test_vectors, TestVector},
+            util::test::assert_bytes_eq,
+        };
 
-                        let mut data = test_vector.plain_text.clone();
-                        let header = Header::with_frame_count(
-                            KeyId::from(test_vector.key_id),
-                            FrameCount::from(test_vector.frame_count),
-                        );
-                        let header_buffer = Vec::from(&header);
-                        let tag = cipher_suite
-                            .encrypt(&mut data, &secret, &header_buffer, header.frame_count())
-                            .unwrap();
-                        let full_frame: Vec<u8> = header_buffer
-                            .into_iter()
-                            .chain(data.into_iter())
-                            .chain(tag.as_ref().iter().cloned())
-                            .collect();
-
-                        assert_bytes_eq(&full_frame, &test_vector.cipher_text);
-                    });
-            }
+        test_with_backends!(encrypt_test_vectors, decrypt_test_vectors);
+
+        fn prepare<B: CryptoBackend>(
+            test_vector: &TestVector,
+        ) -> (CipherSuite, Secret<B>, Header, Vec<u8>) {
+            let cipher_suite = CipherSuite::from(test_vector.cipher_suite_variant);
+            let secret = Secret::<B>::new(
+                &cipher_suite,
+                test_vector.key.clone().into(),
+                test_vector.salt.clone().into(),
+            )
+            .unwrap();
+            let header = Header::with_frame_count(
+                KeyId::from(test_vector.key_id),
+                FrameCount::from(test_vector.frame_count),
+            );
+            // the AAD is the encoded header followed by the metadata
+            let aad = Vec::from(&header)
+                .into_iter()
+                .chain(test_vector.metadata.iter().cloned())
+                .collect();
 
-            #[test]
-            fn should_decrypt_test_vectors_aes_gcm_256_sha512() {
-                aes_gcm_256_sha512::get_test_vectors()
+            (cipher_suite, secret, header, aad)
+        }
+
+        fn encrypt_test_vectors<B: CryptoBackend>() {
+            get_test_vectors().into_iter().for_each(|test_vector| {
+                let (cipher_suite, secret, header, aad) = prepare::<B>(&test_vector);
+
+                let mut data = test_vector.plain_text.clone();
+                let tag = cipher_suite
+                    .encrypt(&mut data, &secret, &aad, header.frame_count())
+                    .unwrap();
+                let full_frame: Vec<u8> = Vec::from(&header)
                     .into_iter()
-                    .for_each(|test_vector| {
-                        let cipher_suite = CipherSuite::from(CipherSuiteVariant::AesGcm256Sha512);
+                    .chain(data)
+                    .chain(tag)
+                    .collect();
 
-                        let secret = Secret {
-                            key: test_vector.key,
-                            salt: test_vector.salt,
-                        };
+                assert_bytes_eq(&full_frame, &test_vector.cipher_text);
+            });
+        }
 
-                        let header = Header::with_frame_count(
-                            KeyId::from(test_vector.key_id),
-                            FrameCount::from(test_vector.frame_count),
-                        );
-                        let header_buffer = Vec::from(&header);
-                        let mut data = Vec::from(&test_vector.cipher_text[header.size()..]);
+        fn decrypt_test_vectors<B: CryptoBackend>() {
+            get_test_vectors().into_iter().for_each(|test_vector| {
+                let (cipher_suite, secret, header, aad) = prepare::<B>(&test_vector);
 
-                        let decrypted = cipher_suite
-                            .decrypt(&mut data, &secret, &header_buffer, header.frame_count())
-                            .unwrap();
+                let mut data = Vec::from(&test_vector.cipher_text[header.size()..]);
+                let decrypted = cipher_suite
+                    .decrypt(&mut data, &secret, &aad, header.frame_count())
+                    .unwrap();
 
-                        assert_bytes_eq(decrypted, &test_vector.plain_text);
-                    });
-            }
+                assert_bytes_eq(decrypted, &test_vector.plain_text);
+            });
+        }
+    }
+
+    mod aes_ctr {
+        use crate::{
+            crypto::{
+                aead::{AeadDecrypt, AeadEncrypt},
+                backend::{test_with_backends, CryptoBackend},
+                cipher_suite::CipherSuite,
+                secret::Secret,
+            },
+            error::SframeError,
+            header::FrameCount,
+            test_vectors::{aes_ctr_128_hmac_sha256, AeadTestVector},
+            util::test::assert_bytes_eq,
+        };
+
+        test_with_backends!(
+            encrypt_test_vectors,
+            decrypt_test_vectors,
+            fail_to_decrypt_with_modified_tag
+        );
+
+        fn secret_from<B: CryptoBackend>(test_vector: &AeadTestVector) -> Secret<B> {
+            // with a frame count of 0 the nonce equals the salt
+            Secret::<B>::new(
+                &test_vector.cipher_suite_variant.into(),
+                test_vector.key.clone().into(),
+                test_vector.nonce.clone().into(),
+            )
+            .unwrap()
+        }
+
+        fn encrypt_test_vectors<B: CryptoBackend>() {
+            aes_ctr_128_hmac_sha256::get_test_vectors()
+                .into_iter()
+                .for_each(|test_vector| {
+                    let cipher_suite = CipherSuite::from(test_vector.cipher_suite_variant);
+                    let secret = secret_from::<B>(&test_vector);
+
+                    let mut data = test_vector.plain_text.clone();
+                    let tag = cipher_suite
+                        .encrypt(&mut data, &secret, &test_vector.aad, FrameCount::from(0))
+                        .unwrap();
+                    data.extend(tag);
+
+                    assert_bytes_eq(&data, &test_vector.cipher_text);
+                });
+        }
+
+        fn decrypt_test_vectors<B: CryptoBackend>() {
+            aes_ctr_128_hmac_sha256::get_test_vectors()
+                .into_iter()
+                .for_each(|test_vector| {
+                    let cipher_suite = CipherSuite::from(test_vector.cipher_suite_variant);
+                    let secret = secret_from::<B>(&test_vector);
+
+                    let mut data = test_vector.cipher_text.clone();
+                    let decrypted = cipher_suite
+                        .decrypt(&mut data, &secret, &test_vector.aad, FrameCount::from(0))
+                        .unwrap();
+
+                    assert_bytes_eq(decrypted, &test_vector.plain_text);
+                });
+        }
+
+        fn fail_to_decrypt_with_modified_tag<B: CryptoBackend>() {
+            aes_ctr_128_hmac_sha256::get_test_vectors()
+                .into_iter()
+                .for_each(|test_vector| {
+                    let cipher_suite = CipherSuite::from(test_vector.cipher_suite_variant);
+                    let secret = secret_from::<B>(&test_vector);
+
+                    let mut data = test_vector.cipher_text.clone();
+                    *data.last_mut().unwrap() ^= 1;
+                    let decrypted = cipher_suite.decrypt(
+                        &mut data,
+                        &secret,
+                        &test_vector.aad,
+                        FrameCount::from(0),
+                    );
+
+                    assert_eq!(decrypted, Err(SframeError::DecryptionFailure));
+                });
         }
     }
 }
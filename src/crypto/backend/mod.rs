@@ -0,0 +1,179 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+//! Crypto backends providing the primitives for the cipher suites, selected with cargo features:
+//! - `ring` (default) based on [ring](https://github.com/briansmith/ring)
+//! - `openssl` based on [OpenSSL](https://www.openssl.org/), e.g. for a FIPS validated build
+//! - `rust-crypto` based on the pure rust crates of [RustCrypto](https://github.com/RustCrypto), e.g. for wasm builds without C
+//!
+//! If several backends are enabled, `openssl` is preferred over `rust-crypto`, which is preferred over `ring`.
+//!
+//! Derived keys, salts and base keys are returned in [`Zeroizing`] buffers and wiped on drop.
+//! The key objects prepared by the backends are only wiped where the backend supports it:
+//! - `ring` cannot wipe its AEAD keys (`LessSafeKey`), HMAC keys and HKDF PRKs
+//! - `openssl` cleanses the keys of its cipher contexts and `PKey`s when they are freed
+//! - `rust-crypto` wipes its AES, AES-GCM and ChaCha20-Poly1305 keys, but not the HMAC keys and HKDF PRKs
+//!   of the `hmac` and `hkdf` crates
+
+// backends which are not preferred are still compiled and tested
+#[cfg(feature = "openssl")]
+pub mod openssl;
+#[cfg(feature = "ring")]
+#[cfg_attr(any(feature = "openssl", feature = "rust-crypto"), allow(dead_code))]
+pub mod ring;
+#[cfg(feature = "rust-crypto")]
+#[cfg_attr(feature = "openssl", allow(dead_code))]
+pub mod rust_crypto;
+#[cfg(any(feature = "ring", feature = "rust-crypto"))]
+#[cfg_attr(feature = "openssl", allow(dead_code))]
+mod truncated_tag;
+
+use alloc::vec::Vec;
+use zeroize::Zeroizing;
+
+use super::cipher_suite::{CipherSuite, CipherSuiteVariant};
+use crate::error::Result;
+
+#[cfg(feature = "openssl")]
+pub type Backend = openssl::OpensslBackend;
+#[cfg(all(feature = "rust-crypto", not(feature = "openssl")))]
+pub type Backend = rust_crypto::RustCryptoBackend;
+#[cfg(all(
+    feature = "ring",
+    not(any(feature = "openssl", feature = "rust-crypto"))
+))]
+pub type Backend = ring::RingBackend;
+
+#[cfg(not(any(feature = "ring", feature = "openssl", feature = "rust-crypto")))]
+compile_error!(
+    "No crypto backend is enabled, enable one of the features `ring`, `openssl` or `rust-crypto`"
+);
+
+/// Length of the nonce of all cipher suites
+pub const NONCE_LEN: usize = 12;
+/// Length of the full authentication tag of all AEAD algorithms
+pub const AEAD_TAG_LEN: usize = 16;
+/// Length of an AES block, i.e. of the initial counter block of AES-CTR
+pub const AES_BLOCK_LEN: usize = 16;
+
+/// Hash function of the HKDF of a cipher suite
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl From<&CipherSuite> for HashAlgorithm {
+    fn from(cipher_suite: &CipherSuite) -> Self {
+        match cipher_suite.variant {
+            CipherSuiteVariant::AesCtr128HmacSha256_80
+            | CipherSuiteVariant::AesCtr128HmacSha256_64
+            | CipherSuiteVariant::AesCtr128HmacSha256_32
+            | CipherSuiteVariant::AesGcm128Sha256
+            | CipherSuiteVariant::ChaCha20Poly1305Sha256 => HashAlgorithm::Sha256,
+            CipherSuiteVariant::AesGcm256Sha512 => HashAlgorithm::Sha512,
+        }
+    }
+}
+
+/// The crypto primitives needed by the cipher suites.
+/// Keys are prepared once and can then be used for many frames, also from several threads.
+pub trait CryptoBackend {
+    /// Key of an AEAD algorithm (AES-GCM, ChaCha20-Poly1305)
+    type AeadKey: Send + Sync;
+    /// AES-128 key of the AES-CTR cipher suites
+    type AesCtrKey: Send + Sync;
+    /// HMAC-SHA256 key of the AES-CTR cipher suites
+    type HmacKey: Send + Sync;
+    /// Pseudorandom key of the HKDF, which should be wiped on drop where the backend allows it
+    type Prk;
+
+    /// Prepares the key for the AEAD algorithm of the given variant
+    fn aead_key(variant: CipherSuiteVariant, key: &[u8]) -> Result<Self::AeadKey>;
+
+    /// Encrypts `io_buffer` in place and returns the full authentication tag
+    fn seal(
+        key: &Self::AeadKey,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        io_buffer: &mut [u8],
+    ) -> Result<Vec<u8>>;
+
+    /// Verifies the authentication tag of `tag_len` bytes at the end of `io_buffer`, which may be truncated
+    /// to the leading bytes of the full tag, and decrypts the cipher text in place
+    fn open<'a>(
+        key: &Self::AeadKey,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        io_buffer: &'a mut [u8],
+        tag_len: usize,
+    ) -> Result<&'a mut [u8]>;
+
+    /// Prepares an AES-128 key for AES-CTR
+    fn aes_ctr_key(key: &[u8]) -> Result<Self::AesCtrKey>;
+
+    /// Applies the AES-CTR key stream starting with the given counter block to `buffer`
+    fn aes_ctr(
+        key: &Self::AesCtrKey,
+        initial_counter: &[u8; AES_BLOCK_LEN],
+        buffer: &mut [u8],
+    ) -> Result<()>;
+
+    /// Prepares a key for HMAC-SHA256
+    fn hmac_key(key: &[u8]) -> Result<Self::HmacKey>;
+
+    /// Computes the HMAC-SHA256 over the concatenation of `data`
+    fn hmac(key: &Self::HmacKey, data: &[&[u8]]) -> Result<Vec<u8>>;
+
+    /// Compares the expected and the received authentication tag in constant time
+    fn verify_tag(expected_tag: &[u8], auth_tag: &[u8]) -> Result<()>;
+
+    /// HKDF-Extract
+    fn hkdf_extract(hash: HashAlgorithm, salt: &[u8], ikm: &[u8]) -> Result<Self::Prk>;
+
+    /// Uses an already uniformly random secret as PRK for HKDF-Expand, without HKDF-Extract
+    fn hkdf_prk(hash: HashAlgorithm, secret: &[u8]) -> Result<Self::Prk>;
+
+    /// HKDF-Expand with the concatenation of `info`, the output is wiped on drop
+    fn hkdf_expand(prk: &Self::Prk, info: &[&[u8]], len: usize) -> Result<Zeroizing<Vec<u8>>>;
+}
+
+/// Generates a test module for each enabled backend, calling the given generic test functions
+/// with the backend as type parameter
+#[cfg(test)]
+macro_rules! test_with_backends {
+    ($($test:ident),+ $(,)?) => {
+        #[cfg(feature = "ring")]
+        mod ring {
+            $(
+                #[test]
+                fn $test() {
+                    super::$test::<crate::crypto::backend::ring::RingBackend>();
+                }
+            )+
+        }
+
+        #[cfg(feature = "openssl")]
+        mod openssl {
+            $(
+                #[test]
+                fn $test() {
+                    super::$test::<crate::crypto::backend::openssl::OpensslBackend>();
+                }
+            )+
+        }
+
+        #[cfg(feature = "rust-crypto")]
+        mod rust_crypto {
+            $(
+                #[test]
+                fn $test() {
+                    super::$test::<crate::crypto::backend::rust_crypto::RustCryptoBackend>();
+                }
+            )+
+        }
+    };
+}
+
+#[cfg(test)]
+pub(crate) use test_with_backends;
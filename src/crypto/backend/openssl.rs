@@ -0,0 +1,228 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+use openssl::{
+    cipher::{Cipher, CipherRef},
+    cipher_ctx::CipherCtx,
+    hash::MessageDigest,
+    md::{Md, MdRef},
+    pkey::{Id, PKey, Private},
+    pkey_ctx::{HkdfMode, PkeyCtx},
+    sign::Signer,
+};
+
+use alloc::{vec, vec::Vec};
+use zeroize::Zeroizing;
+
+use super::{CryptoBackend, HashAlgorithm, AEAD_TAG_LEN, AES_BLOCK_LEN, NONCE_LEN};
+use crate::{
+    crypto::cipher_suite::CipherSuiteVariant,
+    error::{Result, SframeError},
+};
+
+/// Crypto backend based on OpenSSL, which cleanses the keys of its contexts when they are freed
+#[derive(Clone, Copy, Debug)]
+pub struct OpensslBackend;
+
+/// Cipher contexts initialized with the key, which are copied for each frame to keep the key schedule
+pub struct AeadKey {
+    encryption: CipherCtx,
+    decryption: CipherCtx,
+}
+
+pub struct Prk {
+    hash: HashAlgorithm,
+    key: Zeroizing<Vec<u8>>,
+}
+
+impl From<HashAlgorithm> for &'static MdRef {
+    fn from(hash: HashAlgorithm) -> Self {
+        match hash {
+            HashAlgorithm::Sha256 => Md::sha256(),
+            HashAlgorithm::Sha512 => Md::sha512(),
+        }
+    }
+}
+
+impl TryFrom<CipherSuiteVariant> for &'static CipherRef {
+    type Error = SframeError;
+
+    fn try_from(variant: CipherSuiteVariant) -> Result<Self> {
+        use CipherSuiteVariant::*;
+        match variant {
+            AesGcm128Sha256 => Ok(Cipher::aes_128_gcm()),
+            AesGcm256Sha512 => Ok(Cipher::aes_256_gcm()),
+            ChaCha20Poly1305Sha256 => Ok(Cipher::chacha20_poly1305()),
+            AesCtr128HmacSha256_80 | AesCtr128HmacSha256_64 | AesCtr128HmacSha256_32 => {
+                Err(SframeError::KeyExpansion)
+            }
+        }
+    }
+}
+
+fn keyed_context(cipher: &CipherRef, key: &[u8], encryption: bool) -> Result<CipherCtx> {
+    if key.len() != cipher.key_length() {
+        return Err(SframeError::KeyExpansion);
+    }
+    let mut context = CipherCtx::new().map_err(|_| SframeError::KeyExpansion)?;
+    if encryption {
+        context.encrypt_init(Some(cipher), Some(key), None)
+    } else {
+        context.decrypt_init(Some(cipher), Some(key), None)
+    }
+    .map_err(|_| SframeError::KeyExpansion)?;
+    Ok(context)
+}
+
+fn copy_context(
+    keyed_context: &CipherCtx,
+) -> core::result::Result<CipherCtx, openssl::error::ErrorStack> {
+    let mut context = CipherCtx::new()?;
+    context.copy(keyed_context)?;
+    Ok(context)
+}
+
+impl CryptoBackend for OpensslBackend {
+    type AeadKey = AeadKey;
+    type AesCtrKey = CipherCtx;
+    type HmacKey = PKey<Private>;
+    type Prk = Prk;
+
+    fn aead_key(variant: CipherSuiteVariant, key: &[u8]) -> Result<Self::AeadKey> {
+        let cipher = variant.try_into()?;
+        Ok(AeadKey {
+            encryption: keyed_context(cipher, key, true)?,
+            decryption: keyed_context(cipher, key, false)?,
+        })
+    }
+
+    fn seal(
+        key: &Self::AeadKey,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        io_buffer: &mut [u8],
+    ) -> Result<Vec<u8>> {
+        let mut tag = vec![0; AEAD_TAG_LEN];
+        copy_context(&key.encryption)
+            .and_then(|mut context| {
+                context.encrypt_init(None, None, Some(nonce))?;
+                context.cipher_update(aad, None)?;
+                context.cipher_update_inplace(io_buffer, io_buffer.len())?;
+                context.cipher_final(&mut [])?;
+                context.tag(&mut tag)
+            })
+            .map_err(|_| SframeError::EncryptionFailure)?;
+        Ok(tag)
+    }
+
+    fn open<'a>(
+        key: &Self::AeadKey,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        io_buffer: &'a mut [u8],
+        tag_len: usize,
+    ) -> Result<&'a mut [u8]> {
+        if tag_len > AEAD_TAG_LEN {
+            return Err(SframeError::DecryptionFailure);
+        }
+        let cipher_text_len = io_buffer
+            .len()
+            .checked_sub(tag_len)
+            .ok_or(SframeError::DecryptionFailure)?;
+        let (cipher_text, tag) = io_buffer.split_at_mut(cipher_text_len);
+
+        // OpenSSL compares the leading bytes of the full tag with a truncated tag
+        copy_context(&key.decryption)
+            .and_then(|mut context| {
+                context.decrypt_init(None, None, Some(nonce))?;
+                context.set_tag(tag)?;
+                context.cipher_update(aad, None)?;
+                context.cipher_update_inplace(cipher_text, cipher_text_len)?;
+                context.cipher_final(&mut [])
+            })
+            .map_err(|_| SframeError::DecryptionFailure)?;
+
+        Ok(cipher_text)
+    }
+
+    fn aes_ctr_key(key: &[u8]) -> Result<Self::AesCtrKey> {
+        keyed_context(Cipher::aes_128_ctr(), key, true)
+    }
+
+    fn aes_ctr(
+        key: &Self::AesCtrKey,
+        initial_counter: &[u8; AES_BLOCK_LEN],
+        buffer: &mut [u8],
+    ) -> Result<()> {
+        copy_context(key)
+            .and_then(|mut context| {
+                context.encrypt_init(None, None, Some(initial_counter))?;
+                context.cipher_update_inplace(buffer, buffer.len())
+            })
+            .map_err(|_| SframeError::EncryptionFailure)?;
+        Ok(())
+    }
+
+    fn hmac_key(key: &[u8]) -> Result<Self::HmacKey> {
+        PKey::hmac(key).map_err(|_| SframeError::KeyExpansion)
+    }
+
+    fn hmac(key: &Self::HmacKey, data: &[&[u8]]) -> Result<Vec<u8>> {
+        Signer::new(MessageDigest::sha256(), key)
+            .and_then(|mut signer| {
+                for data in data {
+                    signer.update(data)?;
+                }
+                signer.sign_to_vec()
+            })
+            .map_err(|_| SframeError::EncryptionFailure)
+    }
+
+    fn verify_tag(expected_tag: &[u8], auth_tag: &[u8]) -> Result<()> {
+        // memcmp::eq panics on different lengths
+        if expected_tag.len() == auth_tag.len() && openssl::memcmp::eq(expected_tag, auth_tag) {
+            Ok(())
+        } else {
+            Err(SframeError::DecryptionFailure)
+        }
+    }
+
+    fn hkdf_extract(hash: HashAlgorithm, salt: &[u8], ikm: &[u8]) -> Result<Self::Prk> {
+        let mut key = Zeroizing::new(Vec::new());
+        PkeyCtx::new_id(Id::HKDF)
+            .and_then(|mut context| {
+                context.derive_init()?;
+                context.set_hkdf_mode(HkdfMode::EXTRACT_ONLY)?;
+                context.set_hkdf_md(hash.into())?;
+                context.set_hkdf_salt(salt)?;
+                context.set_hkdf_key(ikm)?;
+                context.derive_to_vec(&mut key)
+            })
+            .map_err(|_| SframeError::KeyExpansion)?;
+        Ok(Prk { hash, key })
+    }
+
+    fn hkdf_prk(hash: HashAlgorithm, secret: &[u8]) -> Result<Self::Prk> {
+        Ok(Prk {
+            hash,
+            key: Zeroizing::new(secret.to_vec()),
+        })
+    }
+
+    fn hkdf_expand(prk: &Self::Prk, info: &[&[u8]], len: usize) -> Result<Zeroizing<Vec<u8>>> {
+        let mut okm = Zeroizing::new(vec![0_u8; len]);
+        PkeyCtx::new_id(Id::HKDF)
+            .and_then(|mut context| {
+                context.derive_init()?;
+                context.set_hkdf_mode(HkdfMode::EXPAND_ONLY)?;
+                context.set_hkdf_md(prk.hash.into())?;
+                context.set_hkdf_key(&prk.key)?;
+                for info in info {
+                    context.add_hkdf_info(info)?;
+                }
+                context.derive(Some(&mut okm))
+            })
+            .map_err(|_| SframeError::KeyExpansion)?;
+        Ok(okm)
+    }
+}
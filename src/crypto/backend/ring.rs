@@ -0,0 +1,177 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+use aes::cipher::{InnerIvInit, KeyInit, StreamCipher};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey};
+
+use alloc::{vec, vec::Vec};
+use zeroize::Zeroizing;
+
+use super::{
+    truncated_tag::TruncatedTagKey, CryptoBackend, HashAlgorithm, AEAD_TAG_LEN, AES_BLOCK_LEN,
+    NONCE_LEN,
+};
+use crate::{
+    crypto::cipher_suite::CipherSuiteVariant,
+    error::{Result, SframeError},
+};
+
+/// Crypto backend based on ring. As ring does not support AES-CTR, the `aes` and `ctr` crates are used instead.
+/// ring only verifies full authentication tags, so truncated tags of the AEAD cipher suites are verified
+/// with the `RustCrypto` crates as well.
+///
+/// The AEAD keys, HMAC keys and HKDF PRKs of ring are not wiped on drop, as ring does not support it.
+#[derive(Clone, Copy, Debug)]
+pub struct RingBackend;
+
+/// The AEAD key of ring for full tags, besides the key prepared for verifying truncated tags
+pub struct AeadKey {
+    aead: LessSafeKey,
+    truncated_tag_key: TruncatedTagKey,
+}
+
+struct OkmKeyLength(usize);
+
+impl ring::hkdf::KeyType for OkmKeyLength {
+    fn len(&self) -> usize {
+        self.0
+    }
+}
+
+impl From<HashAlgorithm> for ring::hkdf::Algorithm {
+    fn from(hash: HashAlgorithm) -> Self {
+        match hash {
+            HashAlgorithm::Sha256 => ring::hkdf::HKDF_SHA256,
+            HashAlgorithm::Sha512 => ring::hkdf::HKDF_SHA512,
+        }
+    }
+}
+
+impl TryFrom<CipherSuiteVariant> for &'static ring::aead::Algorithm {
+    type Error = SframeError;
+
+    fn try_from(variant: CipherSuiteVariant) -> Result<Self> {
+        use CipherSuiteVariant::*;
+        match variant {
+            AesGcm128Sha256 => Ok(&ring::aead::AES_128_GCM),
+            AesGcm256Sha512 => Ok(&ring::aead::AES_256_GCM),
+            ChaCha20Poly1305Sha256 => Ok(&ring::aead::CHACHA20_POLY1305),
+            AesCtr128HmacSha256_80 | AesCtr128HmacSha256_64 | AesCtr128HmacSha256_32 => {
+                Err(SframeError::KeyExpansion)
+            }
+        }
+    }
+}
+
+impl CryptoBackend for RingBackend {
+    type AeadKey = AeadKey;
+    type AesCtrKey = aes::Aes128;
+    type HmacKey = ring::hmac::Key;
+    type Prk = ring::hkdf::Prk;
+
+    fn aead_key(variant: CipherSuiteVariant, key: &[u8]) -> Result<Self::AeadKey> {
+        let aead = UnboundKey::new(variant.try_into()?, key)
+            .map(LessSafeKey::new)
+            .map_err(|_| SframeError::KeyExpansion)?;
+        Ok(AeadKey {
+            aead,
+            truncated_tag_key: TruncatedTagKey::new(variant, key)?,
+        })
+    }
+
+    fn seal(
+        key: &Self::AeadKey,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        io_buffer: &mut [u8],
+    ) -> Result<Vec<u8>> {
+        key.aead
+            .seal_in_place_separate_tag(
+                Nonce::assume_unique_for_key(*nonce),
+                Aad::from(aad),
+                io_buffer,
+            )
+            .map(|tag| tag.as_ref().to_vec())
+            .map_err(|_| SframeError::EncryptionFailure)
+    }
+
+    fn open<'a>(
+        key: &Self::AeadKey,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        io_buffer: &'a mut [u8],
+        tag_len: usize,
+    ) -> Result<&'a mut [u8]> {
+        if tag_len == AEAD_TAG_LEN {
+            return key
+                .aead
+                .open_in_place(
+                    Nonce::assume_unique_for_key(*nonce),
+                    Aad::from(aad),
+                    io_buffer,
+                )
+                .map_err(|_| SframeError::DecryptionFailure);
+        }
+
+        // ring only verifies full tags
+        let cipher_text_len = io_buffer
+            .len()
+            .checked_sub(tag_len)
+            .ok_or(SframeError::DecryptionFailure)?;
+        let (cipher_text, tag) = io_buffer.split_at_mut(cipher_text_len);
+        key.truncated_tag_key
+            .decrypt::<Self>(nonce, aad, cipher_text, tag)?;
+        Ok(cipher_text)
+    }
+
+    fn aes_ctr_key(key: &[u8]) -> Result<Self::AesCtrKey> {
+        aes::Aes128::new_from_slice(key).map_err(|_| SframeError::KeyExpansion)
+    }
+
+    fn aes_ctr(
+        key: &Self::AesCtrKey,
+        initial_counter: &[u8; AES_BLOCK_LEN],
+        buffer: &mut [u8],
+    ) -> Result<()> {
+        let mut cipher = ctr::Ctr32BE::from_core(ctr::CtrCore::inner_iv_init(
+            key.clone(),
+            initial_counter.into(),
+        ));
+        cipher
+            .try_apply_keystream(buffer)
+            .map_err(|_| SframeError::EncryptionFailure)
+    }
+
+    fn hmac_key(key: &[u8]) -> Result<Self::HmacKey> {
+        Ok(ring::hmac::Key::new(ring::hmac::HMAC_SHA256, key))
+    }
+
+    fn hmac(key: &Self::HmacKey, data: &[&[u8]]) -> Result<Vec<u8>> {
+        let mut context = ring::hmac::Context::with_key(key);
+        for data in data {
+            context.update(data);
+        }
+        Ok(context.sign().as_ref().to_vec())
+    }
+
+    fn verify_tag(expected_tag: &[u8], auth_tag: &[u8]) -> Result<()> {
+        ring::constant_time::verify_slices_are_equal(expected_tag, auth_tag)
+            .map_err(|_| SframeError::DecryptionFailure)
+    }
+
+    fn hkdf_extract(hash: HashAlgorithm, salt: &[u8], ikm: &[u8]) -> Result<Self::Prk> {
+        Ok(ring::hkdf::Salt::new(hash.into(), salt).extract(ikm))
+    }
+
+    fn hkdf_prk(hash: HashAlgorithm, secret: &[u8]) -> Result<Self::Prk> {
+        Ok(ring::hkdf::Prk::new_less_safe(hash.into(), secret))
+    }
+
+    fn hkdf_expand(prk: &Self::Prk, info: &[&[u8]], len: usize) -> Result<Zeroizing<Vec<u8>>> {
+        let mut okm = Zeroizing::new(vec![0_u8; len]);
+        prk.expand(info, OkmKeyLength(len))
+            .and_then(|expanded| expanded.fill(okm.as_mut_slice()))
+            .map_err(|_| SframeError::KeyExpansion)?;
+        Ok(okm)
+    }
+}
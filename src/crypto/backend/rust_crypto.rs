@@ -0,0 +1,192 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+use aes::cipher::{InnerIvInit, KeyInit, StreamCipher};
+use aes_gcm::{AeadInPlace, Aes128Gcm, Aes256Gcm};
+use chacha20poly1305::ChaCha20Poly1305;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Sha512};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use super::{
+    truncated_tag::TruncatedTagKey, CryptoBackend, HashAlgorithm, AEAD_TAG_LEN, AES_BLOCK_LEN,
+    NONCE_LEN,
+};
+use crate::{
+    crypto::cipher_suite::CipherSuiteVariant,
+    error::{Result, SframeError},
+};
+
+/// Crypto backend based on the pure rust crates of `RustCrypto`.
+///
+/// The AEAD and AES-CTR keys are wiped on drop, the HMAC keys and HKDF PRKs of the `hmac` and `hkdf` crates are not.
+#[derive(Clone, Copy, Debug)]
+pub struct RustCryptoBackend;
+
+/// The AEAD of the cipher suite for full tags, besides the key prepared for verifying truncated tags
+pub struct AeadKey {
+    aead: Aead,
+    truncated_tag_key: TruncatedTagKey,
+}
+
+// boxed, as the unused bytes of a smaller variant may hold stale copies of key material, which are not wiped
+enum Aead {
+    Aes128Gcm(Box<Aes128Gcm>),
+    Aes256Gcm(Box<Aes256Gcm>),
+    ChaCha20Poly1305(Box<ChaCha20Poly1305>),
+}
+
+pub enum Prk {
+    Sha256(Hkdf<Sha256>),
+    Sha512(Hkdf<Sha512>),
+}
+
+impl CryptoBackend for RustCryptoBackend {
+    type AeadKey = AeadKey;
+    type AesCtrKey = aes::Aes128;
+    type HmacKey = Hmac<Sha256>;
+    type Prk = Prk;
+
+    fn aead_key(variant: CipherSuiteVariant, key: &[u8]) -> Result<Self::AeadKey> {
+        let to_key_expansion_error = |_| SframeError::KeyExpansion;
+        let aead = match variant {
+            CipherSuiteVariant::AesGcm128Sha256 => {
+                Aes128Gcm::new_from_slice(key).map(|aead| Aead::Aes128Gcm(Box::new(aead)))
+            }
+            CipherSuiteVariant::AesGcm256Sha512 => {
+                Aes256Gcm::new_from_slice(key).map(|aead| Aead::Aes256Gcm(Box::new(aead)))
+            }
+            CipherSuiteVariant::ChaCha20Poly1305Sha256 => ChaCha20Poly1305::new_from_slice(key)
+                .map(|aead| Aead::ChaCha20Poly1305(Box::new(aead))),
+            CipherSuiteVariant::AesCtr128HmacSha256_80
+            | CipherSuiteVariant::AesCtr128HmacSha256_64
+            | CipherSuiteVariant::AesCtr128HmacSha256_32 => return Err(SframeError::KeyExpansion),
+        }
+        .map_err(to_key_expansion_error)?;
+
+        Ok(AeadKey {
+            aead,
+            truncated_tag_key: TruncatedTagKey::new(variant, key)?,
+        })
+    }
+
+    fn seal(
+        key: &Self::AeadKey,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        io_buffer: &mut [u8],
+    ) -> Result<Vec<u8>> {
+        let nonce = nonce.into();
+        match &key.aead {
+            Aead::Aes128Gcm(aead) => aead.encrypt_in_place_detached(nonce, aad, io_buffer),
+            Aead::Aes256Gcm(aead) => aead.encrypt_in_place_detached(nonce, aad, io_buffer),
+            Aead::ChaCha20Poly1305(aead) => aead.encrypt_in_place_detached(nonce, aad, io_buffer),
+        }
+        .map(|tag| tag.to_vec())
+        .map_err(|_| SframeError::EncryptionFailure)
+    }
+
+    fn open<'a>(
+        key: &Self::AeadKey,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        io_buffer: &'a mut [u8],
+        tag_len: usize,
+    ) -> Result<&'a mut [u8]> {
+        let cipher_text_len = io_buffer
+            .len()
+            .checked_sub(tag_len)
+            .ok_or(SframeError::DecryptionFailure)?;
+        let (cipher_text, tag) = io_buffer.split_at_mut(cipher_text_len);
+
+        if tag_len == AEAD_TAG_LEN {
+            let nonce = nonce.into();
+            let tag = (&*tag).into();
+            match &key.aead {
+                Aead::Aes128Gcm(aead) => {
+                    aead.decrypt_in_place_detached(nonce, aad, cipher_text, tag)
+                }
+                Aead::Aes256Gcm(aead) => {
+                    aead.decrypt_in_place_detached(nonce, aad, cipher_text, tag)
+                }
+                Aead::ChaCha20Poly1305(aead) => {
+                    aead.decrypt_in_place_detached(nonce, aad, cipher_text, tag)
+                }
+            }
+            .map_err(|_| SframeError::DecryptionFailure)?;
+        } else {
+            // the AEAD crates only verify full tags
+            key.truncated_tag_key
+                .decrypt::<Self>(nonce, aad, cipher_text, tag)?;
+        }
+
+        Ok(cipher_text)
+    }
+
+    fn aes_ctr_key(key: &[u8]) -> Result<Self::AesCtrKey> {
+        aes::Aes128::new_from_slice(key).map_err(|_| SframeError::KeyExpansion)
+    }
+
+    fn aes_ctr(
+        key: &Self::AesCtrKey,
+        initial_counter: &[u8; AES_BLOCK_LEN],
+        buffer: &mut [u8],
+    ) -> Result<()> {
+        let mut cipher = ctr::Ctr32BE::from_core(ctr::CtrCore::inner_iv_init(
+            key.clone(),
+            initial_counter.into(),
+        ));
+        cipher
+            .try_apply_keystream(buffer)
+            .map_err(|_| SframeError::EncryptionFailure)
+    }
+
+    fn hmac_key(key: &[u8]) -> Result<Self::HmacKey> {
+        <Hmac<Sha256> as Mac>::new_from_slice(key).map_err(|_| SframeError::KeyExpansion)
+    }
+
+    fn hmac(key: &Self::HmacKey, data: &[&[u8]]) -> Result<Vec<u8>> {
+        let mut mac = key.clone();
+        for data in data {
+            mac.update(data);
+        }
+        Ok(mac.finalize().into_bytes().to_vec())
+    }
+
+    fn verify_tag(expected_tag: &[u8], auth_tag: &[u8]) -> Result<()> {
+        if bool::from(expected_tag.ct_eq(auth_tag)) {
+            Ok(())
+        } else {
+            Err(SframeError::DecryptionFailure)
+        }
+    }
+
+    fn hkdf_extract(hash: HashAlgorithm, salt: &[u8], ikm: &[u8]) -> Result<Self::Prk> {
+        Ok(match hash {
+            HashAlgorithm::Sha256 => Prk::Sha256(Hkdf::new(Some(salt), ikm)),
+            HashAlgorithm::Sha512 => Prk::Sha512(Hkdf::new(Some(salt), ikm)),
+        })
+    }
+
+    fn hkdf_prk(hash: HashAlgorithm, secret: &[u8]) -> Result<Self::Prk> {
+        match hash {
+            HashAlgorithm::Sha256 => Hkdf::from_prk(secret).map(Prk::Sha256),
+            HashAlgorithm::Sha512 => Hkdf::from_prk(secret).map(Prk::Sha512),
+        }
+        .map_err(|_| SframeError::KeyExpansion)
+    }
+
+    fn hkdf_expand(prk: &Self::Prk, info: &[&[u8]], len: usize) -> Result<Zeroizing<Vec<u8>>> {
+        let mut okm = Zeroizing::new(vec![0_u8; len]);
+        match prk {
+            Prk::Sha256(hkdf) => hkdf.expand_multi_info(info, &mut okm),
+            Prk::Sha512(hkdf) => hkdf.expand_multi_info(info, &mut okm),
+        }
+        .map_err(|_| SframeError::KeyExpansion)?;
+        Ok(okm)
+    }
+}
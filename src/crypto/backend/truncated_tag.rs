@@ -0,0 +1,173 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+//! Verification of truncated authentication tags for the AEAD variants, see
+//! [RFC 9605 4.5](https://www.rfc-editor.org/rfc/rfc9605.html#name-cipher-suites),
+//! for backends whose AEAD only verifies full tags (ring and the AEAD crates of `RustCrypto`).
+//!
+//! The full tag is recomputed from the cipher text (GHASH for AES-GCM, Poly1305 for ChaCha20-Poly1305)
+//! and its leading bytes are compared to the received tag, before the cipher text is decrypted.
+
+use aes::cipher::{
+    consts::U16, BlockCipher, BlockEncrypt, BlockSizeUser, InnerIvInit, KeyInit, KeyIvInit,
+    StreamCipher, StreamCipherSeek,
+};
+use ghash::{universal_hash::UniversalHash, GHash};
+use poly1305::Poly1305;
+use zeroize::{Zeroize, Zeroizing};
+
+use alloc::boxed::Box;
+
+use super::{CryptoBackend, AES_BLOCK_LEN, NONCE_LEN};
+use crate::{
+    crypto::cipher_suite::CipherSuiteVariant,
+    error::{Result, SframeError},
+};
+
+const CHACHA20_KEY_LEN: usize = 32;
+const CHACHA20_BLOCK_LEN: u64 = 64;
+
+/// The key prepared once for verifying truncated tags, like the key of the AEAD
+// boxed, as the unused bytes of a smaller variant may hold stale copies of key material, which are not wiped
+pub enum TruncatedTagKey {
+    Aes128Gcm(Box<AesGcmKey<aes::Aes128>>),
+    Aes256Gcm(Box<AesGcmKey<aes::Aes256>>),
+    // ChaCha20 has no key schedule, so the key itself is kept
+    ChaCha20Poly1305(Zeroizing<[u8; CHACHA20_KEY_LEN]>),
+}
+
+impl TruncatedTagKey {
+    pub fn new(variant: CipherSuiteVariant, key: &[u8]) -> Result<Self> {
+        match variant {
+            CipherSuiteVariant::AesGcm128Sha256 => {
+                AesGcmKey::new(key).map(|key| Self::Aes128Gcm(Box::new(key)))
+            }
+            CipherSuiteVariant::AesGcm256Sha512 => {
+                AesGcmKey::new(key).map(|key| Self::Aes256Gcm(Box::new(key)))
+            }
+            CipherSuiteVariant::ChaCha20Poly1305Sha256 => key
+                .try_into()
+                .map(|key| Self::ChaCha20Poly1305(Zeroizing::new(key)))
+                .map_err(|_| SframeError::KeyExpansion),
+            CipherSuiteVariant::AesCtr128HmacSha256_80
+            | CipherSuiteVariant::AesCtr128HmacSha256_64
+            | CipherSuiteVariant::AesCtr128HmacSha256_32 => Err(SframeError::KeyExpansion),
+        }
+    }
+
+    /// Verifies the truncated `auth_tag` and decrypts the cipher text in place
+    pub fn decrypt<B: CryptoBackend>(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        cipher_text: &mut [u8],
+        auth_tag: &[u8],
+    ) -> Result<()> {
+        match self {
+            Self::Aes128Gcm(key) => key.decrypt::<B>(nonce, aad, cipher_text, auth_tag),
+            Self::Aes256Gcm(key) => key.decrypt::<B>(nonce, aad, cipher_text, auth_tag),
+            Self::ChaCha20Poly1305(key) => {
+                chacha20_poly1305::<B>(key, nonce, aad, cipher_text, auth_tag)
+            }
+        }
+    }
+}
+
+/// The expanded AES key schedule and the GHASH key
+pub struct AesGcmKey<Aes> {
+    cipher: Aes,
+    ghash: GHash,
+}
+
+impl<Aes> AesGcmKey<Aes>
+where
+    Aes: BlockCipher + BlockEncrypt + BlockSizeUser<BlockSize = U16> + KeyInit + Clone,
+{
+    fn new(key: &[u8]) -> Result<Self> {
+        let cipher = Aes::new_from_slice(key).map_err(|_| SframeError::KeyExpansion)?;
+
+        let mut hash_key = ghash::Block::default();
+        cipher.encrypt_block(&mut hash_key);
+        let ghash = GHash::new(&hash_key);
+        hash_key.as_mut_slice().zeroize();
+
+        Ok(AesGcmKey { cipher, ghash })
+    }
+
+    fn decrypt<B: CryptoBackend>(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        cipher_text: &mut [u8],
+        auth_tag: &[u8],
+    ) -> Result<()> {
+        let mut ghash = self.ghash.clone();
+        ghash.update_padded(aad);
+        ghash.update_padded(cipher_text);
+        let mut lengths = ghash::Block::default();
+        lengths[..8].copy_from_slice(&(aad.len() as u64 * 8).to_be_bytes());
+        lengths[8..].copy_from_slice(&(cipher_text.len() as u64 * 8).to_be_bytes());
+        ghash.update(&[lengths]);
+
+        // J0 = nonce || 0x00000001, the tag is encrypted with the keystream block of J0
+        let mut counter_block = [0u8; AES_BLOCK_LEN];
+        counter_block[..NONCE_LEN].copy_from_slice(nonce);
+        counter_block[AES_BLOCK_LEN - 1] = 1;
+        let mut tag_mask = ghash::Block::from(counter_block);
+        self.cipher.encrypt_block(&mut tag_mask);
+
+        let mut tag = ghash.finalize();
+        tag.iter_mut()
+            .zip(tag_mask.iter())
+            .for_each(|(tag, mask)| *tag ^= mask);
+        verify_truncated_tag::<B>(&tag, auth_tag)?;
+
+        // the payload is encrypted starting with J0 + 1
+        counter_block[AES_BLOCK_LEN - 1] = 2;
+        let mut ctr = ctr::Ctr32BE::from_core(ctr::CtrCore::inner_iv_init(
+            self.cipher.clone(),
+            &counter_block.into(),
+        ));
+        ctr.apply_keystream(cipher_text);
+
+        Ok(())
+    }
+}
+
+fn chacha20_poly1305<B: CryptoBackend>(
+    key: &[u8; CHACHA20_KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    aad: &[u8],
+    cipher_text: &mut [u8],
+    auth_tag: &[u8],
+) -> Result<()> {
+    let mut cipher = chacha20::ChaCha20::new(key.into(), nonce.into());
+
+    // the one time poly1305 key is taken from the first keystream block
+    let mut poly1305_key = poly1305::Key::default();
+    cipher.apply_keystream(&mut poly1305_key);
+
+    let mut poly1305 = Poly1305::new(&poly1305_key);
+    poly1305_key.as_mut_slice().zeroize();
+    poly1305.update_padded(aad);
+    poly1305.update_padded(cipher_text);
+    let mut lengths = poly1305::Block::default();
+    lengths[..8].copy_from_slice(&(aad.len() as u64).to_le_bytes());
+    lengths[8..].copy_from_slice(&(cipher_text.len() as u64).to_le_bytes());
+    poly1305.update(&[lengths]);
+
+    verify_truncated_tag::<B>(&poly1305.finalize(), auth_tag)?;
+
+    // the payload is encrypted starting with the second keystream block
+    cipher.seek(CHACHA20_BLOCK_LEN);
+    cipher.apply_keystream(cipher_text);
+
+    Ok(())
+}
+
+fn verify_truncated_tag<B: CryptoBackend>(full_tag: &[u8], auth_tag: &[u8]) -> Result<()> {
+    let expected_tag = full_tag
+        .get(..auth_tag.len())
+        .ok_or(SframeError::DecryptionFailure)?;
+    B::verify_tag(expected_tag, auth_tag)
+}
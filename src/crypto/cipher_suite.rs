@@ -1,49 +1,178 @@
 // Copyright (c) 2023 GoTo Group, Inc
 // SPDX-License-Identifier: Apache-2.0 AND MIT
 
+use crate::error::{Result, SframeError};
+
 /// Depicts which AEAD algorithm is used for encryption
 /// and which hashing function is used for the key expansion,
-/// see [sframe draft 00 4.4](https://datatracker.ietf.org/doc/html/draft-ietf-sframe-enc-00#name-ciphersuites)
+/// see [RFC 9605 4.5](https://www.rfc-editor.org/rfc/rfc9605.html#name-cipher-suites)
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum CipherSuiteVariant {
-    // /// counter mode is [not implemented in ring](https://github.com/briansmith/ring/issues/656)
-    // AesCm128HmacSha256_8,
-    // /// counter mode is [not implemented in ring](https://github.com/briansmith/ring/issues/656)
-    // AesCm128HmacSha256_4,
+    /// encryption: AES CTR 128 with HMAC SHA256 (80 bit tag), key expansion: HKDF with SHA256
+    AesCtr128HmacSha256_80,
+    /// encryption: AES CTR 128 with HMAC SHA256 (64 bit tag), key expansion: HKDF with SHA256
+    AesCtr128HmacSha256_64,
+    /// encryption: AES CTR 128 with HMAC SHA256 (32 bit tag), key expansion: HKDF with SHA256
+    AesCtr128HmacSha256_32,
     /// encryption: AES GCM 128, key expansion: HKDF with SHA256
     AesGcm128Sha256,
     /// encryption: AES GCM 256, key expansion: HKDF with SHA512
     AesGcm256Sha512,
+    /// encryption: ChaCha20-Poly1305, key expansion: HKDF with SHA256,
+    /// not registered with IANA, hence an id of the private use range is used
+    ChaCha20Poly1305Sha256,
 }
 
+/// Parameters of a [`CipherSuiteVariant`], created with [`From<CipherSuiteVariant>`]
+/// or with [`CipherSuite::with_auth_tag_len`] to truncate the authentication tag
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct CipherSuite {
+    /// the underlying variant
     pub variant: CipherSuiteVariant,
+    /// cipher suite identifier as registered with IANA, used in the key derivation
+    pub id: u16,
+    /// output size of the hash function in bytes (Nh)
     pub hash_len: usize,
+    /// size of the encryption key in bytes (Nk)
     pub key_len: usize,
+    /// size of the nonce in bytes (Nn)
     pub nonce_len: usize,
+    /// size of the authentication tag in bytes (Nt)
     pub auth_tag_len: usize,
 }
 
 impl From<CipherSuiteVariant> for CipherSuite {
     fn from(variant: CipherSuiteVariant) -> Self {
         match variant {
-            // CipherSuiteVariant::AesCm128HmacSha256_8 => unimplemented!(),
-            // CipherSuiteVariant::AesCm128HmacSha256_4 => unimplemented!(),
+            CipherSuiteVariant::AesCtr128HmacSha256_80 => CipherSuite {
+                variant,
+                id: 0x0001,
+                hash_len: 32,
+                key_len: 48,
+                nonce_len: 12,
+                auth_tag_len: 10,
+            },
+            CipherSuiteVariant::AesCtr128HmacSha256_64 => CipherSuite {
+                variant,
+                id: 0x0002,
+                hash_len: 32,
+                key_len: 48,
+                nonce_len: 12,
+                auth_tag_len: 8,
+            },
+            CipherSuiteVariant::AesCtr128HmacSha256_32 => CipherSuite {
+                variant,
+                id: 0x0003,
+                hash_len: 32,
+                key_len: 48,
+                nonce_len: 12,
+                auth_tag_len: 4,
+            },
             CipherSuiteVariant::AesGcm128Sha256 => CipherSuite {
                 variant,
+                id: 0x0004,
                 hash_len: 32,
                 key_len: 16,
                 nonce_len: 12,
-                auth_tag_len: 8,
+                auth_tag_len: 16,
             },
             CipherSuiteVariant::AesGcm256Sha512 => CipherSuite {
                 variant,
+                id: 0x0005,
                 hash_len: 64,
                 key_len: 32,
                 nonce_len: 12,
                 auth_tag_len: 16,
             },
+            CipherSuiteVariant::ChaCha20Poly1305Sha256 => CipherSuite {
+                variant,
+                id: 0xF000,
+                hash_len: 32,
+                key_len: 32,
+                nonce_len: 12,
+                auth_tag_len: 16,
+            },
+        }
+    }
+}
+
+impl CipherSuite {
+    /// Minimum length of a truncated authentication tag in bytes
+    pub const MIN_AUTH_TAG_LEN: usize = 4;
+
+    /// Creates a [`CipherSuite`] whose authentication tag is truncated to `auth_tag_len` bytes,
+    /// e.g. to save bandwidth on audio streams. This is only supported by the AEAD variants
+    /// (AES-GCM, ChaCha20-Poly1305) for lengths between [`CipherSuite::MIN_AUTH_TAG_LEN`] and the full tag length.
+    /// The tag length of the AES-CTR variants is defined by the variant itself.
+    pub fn with_auth_tag_len(variant: CipherSuiteVariant, auth_tag_len: usize) -> Result<Self> {
+        let cipher_suite = CipherSuite::from(variant);
+        let is_valid_len = if cipher_suite.is_ctr_mode() {
+            auth_tag_len == cipher_suite.auth_tag_len
+        } else {
+            (Self::MIN_AUTH_TAG_LEN..=cipher_suite.auth_tag_len).contains(&auth_tag_len)
+        };
+
+        if is_valid_len {
+            Ok(CipherSuite {
+                auth_tag_len,
+                ..cipher_suite
+            })
+        } else {
+            Err(SframeError::UnsupportedAuthTagLength(auth_tag_len))
         }
     }
+
+    /// AES-CTR suites derive an encryption and an authentication key from one secret
+    pub(crate) fn is_ctr_mode(&self) -> bool {
+        matches!(
+            self.variant,
+            CipherSuiteVariant::AesCtr128HmacSha256_80
+                | CipherSuiteVariant::AesCtr128HmacSha256_64
+                | CipherSuiteVariant::AesCtr128HmacSha256_32
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CipherSuite, CipherSuiteVariant};
+    use crate::error::SframeError;
+
+    #[test]
+    fn truncate_auth_tag_of_aead_variants() {
+        for variant in [
+            CipherSuiteVariant::AesGcm128Sha256,
+            CipherSuiteVariant::AesGcm256Sha512,
+            CipherSuiteVariant::ChaCha20Poly1305Sha256,
+        ] {
+            for auth_tag_len in CipherSuite::MIN_AUTH_TAG_LEN..=16 {
+                let cipher_suite = CipherSuite::with_auth_tag_len(variant, auth_tag_len).unwrap();
+                assert_eq!(cipher_suite.auth_tag_len, auth_tag_len);
+                assert_eq!(cipher_suite.variant, variant);
+            }
+        }
+    }
+
+    #[test]
+    fn reject_invalid_auth_tag_len() {
+        assert_eq!(
+            CipherSuite::with_auth_tag_len(CipherSuiteVariant::AesGcm256Sha512, 3),
+            Err(SframeError::UnsupportedAuthTagLength(3))
+        );
+        assert_eq!(
+            CipherSuite::with_auth_tag_len(CipherSuiteVariant::AesGcm128Sha256, 17),
+            Err(SframeError::UnsupportedAuthTagLength(17))
+        );
+    }
+
+    #[test]
+    fn keep_auth_tag_len_of_ctr_variants() {
+        assert!(
+            CipherSuite::with_auth_tag_len(CipherSuiteVariant::AesCtr128HmacSha256_64, 8).is_ok()
+        );
+        assert_eq!(
+            CipherSuite::with_auth_tag_len(CipherSuiteVariant::AesCtr128HmacSha256_64, 4),
+            Err(SframeError::UnsupportedAuthTagLength(4))
+        );
+    }
 }
@@ -1,124 +1,221 @@
 // Copyright (c) 2023 GoTo Group, Inc
 // SPDX-License-Identifier: Apache-2.0 AND MIT
 
-use super::{cipher_suite::CipherSuite, secret::Secret};
-use crate::error::Result;
-
-#[derive(Debug, Default, Clone, Copy)]
+use alloc::vec::Vec;
+use core::fmt;
+use zeroize::Zeroizing;
+
+use super::{
+    backend::{Backend, CryptoBackend, HashAlgorithm},
+    cipher_suite::CipherSuite,
+    secret::Secret,
+};
+use crate::{
+    error::{Result, SframeError},
+    header::KeyId,
+};
+
+#[derive(Default, Clone, Copy)]
 pub struct KeyMaterial<'a>(pub &'a [u8]);
 
-impl KeyMaterial<'_> {
-    pub fn expand_as_secret(&self, cipher_suite: &CipherSuite) -> Result<Secret> {
-        ring::expand_secret_from(self.0, cipher_suite)
+impl fmt::Debug for KeyMaterial<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // never print the key material
+        f.write_str("KeyMaterial(..)")
     }
 }
 
-const SFRAME_HKDF_SALT: &[u8] = "SFrame10".as_bytes();
-const SFRAME_HKDF_KEY_EXPAND_INFO: &[u8] = "key".as_bytes();
-const SFRAME_HDKF_SALT_EXPAND_INFO: &[u8] = "salt".as_bytes();
-
-mod ring {
-    use crate::{
-        crypto::{
-            cipher_suite::{CipherSuite, CipherSuiteVariant},
-            secret::Secret,
-        },
-        error::{Result, SframeError},
-    };
-
-    use super::{SFRAME_HDKF_SALT_EXPAND_INFO, SFRAME_HKDF_KEY_EXPAND_INFO, SFRAME_HKDF_SALT};
-
-    struct OkmKeyLength(usize);
-
-    impl ring::hkdf::KeyType for OkmKeyLength {
-        fn len(&self) -> usize {
-            self.0
-        }
+impl KeyMaterial<'_> {
+    /// derives the key and salt of a sender, identified by its key id,
+    /// see [RFC 9605 4.4.2](https://www.rfc-editor.org/rfc/rfc9605.html#name-key-derivation)
+    pub fn expand_as_secret(&self, cipher_suite: &CipherSuite, key_id: KeyId) -> Result<Secret> {
+        self.expand_as_secret_with::<Backend>(cipher_suite, key_id)
     }
 
-    impl From<&CipherSuite> for ring::hkdf::Algorithm {
-        fn from(cipher_suite: &CipherSuite) -> Self {
-            match cipher_suite.variant {
-                CipherSuiteVariant::AesGcm128Sha256 => ring::hkdf::HKDF_SHA256,
-                CipherSuiteVariant::AesGcm256Sha512 => ring::hkdf::HKDF_SHA512,
-            }
-        }
+    /// derives the base key of the next ratchet step,
+    /// see [RFC 9605 5.1](https://www.rfc-editor.org/rfc/rfc9605.html#name-sender-keys)
+    pub fn ratchet(&self, cipher_suite: &CipherSuite) -> Result<Zeroizing<Vec<u8>>> {
+        self.ratchet_with::<Backend>(cipher_suite)
     }
 
-    pub fn expand_secret_from(key_material: &[u8], cipher_suite: &CipherSuite) -> Result<Secret> {
-        let algorithm = cipher_suite.into();
-        let prk = ring::hkdf::Salt::new(algorithm, SFRAME_HKDF_SALT).extract(key_material);
-
-        let key = expand_key(&prk, SFRAME_HKDF_KEY_EXPAND_INFO, cipher_suite.key_len)?;
-        let salt = expand_key(&prk, SFRAME_HDKF_SALT_EXPAND_INFO, cipher_suite.nonce_len)?;
+    /// derives the base key of an MLS group member from the sframe epoch secret,
+    /// see [RFC 9605 5.2](https://www.rfc-editor.org/rfc/rfc9605.html#name-mls)
+    pub fn mls_sender_base_key(
+        &self,
+        cipher_suite: &CipherSuite,
+        index: u32,
+    ) -> Result<Zeroizing<Vec<u8>>> {
+        self.mls_sender_base_key_with::<Backend>(cipher_suite, index)
+    }
 
-        Ok(Secret { key, salt })
+    /// same as [`KeyMaterial::expand_as_secret`], with an explicit crypto backend
+    pub(crate) fn expand_as_secret_with<B: CryptoBackend>(
+        &self,
+        cipher_suite: &CipherSuite,
+        key_id: KeyId,
+    ) -> Result<Secret<B>> {
+        let prk = B::hkdf_extract(HashAlgorithm::from(cipher_suite), SFRAME_HKDF_SALT, self.0)?;
+
+        // the labels are suffixed with the KID and the cipher suite id, both encoded in big-endian
+        let key_id = u64::from(key_id).to_be_bytes();
+        let cipher_suite_id = cipher_suite.id.to_be_bytes();
+
+        let key = B::hkdf_expand(
+            &prk,
+            &[SFRAME_HKDF_KEY_EXPAND_INFO, &key_id, &cipher_suite_id],
+            cipher_suite.key_len,
+        )?;
+        let salt = B::hkdf_expand(
+            &prk,
+            &[SFRAME_HDKF_SALT_EXPAND_INFO, &key_id, &cipher_suite_id],
+            cipher_suite.nonce_len,
+        )?;
+
+        Secret::new(cipher_suite, key, salt)
     }
 
-    fn expand_key(prk: &ring::hkdf::Prk, info: &[u8], key_len: usize) -> Result<Vec<u8>> {
-        let mut sframe_key = vec![0_u8; key_len];
+    /// same as [`KeyMaterial::ratchet`], with an explicit crypto backend
+    pub(crate) fn ratchet_with<B: CryptoBackend>(
+        &self,
+        cipher_suite: &CipherSuite,
+    ) -> Result<Zeroizing<Vec<u8>>> {
+        let prk = B::hkdf_extract(HashAlgorithm::from(cipher_suite), SFRAME_HKDF_SALT, self.0)?;
+
+        B::hkdf_expand(
+            &prk,
+            &[SFRAME_HKDF_RATCHET_EXPAND_INFO],
+            cipher_suite.hash_len,
+        )
+    }
 
-        prk.expand(&[info], OkmKeyLength(key_len))
-            .and_then(|okm| okm.fill(sframe_key.as_mut_slice()))
-            .map_err(|_| SframeError::KeyExpansion)?;
+    /// same as [`KeyMaterial::mls_sender_base_key`], with an explicit crypto backend
+    pub(crate) fn mls_sender_base_key_with<B: CryptoBackend>(
+        &self,
+        cipher_suite: &CipherSuite,
+        index: u32,
+    ) -> Result<Zeroizing<Vec<u8>>> {
+        // the epoch secret is already the output of a KDF, hence it is used as PRK directly
+        if self.0.len() < cipher_suite.hash_len {
+            return Err(SframeError::KeyExpansion);
+        }
+        let prk = B::hkdf_prk(HashAlgorithm::from(cipher_suite), self.0)?;
 
-        Ok(sframe_key)
+        B::hkdf_expand(&prk, &[&index.to_be_bytes()], cipher_suite.hash_len)
     }
 }
 
+const SFRAME_HKDF_SALT: &[u8] = &[];
+const SFRAME_HKDF_KEY_EXPAND_INFO: &[u8] = "SFrame 1.0 Secret key ".as_bytes();
+const SFRAME_HDKF_SALT_EXPAND_INFO: &[u8] = "SFrame 1.0 Secret salt ".as_bytes();
+const SFRAME_HKDF_RATCHET_EXPAND_INFO: &[u8] = "SFrame 1.0 Ratchet".as_bytes();
+
 #[cfg(test)]
 mod test {
-    fn hex(hex_str: &str) -> Vec<u8> {
-        hex::decode(hex_str).unwrap()
-    }
+    use crate::{
+        crypto::{
+            backend::{test_with_backends, CryptoBackend},
+            cipher_suite::{CipherSuite, CipherSuiteVariant},
+            key_expansion::KeyMaterial,
+        },
+        error::SframeError,
+        header::KeyId,
+        test_vectors::sframe::get_test_vectors,
+        util::test::assert_bytes_eq,
+    };
 
-    mod aes_gcm_256_sha512 {
-        use crate::{
-            crypto::{
-                cipher_suite::{CipherSuite, CipherSuiteVariant},
-                key_expansion::{test::hex, KeyMaterial},
-            },
-            test_vectors::aes_gcm_256_sha512::get_test_vectors,
-            util::test::assert_bytes_eq,
-        };
-
-        #[test]
-        fn derive_correct_keys() {
-            // Test vectors are based on the same key, so we test only one here
-            let test_vector = &get_test_vectors()[0];
-            let secret = KeyMaterial(&test_vector.key_material)
-                .expand_as_secret(&CipherSuite::from(CipherSuiteVariant::AesGcm256Sha512))
+    test_with_backends!(
+        derive_correct_keys,
+        ratchet_key_material,
+        derive_different_keys_for_different_key_ids,
+        derive_mls_sender_base_keys,
+        fail_to_derive_mls_sender_base_key_from_short_secret
+    );
+
+    fn derive_correct_keys<B: CryptoBackend>() {
+        get_test_vectors().into_iter().for_each(|test_vector| {
+            let secret = KeyMaterial(&test_vector.base_key)
+                .expand_as_secret_with::<B>(
+                    &CipherSuite::from(test_vector.cipher_suite_variant),
+                    KeyId::from(test_vector.key_id),
+                )
                 .unwrap();
             assert_bytes_eq(&secret.key, &test_vector.key);
             assert_bytes_eq(&secret.salt, &test_vector.salt);
-        }
+        });
+    }
+
+    fn ratchet_key_material<B: CryptoBackend>() {
+        let key_material = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+
+        let ratcheted = KeyMaterial(&key_material)
+            .ratchet_with::<B>(&CipherSuite::from(CipherSuiteVariant::AesGcm128Sha256))
+            .unwrap();
+        assert_bytes_eq(
+            &ratcheted,
+            &hex::decode("fb75d8d5782da6c6cbf18ac43eca5da9e47f7e6ac7926a78e486226bd2af0f87")
+                .unwrap(),
+        );
+
+        let ratcheted = KeyMaterial(&key_material)
+            .ratchet_with::<B>(&CipherSuite::from(CipherSuiteVariant::AesGcm256Sha512))
+            .unwrap();
+        assert_bytes_eq(
+            &ratcheted,
+            &hex::decode("895fe5603750295ccbe0d5ed9745617b46e9cf9b428179b8f29f3147492bb08faa190560720ee0e4570760b64e7d5931120c391b7c7becc429ea35a9d07475aa")
+                .unwrap(),
+        );
+    }
 
-        #[test]
-        fn derive_keys_as_ts_aes_gcm128_sha256() {
-            let material = hex("303132333435363738393a3b3c3d3e3f");
-            let hex_key = hex("2ea2e8163ff56c0613e6fa9f20a213da");
-            let hex_salt = hex("a80478b3f6fba19983d540d5");
+    fn derive_different_keys_for_different_key_ids<B: CryptoBackend>() {
+        let test_vector = &get_test_vectors()[0];
+        let cipher_suite = CipherSuite::from(test_vector.cipher_suite_variant);
+        let key_material = KeyMaterial(&test_vector.base_key);
 
-            let key = KeyMaterial(&material);
-            let expanded = key
-                .expand_as_secret(&CipherSuite::from(CipherSuiteVariant::AesGcm128Sha256))
-                .unwrap();
-            assert_eq!(expanded.key, hex_key);
-            assert_eq!(expanded.salt, hex_salt);
-        }
+        let secret = key_material
+            .expand_as_secret_with::<B>(&cipher_suite, KeyId::from(1_u8))
+            .unwrap();
+        let other_secret = key_material
+            .expand_as_secret_with::<B>(&cipher_suite, KeyId::from(2_u8))
+            .unwrap();
 
-        #[test]
-        fn derive_keys_as_ts_aes_gcm_256_sha512() {
-            let material = hex("404142434445464748494a4b4c4d4e4f505152535455565758595a5b5c5d5e5f");
-            let hex_key = hex("436774b0b5ae45633d96547f8f3cb06c8e6628eff2e4255b5c4d77e721aa3355");
-            let hex_salt = hex("31ed26f90a072e6aee646298");
+        assert_ne!(secret.key, other_secret.key);
+        assert_ne!(secret.salt, other_secret.salt);
+    }
 
-            let key = KeyMaterial(&material);
-            let exp = key
-                .expand_as_secret(&CipherSuite::from(CipherSuiteVariant::AesGcm256Sha512))
+    fn derive_mls_sender_base_keys<B: CryptoBackend>() {
+        [
+            (
+                CipherSuiteVariant::AesGcm128Sha256,
+                "904bda2de46486b7a221d19a3905d3c44967ea015ea62d4b22d50040e2fd3c5f",
+            ),
+            (
+                CipherSuiteVariant::AesGcm256Sha512,
+                "fbf52d8942861ee61d34c3b7f19cad042d88b41b334f803ba35f3d071deae62eaac258f6e5f6f98b02ee35ac79df716717ee6e9bc873e3151c154a2d133082bf",
+            ),
+        ]
+        .into_iter()
+        .for_each(|(variant, expected)| {
+            let cipher_suite = CipherSuite::from(variant);
+            let epoch_secret = vec![0x42_u8; cipher_suite.hash_len];
+            let key_material = KeyMaterial(&epoch_secret);
+
+            let base_key = key_material
+                .mls_sender_base_key_with::<B>(&cipher_suite, 1)
                 .unwrap();
-            assert_eq!(exp.key, hex_key);
-            assert_eq!(exp.salt, hex_salt);
-        }
+            assert_bytes_eq(&base_key, &hex::decode(expected).unwrap());
+
+            let other_base_key = key_material
+                .mls_sender_base_key_with::<B>(&cipher_suite, 2)
+                .unwrap();
+            assert_ne!(base_key, other_base_key);
+        });
+    }
+
+    fn fail_to_derive_mls_sender_base_key_from_short_secret<B: CryptoBackend>() {
+        let cipher_suite = CipherSuite::from(CipherSuiteVariant::AesGcm128Sha256);
+        assert_eq!(
+            KeyMaterial(&[0x42; 31]).mls_sender_base_key_with::<B>(&cipher_suite, 1),
+            Err(SframeError::KeyExpansion)
+        );
     }
 }
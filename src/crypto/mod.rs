@@ -1,7 +1,12 @@
 // Copyright (c) 2023 GoTo Group, Inc
 // SPDX-License-Identifier: Apache-2.0 AND MIT
 
+#[cfg(feature = "alloc")]
 pub mod aead;
+#[cfg(feature = "alloc")]
+pub mod backend;
 pub mod cipher_suite;
+#[cfg(feature = "alloc")]
 pub mod key_expansion;
+#[cfg(feature = "alloc")]
 pub mod secret;
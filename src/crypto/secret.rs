@@ -1,11 +1,38 @@
-use crate::header::FrameCount;
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+use zeroize::{Zeroize, Zeroizing};
 
-pub struct Secret {
-    pub key: Vec<u8>,
-    pub salt: Vec<u8>,
+use super::{
+    aead::AeadKey,
+    backend::{Backend, CryptoBackend},
+    cipher_suite::CipherSuite,
+};
+use crate::{error::Result, header::FrameCount};
+
+/// The key and salt of a sender, which are wiped when the secret is dropped, e.g. when a key is replaced or removed.
+/// The key prepared by the crypto backend is only wiped if the backend supports it, see [`super::backend`]
+pub struct Secret<B: CryptoBackend = Backend> {
+    pub key: Zeroizing<Vec<u8>>,
+    pub salt: Zeroizing<Vec<u8>>,
+    /// the key prepared once for the AEAD of the cipher suite, so there is no per frame key setup.
+    /// It is boxed, as moving the secret would otherwise leave copies of it, which are not wiped.
+    pub(crate) aead_key: Box<AeadKey<B>>,
 }
 
-impl Secret {
+impl<B: CryptoBackend> Secret<B> {
+    pub(crate) fn new(
+        cipher_suite: &CipherSuite,
+        key: Zeroizing<Vec<u8>>,
+        salt: Zeroizing<Vec<u8>>,
+    ) -> Result<Self> {
+        let aead_key = Box::new(AeadKey::new(cipher_suite, &key)?);
+        Ok(Secret {
+            key,
+            salt,
+            aead_key,
+        })
+    }
+
     pub(crate) fn create_nonce<const LEN: usize>(&self, frame_count: &FrameCount) -> [u8; LEN] {
         debug_assert!(
             self.salt.len() >= LEN,
@@ -25,44 +52,80 @@ impl Secret {
     }
 }
 
+impl<B: CryptoBackend> Zeroize for Secret<B> {
+    fn zeroize(&mut self) {
+        self.key.zeroize();
+        self.salt.zeroize();
+    }
+}
+
+impl<B: CryptoBackend> fmt::Debug for Secret<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // never print the key material
+        f.debug_struct("Secret").finish_non_exhaustive()
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use zeroize::Zeroize;
+
     use crate::{
-        crypto::{
-            cipher_suite::{CipherSuite, CipherSuiteVariant},
-            key_expansion::KeyMaterial,
-        },
-        header::FrameCount,
-        test_vectors::*,
+        crypto::{cipher_suite::CipherSuite, key_expansion::KeyMaterial},
+        header::{FrameCount, KeyId},
+        test_vectors::sframe::get_test_vectors,
         util::test::assert_bytes_eq,
     };
 
     const NONCE_LEN: usize = 12;
 
-    fn test_nonce(cipher_suite: CipherSuite, test_vector: TestVector) {
-        let secret = KeyMaterial(&test_vector.key_material)
-            .expand_as_secret(&cipher_suite)
-            .unwrap();
-        let nonce: [u8; NONCE_LEN] =
-            secret.create_nonce(&FrameCount::from(test_vector.frame_count));
+    #[test]
+    fn create_correct_nonce() {
+        get_test_vectors().into_iter().for_each(|test_vector| {
+            let secret = KeyMaterial(&test_vector.base_key)
+                .expand_as_secret(
+                    &CipherSuite::from(test_vector.cipher_suite_variant),
+                    KeyId::from(test_vector.key_id),
+                )
+                .unwrap();
+            let nonce: [u8; NONCE_LEN] =
+                secret.create_nonce(&FrameCount::from(test_vector.frame_count));
 
-        assert_bytes_eq(&nonce, &test_vector.nonce);
+            assert_bytes_eq(&nonce, &test_vector.nonce);
+        });
     }
 
     #[test]
-    fn create_correct_nonce_aes_gcm_128_sha256() {
-        aes_gcm_128_sha256::get_test_vectors()
-            .into_iter()
-            .for_each(|test_vector| {
-                test_nonce(CipherSuiteVariant::AesGcm128Sha256.into(), test_vector);
-            });
+    fn wipe_key_and_salt() {
+        let test_vector = &get_test_vectors()[0];
+        let mut secret = KeyMaterial(&test_vector.base_key)
+            .expand_as_secret(
+                &CipherSuite::from(test_vector.cipher_suite_variant),
+                KeyId::from(test_vector.key_id),
+            )
+            .unwrap();
+        assert!(secret.key.iter().any(|byte| *byte != 0));
+
+        secret.zeroize();
+
+        assert!(secret.key.is_empty());
+        assert!(secret.salt.is_empty());
     }
+
     #[test]
-    fn create_correct_nonce_aes_gcm_256_sha512() {
-        aes_gcm_256_sha512::get_test_vectors()
-            .into_iter()
-            .for_each(|test_vector| {
-                test_nonce(CipherSuiteVariant::AesGcm256Sha512.into(), test_vector);
-            });
+    fn do_not_print_key_material() {
+        let test_vector = &get_test_vectors()[0];
+        let secret = KeyMaterial(&test_vector.base_key)
+            .expand_as_secret(
+                &CipherSuite::from(test_vector.cipher_suite_variant),
+                KeyId::from(test_vector.key_id),
+            )
+            .unwrap();
+
+        assert_eq!(format!("{secret:?}"), "Secret { .. }");
+        assert_eq!(
+            format!("{:?}", KeyMaterial(&test_vector.base_key)),
+            "KeyMaterial(..)"
+        );
     }
 }
@@ -1,39 +1,208 @@
 // Copyright (c) 2023 GoTo Group, Inc
 // SPDX-License-Identifier: Apache-2.0 AND MIT
 
-use crate::header::KeyId;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+use core::fmt;
+
+use crate::header::{FrameCount, KeyId};
 
 /// Represents either success(T) or an failure ([`SframeError`])
-pub type Result<T> = std::result::Result<T, SframeError>;
+pub type Result<T> = core::result::Result<T, SframeError>;
 
 /// Represents an error which has occured in the sframe-rs library
-#[derive(PartialEq, Eq, Debug, thiserror::Error)]
+///
+/// The enum is non exhaustive, as some variants only exist with the `alloc` feature.
+// not `Copy`, as the message of a failed frame validation is owned with the `alloc` feature
+#[allow(missing_copy_implementations)]
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
 pub enum SframeError {
     /// [`Sender`] has no valid encryption key set
-    #[error("No EncryptionKey has been set")]
     MissingEncryptionKey,
 
     /// `Receiver` has no valid encryption key set
-    #[error("No DecryptionKey has been found")]
     MissingDecryptionKey(KeyId),
 
     /// Failed to decrypt a frame with AEAD
-    #[error("Failed to Decrypt")]
     DecryptionFailure,
 
     /// Failed to encrypt a frame with AEAD
-    #[error("Failed to Encrypt")]
     EncryptionFailure,
 
     /// Could not expand encryption key for [`Sender`] or decryption key for [`Receiver`] with HKDF
-    #[error("Unable to create unbound encryption key")]
     KeyExpansion,
 
-    /// frame validation failed in the [`Receiver`] before decryption
-    #[error("{0}")]
+    /// The authentication tag length is not supported by the cipher suite
+    UnsupportedAuthTagLength(usize),
+
+    /// The key id can not be split into a sender part and a ratchet generation
+    InvalidRatchetingKeyId,
+
+    /// Ratcheting is not possible, as the [`Sender`] was not created with a ratcheting key id
+    RatchetingNotConfigured,
+
+    /// Expiry by time is not possible, as the `Receiver` has no [`crate::clock::Clock`]
+    ClockNotConfigured,
+
+    /// The codec specific header of a video frame is truncated or invalid,
+    /// or a VP9 frame refers to a frame size which is not known
+    InvalidCodecFrame,
+
+    /// The KID layout for MLS is invalid, or the member index or context does not fit into it
+    InvalidMlsKeyId,
+
+    /// More keys are set at once than the `Receiver` may store, e.g. the members of an MLS epoch
+    TooManyKeys {
+        /// nof keys to be set
+        keys: usize,
+        /// maximum nof keys of the `Receiver`
+        max_keys: usize,
+    },
+
+    /// All frame counts of the [`Sender`] have been used with the current key,
+    /// continuing would reuse nonces. A new key has to be set.
+    FrameCountExhausted,
+
+    /// Encrypting the frame would exceed the hard usage limit of the current key of the [`Sender`],
+    /// a new key has to be set
+    KeyUsageLimitExceeded,
+
+    /// The buffer provided for the encrypted or decrypted frame is too small
+    BufferTooSmall {
+        /// nof bytes required
+        needed: usize,
+        /// nof bytes provided
+        available: usize,
+    },
+
+    /// The sframe header is truncated, either the config byte is missing or the data is shorter
+    /// than announced by the KLEN and LEN fields
+    HeaderTooShort {
+        /// nof bytes of the header as announced by the config byte
+        needed: usize,
+        /// nof bytes left in the frame
+        available: usize,
+    },
+
+    /// The extended KID flag of the config byte does not match the header type which is deserialized
+    InvalidHeaderType {
+        /// `true` if the extended KID flag is set
+        extended: bool,
+    },
+
+    /// The number of bytes to skip exceeds the length of the frame
+    SkipTooLarge {
+        /// nof bytes to skip
+        skip: usize,
+        /// nof bytes of the frame
+        frame_len: usize,
+    },
+
+    /// The frame count is older than the replay window of the [`Receiver`] allows
+    FrameTooOld {
+        /// frame count of the rejected frame
+        frame_count: FrameCount,
+        /// newest frame count accepted so far
+        newest_frame_count: FrameCount,
+    },
+
+    /// The frame count has already been received within the replay window of the [`Receiver`]
+    ReplayedFrame {
+        /// frame count of the rejected frame
+        frame_count: FrameCount,
+        /// newest frame count accepted so far
+        newest_frame_count: FrameCount,
+    },
+
+    /// frame validation failed in the [`Receiver`] before decryption, returned by custom validations
+    #[cfg(feature = "alloc")]
     FrameValidationFailed(String),
+}
 
-    /// any arbitrary error
-    #[error("{0}")]
-    Other(String),
+impl fmt::Display for SframeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SframeError::MissingEncryptionKey => write!(f, "No EncryptionKey has been set"),
+            SframeError::MissingDecryptionKey(key_id) => {
+                write!(
+                    f,
+                    "No DecryptionKey has been found for KID {}",
+                    u64::from(*key_id)
+                )
+            }
+            SframeError::DecryptionFailure => write!(f, "Failed to Decrypt"),
+            SframeError::EncryptionFailure => write!(f, "Failed to Encrypt"),
+            SframeError::KeyExpansion => write!(f, "Unable to create unbound encryption key"),
+            SframeError::UnsupportedAuthTagLength(len) => {
+                write!(f, "Unsupported authentication tag length of {len} bytes")
+            }
+            SframeError::InvalidRatchetingKeyId => write!(f, "Invalid ratcheting key id"),
+            SframeError::RatchetingNotConfigured => write!(f, "Ratcheting is not configured"),
+            SframeError::ClockNotConfigured => write!(f, "No clock is configured"),
+            SframeError::InvalidCodecFrame => write!(f, "Invalid codec specific frame header"),
+            SframeError::InvalidMlsKeyId => write!(f, "Invalid MLS key id"),
+            SframeError::TooManyKeys { keys, max_keys } => {
+                write!(f, "Cannot set {keys} keys, at most {max_keys} are stored")
+            }
+            SframeError::FrameCountExhausted => {
+                write!(f, "Frame count exhausted, a new encryption key is required")
+            }
+            SframeError::KeyUsageLimitExceeded => {
+                write!(
+                    f,
+                    "Key usage limit exceeded, a new encryption key is required"
+                )
+            }
+            SframeError::BufferTooSmall { needed, available } => {
+                write!(
+                    f,
+                    "Buffer too small, {needed} bytes are required, but only {available} are available"
+                )
+            }
+            SframeError::HeaderTooShort { needed, available } => {
+                write!(
+                    f,
+                    "Header too short, {needed} bytes are required, but only {available} are available"
+                )
+            }
+            SframeError::InvalidHeaderType { extended: true } => {
+                write!(f, "Invalid basic header, the extended KID flag is set")
+            }
+            SframeError::InvalidHeaderType { extended: false } => {
+                write!(
+                    f,
+                    "Invalid extended header, the extended KID flag is not set"
+                )
+            }
+            SframeError::SkipTooLarge { skip, frame_len } => {
+                write!(
+                    f,
+                    "Cannot skip {skip} bytes of a frame with {frame_len} bytes"
+                )
+            }
+            SframeError::FrameTooOld {
+                frame_count,
+                newest_frame_count,
+            } => write!(
+                f,
+                "Replay check failed, frame count {} is too old compared to {}",
+                u64::from(*frame_count),
+                u64::from(*newest_frame_count)
+            ),
+            SframeError::ReplayedFrame {
+                frame_count,
+                newest_frame_count,
+            } => write!(
+                f,
+                "Replay check failed, frame count {} has already been received (newest is {})",
+                u64::from(*frame_count),
+                u64::from(*newest_frame_count)
+            ),
+            #[cfg(feature = "alloc")]
+            SframeError::FrameValidationFailed(reason) => write!(f, "{reason}"),
+        }
+    }
 }
+
+impl core::error::Error for SframeError {}
@@ -5,64 +5,141 @@ use crate::{
     error::{Result, SframeError},
     header::{FrameCount, Header, HeaderFields},
 };
-use std::cell::Cell;
+use alloc::{vec, vec::Vec};
 
-/// Allows to validate frames by their sframe header before the decryption
-pub trait FrameValidation {
+/// Allows to validate frames by their sframe header before the decryption.
+/// Validations are `Send + Sync`, so receivers can be moved to and shared between threads.
+pub trait FrameValidation: Send + Sync {
     /// checks if the new header is valid, returns an [`SframeError`] if not
     fn validate(&self, header: &Header) -> Result<()>;
+
+    /// called after the frame of a valid header has been decrypted successfully,
+    /// which allows to update the validation state only with authenticated frames
+    fn accept(&mut self, _header: &Header) {}
 }
 
-/// This implementation allows to detect replay attacks by omitting frames with
-/// to old frame counters. The window of allowed frame counts is given with a
-/// certain tolerance.
+/// This implementation allows to detect replay attacks with a sliding window
+/// over the frame counts, similar to the anti-replay window of `IPsec` or `SRTP`.
+/// Frames which are older than the newest received frame by more than a given tolerance
+/// are omitted, as well as frames whose frame count has already been received within the window.
+/// Counters which wrapped around are considered as newer, as long as they are less than half of the
+/// counter range ahead.
 pub struct ReplayAttackProtection {
     tolerance: u64,
-    last_frame_count: Cell<FrameCount>,
+    newest_frame_count: Option<FrameCount>,
+    received: ReceivedWindow,
 }
 
 impl ReplayAttackProtection {
-    /// creates a [`ReplayAttackProtection`] with a given tolerance for the frame count
+    /// Maximum tolerance, limiting the size of the window to 8 KiB
+    pub const MAX_TOLERANCE: u64 = (1 << 16) - 1;
+
+    /// creates a [`ReplayAttackProtection`] with a given tolerance for the frame count,
+    /// which is limited to [`ReplayAttackProtection::MAX_TOLERANCE`]
     pub fn with_tolerance(tolerance: u64) -> Self {
+        let tolerance = tolerance.min(Self::MAX_TOLERANCE);
         ReplayAttackProtection {
             tolerance,
-            last_frame_count: Cell::new(0.into()),
+            newest_frame_count: None,
+            received: ReceivedWindow::with_size(tolerance),
         }
     }
+
+    /// Returns the age of a frame compared to the newest frame, or `None` if the frame is newer.
+    /// Frame counts up to half of the counter range ahead are considered as newer, to handle wraparounds.
+    fn age(&self, frame_count: FrameCount) -> Option<u64> {
+        let newest_frame_count = u64::from(self.newest_frame_count?);
+        let age = newest_frame_count.wrapping_sub(u64::from(frame_count));
+        (age <= u64::MAX / 2).then_some(age)
+    }
 }
 
 impl FrameValidation for ReplayAttackProtection {
     fn validate(&self, header: &Header) -> Result<()> {
-        let last_frame_count = self.last_frame_count.get();
-        let current_frame_count = header.frame_count();
+        let frame_count = header.frame_count();
+        let Some(newest_frame_count) = self.newest_frame_count else {
+            return Ok(());
+        };
+        match self.age(frame_count) {
+            Some(age) if age > self.tolerance => Err(SframeError::FrameTooOld {
+                frame_count,
+                newest_frame_count,
+            }),
+            Some(_) if self.received.contains(frame_count) => Err(SframeError::ReplayedFrame {
+                frame_count,
+                newest_frame_count,
+            }),
+            _ => Ok(()),
+        }
+    }
 
-        if current_frame_count > last_frame_count {
-            // frame is fine and fresh
-            self.last_frame_count.set(current_frame_count);
-            Ok(())
+    fn accept(&mut self, header: &Header) {
+        let frame_count = header.frame_count();
+        match self.age(frame_count) {
+            Some(age) if age <= self.tolerance => self.received.insert(frame_count),
+            Some(_) => {}
+            None => {
+                // advance the window, but never rewind it
+                let steps = self.newest_frame_count.map_or(u64::MAX, |newest| {
+                    u64::from(frame_count).wrapping_sub(u64::from(newest))
+                });
+                self.received.advance(frame_count, steps);
+                self.newest_frame_count = Some(frame_count);
+            }
+        }
+    }
+}
+
+/// Bitmap of the received frame counts within the window, indexed by the frame count modulo the bitmap size.
+/// The size is a power of two, so frame counts keep their position when the counter wraps around.
+struct ReceivedWindow {
+    bits: Vec<u64>,
+}
+
+impl ReceivedWindow {
+    fn with_size(tolerance: u64) -> Self {
+        let nof_bits = (tolerance + 1).next_power_of_two();
+        let nof_words = nof_bits.div_ceil(u64::from(u64::BITS));
+        ReceivedWindow {
+            bits: vec![0; nof_words as usize],
+        }
+    }
+
+    fn nof_bits(&self) -> u64 {
+        self.bits.len() as u64 * u64::from(u64::BITS)
+    }
+
+    fn position(&self, frame_count: FrameCount) -> (usize, u64) {
+        let index = u64::from(frame_count) % self.nof_bits();
+        (
+            (index / u64::from(u64::BITS)) as usize,
+            1 << (index % u64::from(u64::BITS)),
+        )
+    }
+
+    fn contains(&self, frame_count: FrameCount) -> bool {
+        let (word, mask) = self.position(frame_count);
+        self.bits[word] & mask != 0
+    }
+
+    fn insert(&mut self, frame_count: FrameCount) {
+        let (word, mask) = self.position(frame_count);
+        self.bits[word] |= mask;
+    }
+
+    /// advances the window by `steps` to the new newest frame count, forgetting the
+    /// received frame counts which drop out of the window
+    fn advance(&mut self, newest_frame_count: FrameCount, steps: u64) {
+        if steps >= self.nof_bits() {
+            self.bits.fill(0);
         } else {
-            // frame old
-            let age = last_frame_count - current_frame_count;
-
-            if age <= self.tolerance {
-                self.last_frame_count.set(current_frame_count);
-                Ok(())
-            } else {
-                // maybe there was an overflow
-                let dist_to_overflow = u64::MAX - last_frame_count;
-                let overflow_age = current_frame_count + dist_to_overflow;
-
-                // no it's just too old
-                if overflow_age <= self.tolerance {
-                    self.last_frame_count.set(current_frame_count);
-                    Ok(())
-                } else {
-                    Err(SframeError::FrameValidationFailed(
-                        "Replay check failed, frame counter too old".to_string(),
-                    ))
-                }
+            let newest_frame_count = u64::from(newest_frame_count);
+            for step in 0..steps {
+                let (word, mask) = self.position(newest_frame_count.wrapping_sub(step).into());
+                self.bits[word] &= !mask;
             }
         }
+        self.insert(newest_frame_count);
     }
 }
 
@@ -70,51 +147,144 @@ impl FrameValidation for ReplayAttackProtection {
 mod test {
     use super::*;
 
+    fn receive(validator: &mut ReplayAttackProtection, frame_count: u64) -> Result<()> {
+        let header = Header::with_frame_count(23456789u64, frame_count);
+        validator.validate(&header)?;
+        validator.accept(&header);
+        Ok(())
+    }
+
     #[test]
     fn accept_newer_headers() {
-        let validator = ReplayAttackProtection::with_tolerance(128);
-        let first_header = Header::with_frame_count(23456789u64, 2400);
-        let second_header = Header::with_frame_count(23456789u64, 2480);
+        let mut validator = ReplayAttackProtection::with_tolerance(128);
 
-        assert_eq!(validator.validate(&first_header), Ok(()));
-        assert_eq!(validator.validate(&second_header), Ok(()));
+        assert_eq!(receive(&mut validator, 2400), Ok(()));
+        assert_eq!(receive(&mut validator, 2480), Ok(()));
     }
 
     #[test]
     fn accept_older_headers_in_tolerance() {
-        let validator = ReplayAttackProtection::with_tolerance(128);
-        let first_header = Header::with_frame_count(23456789u64, 2480);
-        let late_header = Header::with_frame_count(23456789u64, 2400);
+        let mut validator = ReplayAttackProtection::with_tolerance(128);
 
-        assert_eq!(validator.validate(&first_header), Ok(()));
-        assert_eq!(validator.validate(&late_header), Ok(()));
+        assert_eq!(receive(&mut validator, 2480), Ok(()));
+        assert_eq!(receive(&mut validator, 2400), Ok(()));
     }
 
     #[test]
     fn reject_too_old_headers() {
-        let validator = ReplayAttackProtection::with_tolerance(128);
-        let first_header = Header::with_frame_count(23456789u64, 2480);
-        let too_late_header = Header::with_frame_count(23456789u64, 1024);
+        let mut validator = ReplayAttackProtection::with_tolerance(128);
 
-        assert_eq!(validator.validate(&first_header), Ok(()));
-        assert!(matches!(
-            validator.validate(&too_late_header),
-            Err(SframeError::FrameValidationFailed(_))
-        ));
+        assert_eq!(receive(&mut validator, 2480), Ok(()));
+        assert_eq!(
+            receive(&mut validator, 1024),
+            Err(SframeError::FrameTooOld {
+                frame_count: FrameCount::from(1024),
+                newest_frame_count: FrameCount::from(2480),
+            })
+        );
+    }
+
+    #[test]
+    fn reject_duplicated_headers() {
+        let mut validator = ReplayAttackProtection::with_tolerance(128);
+
+        for (frame_count, newest_frame_count) in
+            [(2400, 2400), (2480, 2480), (2479, 2480), (2401, 2480)]
+        {
+            assert_eq!(receive(&mut validator, frame_count), Ok(()));
+            assert_eq!(
+                receive(&mut validator, frame_count),
+                Err(SframeError::ReplayedFrame {
+                    frame_count: FrameCount::from(frame_count),
+                    newest_frame_count: FrameCount::from(newest_frame_count),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn accept_reordered_headers_once() {
+        let mut validator = ReplayAttackProtection::with_tolerance(128);
+        let frame_counts = [5, 3, 4, 1, 2, 0, 9, 7, 8, 6];
+
+        for frame_count in frame_counts {
+            assert_eq!(receive(&mut validator, frame_count), Ok(()));
+        }
+        for frame_count in frame_counts {
+            assert!(receive(&mut validator, frame_count).is_err());
+        }
+    }
+
+    #[test]
+    fn do_not_rewind_window_with_late_headers() {
+        let mut validator = ReplayAttackProtection::with_tolerance(128);
+
+        assert_eq!(receive(&mut validator, 2480), Ok(()));
+        assert_eq!(receive(&mut validator, 2400), Ok(()));
+        // still too old compared to the newest frame count
+        assert!(receive(&mut validator, 2351).is_err());
+        assert_eq!(receive(&mut validator, 2352), Ok(()));
+    }
+
+    #[test]
+    fn forget_headers_dropping_out_of_window() {
+        let mut validator = ReplayAttackProtection::with_tolerance(128);
+
+        assert_eq!(receive(&mut validator, 0), Ok(()));
+        assert_eq!(receive(&mut validator, 1), Ok(()));
+        assert_eq!(receive(&mut validator, 129), Ok(()));
+        // 0 is out of the window, 1 is still a duplicate
+        assert!(receive(&mut validator, 0).is_err());
+        assert!(receive(&mut validator, 1).is_err());
+        // a large jump clears the whole window
+        assert_eq!(receive(&mut validator, 10_000), Ok(()));
+        assert_eq!(receive(&mut validator, 9_900), Ok(()));
+    }
+
+    #[test]
+    fn only_update_with_accepted_headers() {
+        let mut validator = ReplayAttackProtection::with_tolerance(128);
+        let header = Header::with_frame_count(23456789u64, 2480);
+
+        // e.g. decryption failed
+        assert_eq!(validator.validate(&header), Ok(()));
+        assert_eq!(receive(&mut validator, 1024), Ok(()));
+        assert_eq!(receive(&mut validator, 2480), Ok(()));
     }
 
     #[test]
     fn handle_overflowing_counters() {
-        let validator = ReplayAttackProtection::with_tolerance(128);
+        let mut validator = ReplayAttackProtection::with_tolerance(128);
         let start_count = u64::MAX - 3;
-        let first_header = Header::with_frame_count(23456789u64, start_count);
 
-        assert_eq!(validator.validate(&first_header), Ok(()));
+        assert_eq!(receive(&mut validator, start_count), Ok(()));
 
-        for step in 0..10 {
+        for step in 1..10 {
             let late_count = start_count.wrapping_add(step); // using this instead of `+` to avoid overflow panic in debug
-            let too_late_header = Header::with_frame_count(23456789u64, late_count);
-            assert_eq!(validator.validate(&too_late_header), Ok(()));
+            assert_eq!(receive(&mut validator, late_count), Ok(()));
         }
+
+        // duplicates before and after the wraparound
+        assert!(receive(&mut validator, u64::MAX - 1).is_err());
+        assert!(receive(&mut validator, 2).is_err());
+        assert_eq!(receive(&mut validator, 6), Ok(()));
+    }
+
+    #[test]
+    fn keep_positions_of_frame_counts_across_wraparound() {
+        let mut validator = ReplayAttackProtection::with_tolerance(128);
+
+        assert_eq!(receive(&mut validator, u64::MAX - 60), Ok(()));
+        assert_eq!(receive(&mut validator, 10), Ok(()));
+        // would share the position of 10 in a bitmap of 192 bits, as 2^64 is not a multiple of it
+        assert_eq!(receive(&mut validator, u64::MAX - 53), Ok(()));
+        assert!(receive(&mut validator, u64::MAX - 53).is_err());
+        assert!(receive(&mut validator, 10).is_err());
+    }
+
+    #[test]
+    fn limit_tolerance() {
+        let validator = ReplayAttackProtection::with_tolerance(u64::MAX);
+        assert_eq!(validator.tolerance, ReplayAttackProtection::MAX_TOLERANCE);
     }
 }
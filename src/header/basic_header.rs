@@ -1,32 +1,33 @@
 // Copyright (c) 2023 GoTo Group, Inc
 // SPDX-License-Identifier: Apache-2.0 AND MIT
 
+#![allow(clippy::unusual_byte_groupings)]
 use bitfield::bitfield;
 
-use crate::error::SframeError;
+use crate::error::{Result, SframeError};
 
 use super::{
-    keyid::BasicKeyId, BasicHeader, Deserialization, FrameCount, HeaderFields, Serialization,
-    LEN_OFFSET,
+    keyid::BasicKeyId,
+    util::{as_min_be_bytes, from_be_bytes, trailing_len_in_bytes},
+    BasicHeader, Deserialization, FrameCount, HeaderFields, Serialization, MAX_INLINE_VALUE,
 };
 
 bitfield! {
-    /// Modeled after [sframe draft 00 4.2](https://datatracker.ietf.org/doc/html/draft-ietf-sframe-enc-00#name-sframe-header)
+    /// Modeled after [RFC 9605 4.3](https://www.rfc-editor.org/rfc/rfc9605.html#name-sframe-header)
     /// ```txt
     ///  0 1 2 3 4 5 6 7
     /// +-+-+-+-+-+-+-+-+---------------------------------+
-    /// |R|LEN  |0| KID |    CTR... (length=LEN)          |
+    /// |0| KID |Y|  C  |    CTR... (length=C+1 if Y)     |
     /// +-+-+-+-+-+-+-+-+---------------------------------+
     /// ```
     ///
     struct BasicHeaderBitfield(MSB0 [u8]);
     impl Debug;
     u8;
-    get_reserved, _: 0;
-    get_frame_count_length, set_frame_count_length: 3 , 1;
-    get_extend_key_id_flag, set_extended_key_flag: 4;
-    get_key_id, set_key_id: 7 , 5;
-    frame_count, set_frame_count: 15, 8, 8;
+    get_extend_key_id_flag, set_extended_key_flag: 0;
+    get_key_id, set_key_id: 3 , 1;
+    get_extend_frame_count_flag, set_extended_frame_count_flag: 4;
+    get_frame_count_or_length, set_frame_count_or_length: 7, 5;
 }
 
 impl HeaderFields for BasicHeader {
@@ -41,33 +42,40 @@ impl HeaderFields for BasicHeader {
     }
 
     fn size(&self) -> usize {
-        BasicHeader::STATIC_HEADER_LENGHT_BYTE + self.frame_count.length_in_bytes() as usize
+        BasicHeader::STATIC_HEADER_LENGHT_BYTE
+            + trailing_len_in_bytes(self.frame_count.into()) as usize
     }
 }
 
 impl Serialization for BasicHeader {
-    fn serialize(&self, buffer: &mut [u8]) -> crate::error::Result<()> {
+    fn serialize(&self, buffer: &mut [u8]) -> Result<()> {
         if buffer.len() < self.size() {
-            return Err(SframeError::Other(format!(
-                "Buffer is to small to serialize the header {}<{}",
-                buffer.len(),
-                self.size()
-            )));
+            return Err(SframeError::BufferTooSmall {
+                needed: self.size(),
+                available: buffer.len(),
+            });
         }
-        let mut header_setter = BasicHeaderBitfield(buffer);
+        let frame_count: u64 = self.frame_count.into();
+        let (config_byte, frame_count_buffer) = buffer.split_at_mut(1);
+
+        let mut header_setter = BasicHeaderBitfield(config_byte);
         header_setter.set_extended_key_flag(false);
         header_setter.set_key_id(self.key_id);
 
-        let frame_count_length = self.frame_count.length_in_bytes();
-        header_setter.set_frame_count_length(frame_count_length - LEN_OFFSET); // frame counter length 1 is coded as 0
-
-        for (idx, value) in self
-            .frame_count
-            .as_be_bytes()
-            .take(frame_count_length as usize)
-            .enumerate()
-        {
-            header_setter.set_frame_count(idx, value);
+        if frame_count <= MAX_INLINE_VALUE {
+            header_setter.set_extended_frame_count_flag(false);
+            header_setter.set_frame_count_or_length(frame_count as u8);
+        } else {
+            let frame_count_length = self.frame_count.length_in_bytes();
+            header_setter.set_extended_frame_count_flag(true);
+            header_setter.set_frame_count_or_length(frame_count_length - 1); // frame count length 1 is coded as 0
+
+            for (target, value) in frame_count_buffer
+                .iter_mut()
+                .zip(as_min_be_bytes(frame_count))
+            {
+                *target = value;
+            }
         }
         Ok(())
     }
@@ -76,38 +84,56 @@ impl Serialization for BasicHeader {
 impl Deserialization for BasicHeader {
     type DeserializedOutput = Self;
 
-    fn deserialize(data: &[u8]) -> crate::error::Result<Self::DeserializedOutput> {
+    fn deserialize(data: &[u8]) -> Result<Self::DeserializedOutput> {
+        let header_len = header_len(data)?;
         let header_view = BasicHeaderBitfield(data);
         let key_id = header_view.get_key_id();
-        let frame_count_length: usize = (header_view.get_frame_count_length() + LEN_OFFSET).into(); // frame counter length 1 is coded as 0
-        let mut numeric_value = [0u8; 8];
-        let offset = numeric_value.len() - frame_count_length;
-        for index in 0..frame_count_length {
-            numeric_value[offset + index] = header_view.frame_count(index);
-        }
+        let frame_count_or_length = header_view.get_frame_count_or_length();
+
+        let frame_count = if header_view.get_extend_frame_count_flag() {
+            from_be_bytes(&data[BasicHeader::STATIC_HEADER_LENGHT_BYTE..header_len])
+        } else {
+            frame_count_or_length.into()
+        };
 
-        Ok(BasicHeader::new(
-            key_id,
-            FrameCount::from(u64::from_be_bytes(numeric_value)),
-        ))
+        Ok(BasicHeader::new(key_id, FrameCount::from(frame_count)))
     }
 
     fn is_valid(data: &[u8]) -> bool {
-        const MIN_LENGTH_IN_BYTES: usize = 2;
-        if data.len() < MIN_LENGTH_IN_BYTES {
-            return false;
-        }
-        let header_view = BasicHeaderBitfield(data);
+        header_len(data).is_ok()
+    }
+}
 
-        !header_view.get_extend_key_id_flag()
-            && data.len()
-                >= BasicHeader::STATIC_HEADER_LENGHT_BYTE
-                    + header_view.get_frame_count_length() as usize
+/// Reads the length of the header from the config byte and checks that the data is long enough
+fn header_len(data: &[u8]) -> Result<usize> {
+    if data.len() < BasicHeader::STATIC_HEADER_LENGHT_BYTE {
+        return Err(SframeError::HeaderTooShort {
+            needed: BasicHeader::STATIC_HEADER_LENGHT_BYTE,
+            available: data.len(),
+        });
+    }
+    let header_view = BasicHeaderBitfield(data);
+    if header_view.get_extend_key_id_flag() {
+        return Err(SframeError::InvalidHeaderType { extended: true });
     }
+
+    let frame_count_length = if header_view.get_extend_frame_count_flag() {
+        header_view.get_frame_count_or_length() as usize + 1 // frame count length 1 is coded as 0
+    } else {
+        0
+    };
+    let header_len = BasicHeader::STATIC_HEADER_LENGHT_BYTE + frame_count_length;
+    if data.len() < header_len {
+        return Err(SframeError::HeaderTooShort {
+            needed: header_len,
+            available: data.len(),
+        });
+    }
+
+    Ok(header_len)
 }
 
 #[cfg(test)]
-#[cfg(not(feature = "verify-test-vectors"))]
 mod test {
     use crate::{
         header::{BasicHeader, Deserialization, FrameCount, HeaderFields, Serialization},
@@ -131,9 +157,21 @@ mod test {
 
         assert!(header.serialize(&mut buffer).is_ok());
 
-        let expected_serialized_buffer = [0b00010110, 0b00000010, 0b10011010];
+        let expected_serialized_buffer = [0b0_110_1_001, 0b00000010, 0b10011010];
         assert_bytes_eq(&expected_serialized_buffer, &buffer);
     }
+
+    #[test]
+    fn serialize_small_frame_count_into_config_byte() {
+        let header = BasicHeader::new(6, FrameCount::from(5));
+        let mut buffer = vec![0u8; header.size()];
+
+        assert!(header.serialize(&mut buffer).is_ok());
+
+        let expected_serialized_buffer = [0b0_110_0_101];
+        assert_bytes_eq(&expected_serialized_buffer, &buffer);
+    }
+
     #[test]
     fn serialize_when_frame_count_and_key_id_is_0() {
         let header = BasicHeader::new(0, FrameCount::from(0));
@@ -141,39 +179,47 @@ mod test {
 
         assert!(header.serialize(&mut buffer).is_ok());
 
-        let expected_serialized_buffer = vec![0x0, 0x0];
+        let expected_serialized_buffer = vec![0x0];
         assert_eq!(expected_serialized_buffer, buffer);
     }
 
     #[test]
     fn be_invalid_if_buffer_to_small() {
-        let data = [0x00];
+        let data = [];
         assert_eq!(BasicHeader::is_valid(&data), false);
     }
 
     #[test]
     fn be_invalid_if_extended_header_flag_is_set() {
-        let data = [0b0000_1000, 0x00, 0x00];
+        let data = [0b1000_0000, 0x00, 0x00];
         assert_eq!(BasicHeader::is_valid(&data), false);
     }
 
     #[test]
     fn be_invalid_if_buffer_smaller_than_expected_size() {
-        let data = [0b0111_0000, 0x00, 0x00];
+        let data = [0b0000_1111, 0x00, 0x00];
         assert_eq!(BasicHeader::is_valid(&data), false);
     }
 
     #[test]
     fn be_valid_for_correct_data() {
-        let data = [0b00010110, 0b00000010, 0b10011010];
+        let data = [0b0_110_1_001, 0b00000010, 0b10011010];
         assert_eq!(BasicHeader::is_valid(&data), true);
     }
 
     #[test]
     fn deserialize_from_valid_data() {
-        let data = [0b00010110, 0b00000010, 0b10011010];
+        let data = [0b0_110_1_001, 0b00000010, 0b10011010];
         let header = BasicHeader::deserialize(&data).unwrap();
         assert_eq!(header.key_id(), 6);
         assert_eq!(header.frame_count(), 666);
     }
+
+    #[test]
+    fn deserialize_small_frame_count_from_config_byte() {
+        let data = [0b0_110_0_101];
+        let header = BasicHeader::deserialize(&data).unwrap();
+        assert_eq!(header.key_id(), 6);
+        assert_eq!(header.frame_count(), 5);
+    }
 }
@@ -8,26 +8,39 @@ use crate::error::{Result, SframeError};
 
 use super::{
     keyid::ExtendedKeyId,
-    util::{as_min_be_bytes, min_len_in_bytes},
-    Deserialization, ExtendedHeader, FrameCount, HeaderFields, Serialization, LEN_OFFSET,
+    util::{as_min_be_bytes, from_be_bytes, min_len_in_bytes, trailing_len_in_bytes},
+    Deserialization, ExtendedHeader, FrameCount, HeaderFields, Serialization, MAX_INLINE_VALUE,
 };
 
 bitfield! {
-    /// Modeled after [sframe draft 00 4.2](https://datatracker.ietf.org/doc/html/draft-ietf-sframe-enc-00#name-sframe-header)
+    /// Modeled after [RFC 9605 4.3](https://www.rfc-editor.org/rfc/rfc9605.html#name-sframe-header)
     /// ```txt
     ///  0 1 2 3 4 5 6 7
     /// +-+-+-+-+-+-+-+-+---------------------------+---------------------------+
-    /// |R|LEN  |1|KLEN |   KID... (length=KLEN)    |    CTR... (length=LEN)    |
+    /// |1|KLEN |Y|  C  |   KID... (length=KLEN+1)  |  CTR... (length=C+1 if Y) |
     /// +-+-+-+-+-+-+-+-+---------------------------+---------------------------+
     /// ```
     pub struct ExtendedHeaderBitField(MSB0 [u8]);
     impl Debug;
     u8;
-    reserved, _: 0;
-    u8, get_frame_count_len, set_frame_count_len: 3 , 1;
-    bool, extend_key_id_flag, set_extended_key_flag: 4;
-    u8, key_id_len, set_key_len: 7 , 5;
-    key_id_and_ctr, set_key_id_and_ctr: 15, 8, 16;
+    bool, extend_key_id_flag, set_extended_key_flag: 0;
+    u8, key_id_len, set_key_len: 3 , 1;
+    bool, extend_frame_count_flag, set_extended_frame_count_flag: 4;
+    u8, frame_count_or_length, set_frame_count_or_length: 7, 5;
+}
+
+impl ExtendedHeaderBitField<&[u8]> {
+    fn trailing_key_id_len(&self) -> usize {
+        self.key_id_len() as usize + 1 // key id length 1 is coded as 0
+    }
+
+    fn trailing_frame_count_len(&self) -> usize {
+        if self.extend_frame_count_flag() {
+            self.frame_count_or_length() as usize + 1 // frame count length 1 is coded as 0
+        } else {
+            0
+        }
+    }
 }
 
 impl HeaderFields for ExtendedHeader {
@@ -42,29 +55,40 @@ impl HeaderFields for ExtendedHeader {
     fn size(&self) -> usize {
         ExtendedHeader::STATIC_HEADER_LENGHT_BYTE
             + min_len_in_bytes(self.key_id) as usize
-            + self.frame_count.length_in_bytes() as usize
+            + trailing_len_in_bytes(self.frame_count.into()) as usize
     }
 }
 
 impl Serialization for ExtendedHeader {
     fn serialize(&self, buffer: &mut [u8]) -> Result<()> {
         if buffer.len() < self.size() {
-            return Err(SframeError::Other(format!(
-                "Buffer is to small to serialize the header {}<{}",
-                buffer.len(),
-                self.size()
-            )));
+            return Err(SframeError::BufferTooSmall {
+                needed: self.size(),
+                available: buffer.len(),
+            });
         }
-        let mut header_setter = ExtendedHeaderBitField(buffer);
-        header_setter.set_frame_count_len(self.frame_count.length_in_bytes() - LEN_OFFSET);
+        let frame_count: u64 = self.frame_count.into();
+        let (config_byte, remainder) = buffer.split_at_mut(1);
+
+        let mut header_setter = ExtendedHeaderBitField(config_byte);
         header_setter.set_extended_key_flag(true);
-        header_setter.set_key_len(min_len_in_bytes(self.key_id) - LEN_OFFSET);
+        header_setter.set_key_len(min_len_in_bytes(self.key_id) - 1); // key id length 1 is coded as 0
+
+        let trailing_frame_count = if frame_count <= MAX_INLINE_VALUE {
+            header_setter.set_extended_frame_count_flag(false);
+            header_setter.set_frame_count_or_length(frame_count as u8);
+            None
+        } else {
+            header_setter.set_extended_frame_count_flag(true);
+            header_setter.set_frame_count_or_length(self.frame_count.length_in_bytes() - 1); // frame count length 1 is coded as 0
+            Some(as_min_be_bytes(frame_count))
+        };
 
-        for (index, value) in as_min_be_bytes(self.key_id)
-            .chain(as_min_be_bytes(self.frame_count.into()))
-            .enumerate()
+        for (target, value) in remainder
+            .iter_mut()
+            .zip(as_min_be_bytes(self.key_id).chain(trailing_frame_count.into_iter().flatten()))
         {
-            header_setter.set_key_id_and_ctr(index, value);
+            *target = value;
         }
         Ok(())
     }
@@ -74,54 +98,57 @@ impl Deserialization for ExtendedHeader {
     type DeserializedOutput = Self;
 
     fn deserialize(data: &[u8]) -> Result<Self::DeserializedOutput> {
+        let header_len = header_len(data)?;
         let view = ExtendedHeaderBitField(data);
 
-        let key_len: usize = (view.key_id_len() + LEN_OFFSET).into();
-        let ctr_len: usize = (view.get_frame_count_len() + LEN_OFFSET).into();
-        let remainder_len = key_len + ctr_len;
-
-        let remainder = || {
-            let mut index = 0_usize;
-            let view = &view;
-            std::iter::from_fn(move || {
-                index += 1;
-                Some(view.key_id_and_ctr(remainder_len - index))
-            })
-        };
-
-        let mut ctr = [0u8; 8];
-        for (i, v) in remainder().take(ctr_len).enumerate() {
-            ctr[8 - (i + 1)] = v;
-        }
+        let key_id_begin = ExtendedHeader::STATIC_HEADER_LENGHT_BYTE;
+        let frame_count_begin = key_id_begin + view.trailing_key_id_len();
+        let key_id = from_be_bytes(&data[key_id_begin..frame_count_begin]);
 
-        let mut kid = [0u8; 8];
-        for (i, v) in remainder().skip(ctr_len).take(key_len).enumerate() {
-            kid[8 - (i + 1)] = v;
-        }
+        let frame_count = if view.extend_frame_count_flag() {
+            from_be_bytes(&data[frame_count_begin..header_len])
+        } else {
+            view.frame_count_or_length().into()
+        };
 
         Ok(ExtendedHeader {
-            key_id: u64::from_be_bytes(kid),
-            frame_count: FrameCount::from(u64::from_be_bytes(ctr)),
+            key_id,
+            frame_count: FrameCount::from(frame_count),
         })
     }
 
     fn is_valid(data: &[u8]) -> bool {
-        const MIN_LENGTH_IN_BYTES: usize = 3;
-        if data.len() < MIN_LENGTH_IN_BYTES {
-            return false;
-        }
-        let header_view = ExtendedHeaderBitField(data);
+        header_len(data).is_ok()
+    }
+}
+
+/// Reads the length of the header from the config byte and checks that the data is long enough
+fn header_len(data: &[u8]) -> Result<usize> {
+    if data.len() < ExtendedHeader::STATIC_HEADER_LENGHT_BYTE {
+        return Err(SframeError::HeaderTooShort {
+            needed: ExtendedHeader::STATIC_HEADER_LENGHT_BYTE,
+            available: data.len(),
+        });
+    }
+    let header_view = ExtendedHeaderBitField(data);
+    if !header_view.extend_key_id_flag() {
+        return Err(SframeError::InvalidHeaderType { extended: false });
+    }
 
-        header_view.extend_key_id_flag()
-            && data.len()
-                >= ExtendedHeader::STATIC_HEADER_LENGHT_BYTE
-                    + header_view.get_frame_count_len() as usize
-                    + header_view.key_id_len() as usize
+    let header_len = ExtendedHeader::STATIC_HEADER_LENGHT_BYTE
+        + header_view.trailing_key_id_len()
+        + header_view.trailing_frame_count_len();
+    if data.len() < header_len {
+        return Err(SframeError::HeaderTooShort {
+            needed: header_len,
+            available: data.len(),
+        });
     }
+
+    Ok(header_len)
 }
 
 #[cfg(test)]
-#[cfg(not(feature = "verify-test-vectors"))]
 mod test {
     use crate::{
         header::{Deserialization, ExtendedHeader, FrameCount, HeaderFields, Serialization},
@@ -140,19 +167,21 @@ mod test {
 
     #[test]
     fn serialize_to_buffer() {
-        let header = ExtendedHeader::new(6, FrameCount::from(154));
+        let header = ExtendedHeader::new(8, FrameCount::from(154));
         let mut buffer = vec![0u8; header.size()];
 
         assert!(header.serialize(&mut buffer).is_ok());
 
-        let expected_serialized_buffer = [0b0_000_1_000, 0b00000110, 0b10011010];
+        let expected_serialized_buffer = [0b1_000_1_000, 0b00001000, 0b10011010];
         assert_bytes_eq(&expected_serialized_buffer, &buffer);
     }
+
     #[test]
     fn calculate_header_size() {
-        assert_eq!(1 + 2, ExtendedHeader::new(0, Default::default()).size());
-        assert_eq!(1 + 3, ExtendedHeader::new(260, Default::default()).size());
-        assert_eq!(1 + 3, ExtendedHeader::new(5000, Default::default()).size());
+        assert_eq!(1 + 1, ExtendedHeader::new(8, Default::default()).size());
+        assert_eq!(1 + 2, ExtendedHeader::new(260, Default::default()).size());
+        assert_eq!(1 + 2, ExtendedHeader::new(5000, FrameCount::from(7)).size());
+        assert_eq!(1 + 3, ExtendedHeader::new(5000, FrameCount::from(8)).size());
         assert_eq!(
             1 + 4,
             ExtendedHeader::new(5000, FrameCount::from(260)).size()
@@ -166,7 +195,7 @@ mod test {
 
         assert!(header.serialize(&mut buffer).is_ok());
 
-        let expected_serialized_buffer = vec![0b0_000_1_000, 0x0, 0x0];
+        let expected_serialized_buffer = vec![0b1_000_0_000, 0x0];
         assert_bytes_eq(&expected_serialized_buffer, &buffer);
     }
 
@@ -177,7 +206,7 @@ mod test {
 
         assert!(header.serialize(&mut buffer).is_ok());
 
-        let mut expected_serialized_buffer = vec![0b0_111_1_111];
+        let mut expected_serialized_buffer = vec![0b1_111_1_111];
         expected_serialized_buffer.extend_from_slice(&u64::MAX.to_be_bytes());
         expected_serialized_buffer.extend_from_slice(&u64::MAX.to_be_bytes());
         assert_bytes_eq(&expected_serialized_buffer, &buffer);
@@ -185,13 +214,7 @@ mod test {
 
     #[test]
     fn be_invalid_if_buffer_to_small() {
-        let data = [0x00];
-        assert_eq!(ExtendedHeader::is_valid(&data), false);
-    }
-
-    #[test]
-    fn be_invalid_if_reserved_is_set() {
-        let data = [0b1_000_0_000, 0x00, 0x00];
+        let data = [0b1_000_0_000];
         assert_eq!(ExtendedHeader::is_valid(&data), false);
     }
 
@@ -203,21 +226,21 @@ mod test {
 
     #[test]
     fn be_invalid_if_buffer_smaller_than_expected_size() {
-        let data = [0b0_111_1_000, 0x00, 0x00];
+        let data = [0b1_111_1_000, 0x00, 0x00];
         assert_eq!(ExtendedHeader::is_valid(&data), false);
     }
 
     #[test]
     fn be_valid_for_correct_data() {
-        let data = [0b0_000_1_000, 0b00000010, 0b10011010];
+        let data = [0b1_000_1_000, 0b00000010, 0b10011010];
         assert_eq!(ExtendedHeader::is_valid(&data), true);
     }
 
     #[test]
     fn deserialize_from_valid_data() {
-        let data = [0b0_000_1_000, 0b00000010, 0b10011010];
+        let data = [0b1_000_1_000, 0b00001000, 0b10011010];
         let header = ExtendedHeader::deserialize(&data).unwrap();
-        assert_eq!(header.key_id(), 2);
+        assert_eq!(header.key_id(), 8);
         assert_eq!(header.frame_count(), 154);
     }
 }
@@ -1,9 +1,11 @@
 // Copyright (c) 2023 GoTo Group, Inc
 // SPDX-License-Identifier: Apache-2.0 AND MIT
 
-use std::ops::Add;
+use core::ops::Add;
 
 use super::util::{as_min_be_bytes, min_len_in_bytes};
+#[cfg(feature = "alloc")]
+use crate::error::{Result, SframeError};
 
 /// Represents the frame count (CTR) in a sframe header
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd)]
@@ -23,7 +25,7 @@ impl FrameCount {
     }
 }
 
-impl std::ops::Sub<FrameCount> for FrameCount {
+impl core::ops::Sub<FrameCount> for FrameCount {
     type Output = Self;
 
     fn sub(self, rhs: FrameCount) -> Self::Output {
@@ -33,7 +35,7 @@ impl std::ops::Sub<FrameCount> for FrameCount {
     }
 }
 
-impl std::ops::Sub<FrameCount> for u64 {
+impl core::ops::Sub<FrameCount> for u64 {
     type Output = Self;
 
     fn sub(self, rhs: FrameCount) -> Self::Output {
@@ -41,7 +43,7 @@ impl std::ops::Sub<FrameCount> for u64 {
     }
 }
 
-impl std::ops::Sub<u64> for FrameCount {
+impl core::ops::Sub<u64> for FrameCount {
     type Output = Self;
 
     fn sub(self, rhs: u64) -> Self::Output {
@@ -62,7 +64,7 @@ impl Add<u64> for FrameCount {
 }
 
 impl PartialOrd<u64> for FrameCount {
-    fn partial_cmp(&self, other: &u64) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &u64) -> Option<core::cmp::Ordering> {
         self.numeric_value.partial_cmp(other)
     }
 }
@@ -85,25 +87,51 @@ impl From<u64> for FrameCount {
     }
 }
 
+/// Generates the frame counts of a sender, which must never repeat with the same key.
+/// The last value [`FrameCountGenerator::MAX_FRAME_COUNT`] marks the counter as exhausted,
+/// so it can be persisted and restored like any other frame count.
+#[cfg(feature = "alloc")]
 #[derive(Copy, Clone, Debug, Default)]
 pub(crate) struct FrameCountGenerator {
     current_frame_count: u64,
 }
 
+#[cfg(feature = "alloc")]
 impl FrameCountGenerator {
     const MAX_FRAME_COUNT: u64 = u64::MAX;
 
-    pub fn increment(&mut self) -> FrameCount {
+    pub fn new(frame_count: FrameCount) -> Self {
+        FrameCountGenerator {
+            current_frame_count: frame_count.into(),
+        }
+    }
+
+    pub fn current(&self) -> FrameCount {
+        FrameCount::from(self.current_frame_count)
+    }
+
+    /// returns the current frame count and advances to the next one,
+    /// fails if all frame counts have been used
+    pub fn increment(&mut self) -> Result<FrameCount> {
+        if self.current_frame_count == Self::MAX_FRAME_COUNT {
+            return Err(SframeError::FrameCountExhausted);
+        }
         let frame_count = FrameCount::from(self.current_frame_count);
-        self.current_frame_count =
-            (self.current_frame_count + 1) % FrameCountGenerator::MAX_FRAME_COUNT;
-        frame_count
+        self.current_frame_count += 1;
+        Ok(frame_count)
+    }
+
+    /// starts again at 0 once the frame counts are exhausted, which is only safe with a new key
+    pub fn renew(&mut self) {
+        if self.current_frame_count == Self::MAX_FRAME_COUNT {
+            self.current_frame_count = 0;
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{FrameCount, FrameCountGenerator};
+    use super::FrameCount;
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -133,12 +161,31 @@ mod test {
         assert_eq!((usize::BITS / 8) as u8, frame_count.length_in_bytes());
     }
 
+    #[cfg(feature = "alloc")]
     #[test]
     fn create_increasing_frame_counts() {
-        let mut frame_count_generator = FrameCountGenerator::default();
+        let mut frame_count_generator = super::FrameCountGenerator::default();
 
         for i in 0..10 {
-            assert_eq!(frame_count_generator.increment(), i);
+            assert_eq!(frame_count_generator.increment().unwrap(), i);
         }
     }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn refuse_to_wrap_around() {
+        let mut frame_count_generator =
+            super::FrameCountGenerator::new(FrameCount::from(u64::MAX - 2));
+
+        assert_eq!(frame_count_generator.increment().unwrap(), u64::MAX - 2);
+        assert_eq!(frame_count_generator.increment().unwrap(), u64::MAX - 1);
+        assert_eq!(
+            frame_count_generator.increment(),
+            Err(crate::error::SframeError::FrameCountExhausted)
+        );
+        assert_eq!(frame_count_generator.current(), u64::MAX);
+
+        frame_count_generator.renew();
+        assert_eq!(frame_count_generator.increment().unwrap(), 0);
+    }
 }
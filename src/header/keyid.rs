@@ -9,7 +9,7 @@ pub type ExtendedKeyId = u64;
 /// Represents the key id (ID) field in the sframe header
 /// The kid can either be represented by 3 bits (Basic) for a short header
 /// or with a length up to 8 byte (Extended)
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub enum KeyId {
     /// 3 bit key id (KID), as a u8
     Basic(BasicKeyId),
@@ -7,7 +7,13 @@ mod frame_count;
 mod keyid;
 mod util;
 
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "arbitrary")]
+use arbitrary::{Arbitrary, Unstructured};
+
 pub use frame_count::FrameCount;
+#[cfg(feature = "alloc")]
 pub(crate) use frame_count::FrameCountGenerator;
 pub use keyid::KeyId;
 
@@ -44,11 +50,11 @@ pub trait HeaderFields {
 }
 
 /// Sframe header with a KID with a length of up to 3bits
-/// modeled after [sframe draft 00 4.2](https://datatracker.ietf.org/doc/html/draft-ietf-sframe-enc-00#name-sframe-header)
+/// modeled after [RFC 9605 4.3](https://www.rfc-editor.org/rfc/rfc9605.html#name-sframe-header)
 /// ```txt
 ///  0 1 2 3 4 5 6 7
 /// +-+-+-+-+-+-+-+-+---------------------------------+
-/// |R|LEN  |0| KID |    CTR... (length=LEN)          |
+/// |0| KID |Y|  C  |    CTR... (length=C+1 if Y)     |
 /// +-+-+-+-+-+-+-+-+---------------------------------+
 /// ```
 #[derive(Copy, Clone, Debug)]
@@ -73,12 +79,13 @@ impl BasicHeader {
     }
 }
 /// Extended sframe header with a KID with a length of up to 8 bytes
-/// modeled after [sframe draft 00 4.2](https://datatracker.ietf.org/doc/html/draft-ietf-sframe-enc-00#name-sframe-header)
+/// modeled after [RFC 9605 4.3](https://www.rfc-editor.org/rfc/rfc9605.html#name-sframe-header)
 /// ```txt
 ///  0 1 2 3 4 5 6 7
 /// +-+-+-+-+-+-+-+-+---------------------------+---------------------------+
-/// |R|LEN  |1|KLEN |   KID... (length=KLEN)    |    CTR... (length=LEN)    |
+/// |1|KLEN |Y|  C  |   KID... (length=KLEN+1)  |  CTR... (length=C+1 if Y) |
 /// +-+-----+-+-----+---------------------------+---------------------------+
+/// ```
 #[derive(Copy, Clone, Debug)]
 pub struct ExtendedHeader {
     key_id: ExtendedKeyId,
@@ -102,10 +109,11 @@ impl ExtendedHeader {
 }
 
 #[derive(Copy, Clone, Debug)]
-/// Represents an Sframe header modeled after [sframe draft 00 4.2](https://datatracker.ietf.org/doc/html/draft-ietf-sframe-enc-00#name-sframe-header)
+/// Represents an Sframe header modeled after [RFC 9605 4.3](https://www.rfc-editor.org/rfc/rfc9605.html#name-sframe-header)
 /// containing the key id of the sender (KID) and the current frame count (CTR).
 /// There are two variants, either with a KID represented by 3 bits (Basic) and an extended version with a KID of up to 8 bytes (Extended).
-/// The CTR field has a variable length of up to 8 bytes where the size is represented with LEN. Here LEN=0 represents a length of 1.
+/// A CTR up to 7 is stored in the config byte (C), larger values are appended with a length of up to 8 bytes,
+/// which is signaled with the Y flag. Here C=0 represents a length of 1.
 /// Same holds for the extended HEADER with the fields KID and KLEN.
 pub enum Header {
     /// see [`BasicHeader`]
@@ -125,7 +133,8 @@ impl Header {
         key_id: K,
         frame_count: F,
     ) -> Header {
-        let key_id = key_id.into();
+        // normalize the key id, as KIDs up to 7 are always encoded in the config byte
+        let key_id = KeyId::from(u64::from(key_id.into()));
         let frame_count = frame_count.into();
         match key_id {
             KeyId::Basic(key_id) => Header::Basic(BasicHeader::new(key_id, frame_count)),
@@ -157,10 +166,12 @@ impl Deserialization for Header {
     type DeserializedOutput = Self;
 
     fn deserialize(data: &[u8]) -> Result<Self::DeserializedOutput> {
-        if BasicHeader::is_valid(data) {
-            BasicHeader::deserialize(data).map(Header::Basic)
-        } else {
-            ExtendedHeader::deserialize(data).map(Header::Extended)
+        // the extended KID flag is the most significant bit of the config byte
+        match data.first() {
+            Some(config_byte) if config_byte & 0x80 != 0 => {
+                ExtendedHeader::deserialize(data).map(Header::Extended)
+            }
+            _ => BasicHeader::deserialize(data).map(Header::Basic),
         }
     }
 
@@ -203,27 +214,53 @@ impl HeaderFields for Header {
     }
 }
 
+/// Creates basic headers with a KID up to 7 and extended headers with any KID,
+/// including small KIDs which a [`Header`] created from a [`KeyId`] never uses
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for Header {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let is_extended = bool::arbitrary(u)?;
+        let key_id = u64::arbitrary(u)?;
+        let frame_count = FrameCount::from(u64::arbitrary(u)?);
+
+        Ok(if is_extended {
+            Header::Extended(ExtendedHeader::new(key_id, frame_count))
+        } else {
+            Header::Basic(BasicHeader::new(
+                (key_id % (BasicHeader::MAX_KEY_ID + 1)) as u8,
+                frame_count,
+            ))
+        })
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        // extended flag, KID and CTR
+        (17, Some(17))
+    }
+}
+
+#[cfg(feature = "alloc")]
 impl From<&Header> for Vec<u8> {
     fn from(header: &Header) -> Self {
-        let mut buffer = vec![0u8; header.size()];
+        let mut buffer = alloc::vec![0u8; header.size()];
         header.serialize(buffer.as_mut_slice()).unwrap();
         buffer
     }
 }
 
-// TODO remove the verify-test-vectors feature as soon the test vectors are fixed in the draft
-// Due to a spec change the lenghth offset changed (0 now means lenght of 1)
-#[cfg(not(feature = "verify-test-vectors"))]
-const LEN_OFFSET: u8 = 1;
-#[cfg(feature = "verify-test-vectors")]
-const LEN_OFFSET: u8 = 0;
+/// Maximum value of KID and CTR which can be stored directly in the config byte
+const MAX_INLINE_VALUE: u64 = 7;
+
 #[cfg(test)]
 mod test {
 
-    use super::{frame_count::FrameCount, keyid::KeyId, Header};
-    use crate::header::{Deserialization, HeaderFields};
-    #[cfg(feature = "verify-test-vectors")]
-    use crate::{test_vectors::*, util::test::assert_bytes_eq};
+    use super::{frame_count::FrameCount, keyid::KeyId, BasicHeader, ExtendedHeader, Header};
+    use crate::{
+        error::SframeError,
+        header::{Deserialization, HeaderFields, Serialization},
+        test_vectors::header::get_test_vectors,
+        util::test::assert_bytes_eq,
+    };
 
     use pretty_assertions::assert_eq;
 
@@ -236,7 +273,7 @@ mod test {
 
         assert_eq!(key_id, header.key_id());
         assert_eq!(frame_count, header.frame_count());
-        assert_eq!(2, header.size());
+        assert_eq!(1, header.size());
     }
 
     #[test]
@@ -250,36 +287,99 @@ mod test {
         assert_eq!(frame_count, header.frame_count());
     }
 
+    #[test]
+    fn create_basic_header_from_small_extended_key_id() {
+        let header = Header::with_frame_count(KeyId::Extended(7), 0);
+        assert!(matches!(header, Header::Basic(_)));
+        assert_eq!(KeyId::Basic(7), header.key_id());
+    }
+
     #[test]
     fn deserialize_basic_header() {
-        let data = [0b00010110, 0b00000010, 0b10011010];
+        let data = [0b0110_1001, 0b00000010, 0b10011010];
         let header = Header::deserialize(&data).unwrap();
         assert!(matches!(header, Header::Basic(_)));
     }
 
     #[test]
-    #[cfg(feature = "verify-test-vectors")]
+    fn fail_to_deserialize_truncated_headers() {
+        assert_eq!(
+            Header::deserialize(&[]).unwrap_err(),
+            SframeError::HeaderTooShort {
+                needed: 1,
+                available: 0
+            }
+        );
+        // basic header with a 2 byte CTR
+        assert_eq!(
+            Header::deserialize(&[0b0110_1001, 0x02]).unwrap_err(),
+            SframeError::HeaderTooShort {
+                needed: 3,
+                available: 2
+            }
+        );
+        // extended header with a 2 byte KID and a 1 byte CTR
+        assert_eq!(
+            Header::deserialize(&[0b1001_1000, 0x01, 0x02]).unwrap_err(),
+            SframeError::HeaderTooShort {
+                needed: 4,
+                available: 3
+            }
+        );
+    }
+
+    #[test]
+    fn fail_to_deserialize_mismatching_header_type() {
+        assert_eq!(
+            BasicHeader::deserialize(&[0b1000_0000, 0x00]).unwrap_err(),
+            SframeError::InvalidHeaderType { extended: true }
+        );
+        assert_eq!(
+            ExtendedHeader::deserialize(&[0b0000_0000, 0x00]).unwrap_err(),
+            SframeError::InvalidHeaderType { extended: false }
+        );
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn create_arbitrary_headers() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let mut data = vec![0_u8];
+        data.extend_from_slice(&15_u64.to_le_bytes());
+        data.extend_from_slice(&666_u64.to_le_bytes());
+        let header = Header::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        assert!(!header.is_extended());
+        assert_eq!(header.key_id(), KeyId::Basic(7));
+        assert_eq!(header.frame_count(), 666);
+
+        data[0] = 1;
+        let header = Header::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        assert!(header.is_extended());
+        assert_eq!(header.key_id(), KeyId::Extended(15));
+        assert_eq!(header.frame_count(), 666);
+    }
+
+    #[test]
     fn serialize_test_vectors() {
-        aes_gcm_256_sha512::get_test_vectors()
-            .into_iter()
-            .for_each(|test_vector| {
-                let header = Header::with_frame_count(
-                    KeyId::from(test_vector.key_id),
-                    FrameCount::from(test_vector.frame_count),
-                );
-                assert_bytes_eq(Vec::from(&header).as_slice(), &test_vector.header);
-            });
+        get_test_vectors().into_iter().for_each(|test_vector| {
+            let header = Header::with_frame_count(
+                KeyId::from(test_vector.key_id),
+                FrameCount::from(test_vector.frame_count),
+            );
+            let mut buffer = vec![0u8; header.size()];
+            header.serialize(&mut buffer).unwrap();
+            assert_bytes_eq(&buffer, &test_vector.encoded);
+        });
     }
 
     #[test]
-    #[cfg(feature = "verify-test-vectors")]
     fn deserialize_test_vectors() {
-        aes_gcm_256_sha512::get_test_vectors()
-            .into_iter()
-            .for_each(|test_vector| {
-                let header = Header::deserialize(&test_vector.header).unwrap();
-                assert_eq!(header.key_id(), KeyId::from(test_vector.key_id));
-                assert_eq!(header.frame_count(), test_vector.frame_count);
-            });
+        get_test_vectors().into_iter().for_each(|test_vector| {
+            let header = Header::deserialize(&test_vector.encoded).unwrap();
+            assert_eq!(header.key_id(), KeyId::from(test_vector.key_id));
+            assert_eq!(header.frame_count(), test_vector.frame_count);
+            assert_eq!(header.size(), test_vector.encoded.len());
+        });
     }
 }
@@ -1,3 +1,5 @@
+use super::MAX_INLINE_VALUE;
+
 pub fn as_min_be_bytes(x: u64) -> impl DoubleEndedIterator<Item = u8> {
     let be_bytes = x.to_be_bytes();
     let length_in_bytes = min_len_in_bytes(x);
@@ -19,9 +21,26 @@ pub fn min_len_in_bytes(value: u64) -> u8 {
     8 - leading_zeros
 }
 
+/// nof bytes needed after the config byte, values up to [`MAX_INLINE_VALUE`] are stored in the config byte itself
+pub fn trailing_len_in_bytes(value: u64) -> u8 {
+    if value <= MAX_INLINE_VALUE {
+        0
+    } else {
+        min_len_in_bytes(value)
+    }
+}
+
+/// interprets up to 8 big-endian bytes as u64
+pub fn from_be_bytes(bytes: &[u8]) -> u64 {
+    debug_assert!(bytes.len() <= 8, "values have a maximum length of 8 bytes");
+    bytes
+        .iter()
+        .fold(0_u64, |value, &byte| (value << 8) | u64::from(byte))
+}
+
 #[cfg(test)]
 mod test {
-    use super::min_len_in_bytes;
+    use super::{from_be_bytes, min_len_in_bytes, trailing_len_in_bytes};
 
     #[test]
     fn nof_non_zero_bytes() {
@@ -30,4 +49,19 @@ mod test {
         assert_eq!(2u8, min_len_in_bytes(256));
         assert_eq!(8u8, min_len_in_bytes(u64::MAX));
     }
+
+    #[test]
+    fn nof_trailing_bytes() {
+        assert_eq!(0u8, trailing_len_in_bytes(0));
+        assert_eq!(0u8, trailing_len_in_bytes(7));
+        assert_eq!(1u8, trailing_len_in_bytes(8));
+        assert_eq!(8u8, trailing_len_in_bytes(u64::MAX));
+    }
+
+    #[test]
+    fn read_be_bytes() {
+        assert_eq!(0, from_be_bytes(&[0]));
+        assert_eq!(666, from_be_bytes(&[2, 154]));
+        assert_eq!(u64::MAX, from_be_bytes(&u64::MAX.to_be_bytes()));
+    }
 }
@@ -0,0 +1,403 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+//! The key store of [`crate::receiver::Receiver`] and [`crate::receiver::SharedReceiver`]: the keys per KID,
+//! their expiry, the members of MLS epochs and the eviction of the least recently used keys.
+//! The store is not synchronized itself, the [`crate::receiver::SharedReceiver`] locks it as a whole.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::time::Duration;
+
+use crate::{
+    clock::Clock,
+    crypto::{key_expansion::KeyMaterial, secret::Secret},
+    error::{Result, SframeError},
+    header::KeyId,
+    mls::MlsEpoch,
+    receiver::{GracePeriod, KeyStoreStats},
+};
+
+/// A key of a [`KeyStore`]
+pub(crate) trait StoredKey {
+    /// value of the use counter of the receiver when the key was added or last decrypted a frame
+    fn last_used(&self) -> u64;
+    /// nof frames which have been decrypted with the key
+    fn decrypted_frames(&self) -> u64;
+}
+
+/// When a retiring key is removed
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Expiry {
+    At(Duration),
+    /// once the newer key has decrypted the given nof frames, so counting needs no mutable access to the store
+    AfterFrames {
+        newer_key_id: KeyId,
+        decrypted_frames: u64,
+    },
+}
+
+pub(crate) struct KeyStore<K> {
+    keys: BTreeMap<KeyId, K>,
+    expiring_keys: BTreeMap<KeyId, Expiry>,
+    mls_epochs: BTreeMap<u64, Vec<KeyId>>,
+    max_keys: Option<usize>,
+    evicted_keys: u64,
+}
+
+impl<K: StoredKey> KeyStore<K> {
+    pub fn new(max_keys: Option<usize>) -> Self {
+        Self {
+            keys: BTreeMap::default(),
+            expiring_keys: BTreeMap::default(),
+            mls_epochs: BTreeMap::default(),
+            max_keys,
+            evicted_keys: 0,
+        }
+    }
+
+    // only used by the `SharedReceiver`, which needs `std`
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub fn get(&self, key_id: &KeyId) -> Option<&K> {
+        self.keys.get(key_id)
+    }
+
+    pub fn get_mut(&mut self, key_id: &KeyId) -> Option<&mut K> {
+        self.keys.get_mut(key_id)
+    }
+
+    pub fn contains_key(&self, key_id: &KeyId) -> bool {
+        self.keys.contains_key(key_id)
+    }
+
+    /// Inserts new keys, which replace retiring or MLS keys with the same KID,
+    /// and evicts the least recently used other keys beyond the maximum nof keys.
+    /// Returns the KIDs of the evicted keys.
+    pub fn insert(&mut self, keys: Vec<(KeyId, K)>) -> Vec<KeyId> {
+        let key_ids = keys.iter().map(|(key_id, _)| *key_id).collect::<Vec<_>>();
+        for (key_id, key) in keys {
+            self.expiring_keys.remove(&key_id);
+            self.remove_mls_member(key_id);
+            if let Some(replaced_key) = self.keys.insert(key_id, key) {
+                self.restart_frame_count(key_id, replaced_key.decrypted_frames());
+            }
+        }
+
+        let max_keys = self.max_keys.unwrap_or(usize::MAX);
+        let mut evicted_key_ids = Vec::new();
+        while self.keys.len() > max_keys {
+            let Some(evicted_key_id) = self
+                .keys
+                .iter()
+                .filter(|(other, _)| !key_ids.contains(other))
+                .min_by_key(|(_, key)| key.last_used())
+                .map(|(&other, _)| other)
+            else {
+                break;
+            };
+            self.remove(evicted_key_id);
+            self.evicted_keys += 1;
+            evicted_key_ids.push(evicted_key_id);
+        }
+        evicted_key_ids
+    }
+
+    /// Removes the key of the KID, returns `false` if it was not set
+    pub fn remove(&mut self, key_id: KeyId) -> bool {
+        self.expiring_keys.remove(&key_id);
+        self.remove_mls_member(key_id);
+        self.keys.remove(&key_id).is_some()
+    }
+
+    /// Keeps the key of the KID only for the grace period, which starts now.
+    /// Fails if the key, or the newer key of [`GracePeriod::Frames`], is not set,
+    /// or if a time based grace period has no clock.
+    pub fn retire(
+        &mut self,
+        key_id: KeyId,
+        grace_period: GracePeriod,
+        clock: Option<&dyn Clock>,
+    ) -> Result<Expiry> {
+        if !self.keys.contains_key(&key_id) {
+            return Err(SframeError::MissingDecryptionKey(key_id));
+        }
+
+        let expiry = match grace_period {
+            GracePeriod::Time(grace_period) => {
+                let clock = clock.ok_or(SframeError::ClockNotConfigured)?;
+                Expiry::At(clock.now().saturating_add(grace_period))
+            }
+            GracePeriod::Frames {
+                newer_key_id,
+                frames,
+            } => {
+                let newer_key = self
+                    .keys
+                    .get(&newer_key_id)
+                    .ok_or(SframeError::MissingDecryptionKey(newer_key_id))?;
+                Expiry::AfterFrames {
+                    newer_key_id,
+                    decrypted_frames: newer_key.decrypted_frames().saturating_add(frames),
+                }
+            }
+        };
+        self.expiring_keys.insert(key_id, expiry);
+        Ok(expiry)
+    }
+
+    /// Whether a retiring key has expired, which is checked for every frame,
+    /// so it returns early if no key is retiring and reads the clock only for grace periods by time
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub fn has_expired_keys(&self, clock: Option<&dyn Clock>) -> bool {
+        if self.expiring_keys.is_empty() {
+            return false;
+        }
+        let now = self.now(clock);
+        self.expiring_keys
+            .values()
+            .any(|expiry| self.is_expired(expiry, now))
+    }
+
+    /// Removes the expired keys and returns their KIDs
+    pub fn remove_expired_keys(&mut self, clock: Option<&dyn Clock>) -> Vec<KeyId> {
+        if self.expiring_keys.is_empty() {
+            return Vec::new();
+        }
+        let now = self.now(clock);
+        let expired_key_ids = self
+            .expiring_keys
+            .iter()
+            .filter(|(_, expiry)| self.is_expired(expiry, now))
+            .map(|(&key_id, _)| key_id)
+            .collect::<Vec<_>>();
+        for &key_id in &expired_key_ids {
+            self.remove(key_id);
+        }
+        expired_key_ids
+    }
+
+    /// Removes the MLS epochs which are replaced by the given epoch and its KIDs,
+    /// e.g. as the epoch bits of the KID wrapped around. Returns the KIDs of the removed keys.
+    pub fn remove_colliding_mls_epochs(&mut self, epoch: u64, key_ids: &[KeyId]) -> Vec<KeyId> {
+        let colliding_epochs = self
+            .mls_epochs
+            .iter()
+            .filter(|(&other, other_key_ids)| {
+                other == epoch || other_key_ids.iter().any(|key_id| key_ids.contains(key_id))
+            })
+            .map(|(&other, _)| other)
+            .collect::<Vec<_>>();
+        colliding_epochs
+            .into_iter()
+            .filter_map(|other| self.remove_mls_epoch(other))
+            .flatten()
+            .collect()
+    }
+
+    /// Inserts the keys of the members of an MLS epoch, see [`KeyStore::insert`]
+    pub fn insert_mls_epoch(&mut self, epoch: u64, keys: Vec<(KeyId, K)>) -> Vec<KeyId> {
+        let key_ids = keys.iter().map(|(key_id, _)| *key_id).collect();
+        let evicted_key_ids = self.insert(keys);
+        self.mls_epochs.insert(epoch, key_ids);
+        evicted_key_ids
+    }
+
+    /// Removes the keys of all members of an MLS epoch and returns their KIDs,
+    /// or `None` if the epoch was not set
+    pub fn remove_mls_epoch(&mut self, epoch: u64) -> Option<Vec<KeyId>> {
+        let key_ids = self.mls_epochs.remove(&epoch)?;
+        for &key_id in &key_ids {
+            self.remove(key_id);
+        }
+        Some(key_ids)
+    }
+
+    /// Removes the keys of all MLS epochs older than the given one and returns their KIDs
+    pub fn remove_mls_epochs_before(&mut self, epoch: u64) -> Vec<KeyId> {
+        let current_epochs = self.mls_epochs.split_off(&epoch);
+        let old_epochs = core::mem::replace(&mut self.mls_epochs, current_epochs);
+        let key_ids = old_epochs.into_values().flatten().collect::<Vec<_>>();
+        for &key_id in &key_ids {
+            self.remove(key_id);
+        }
+        key_ids
+    }
+
+    pub fn stats(&self) -> KeyStoreStats {
+        KeyStoreStats {
+            keys: self.keys.len(),
+            evicted_keys: self.evicted_keys,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn key_ids(&self) -> Vec<KeyId> {
+        self.keys.keys().copied().collect()
+    }
+
+    #[cfg(test)]
+    pub fn expiring_key_ids(&self) -> Vec<KeyId> {
+        self.expiring_keys.keys().copied().collect()
+    }
+
+    fn now(&self, clock: Option<&dyn Clock>) -> Option<Duration> {
+        self.expiring_keys
+            .values()
+            .any(|expiry| matches!(expiry, Expiry::At(_)))
+            .then(|| clock.map(Clock::now))
+            .flatten()
+    }
+
+    fn is_expired(&self, expiry: &Expiry, now: Option<Duration>) -> bool {
+        match *expiry {
+            Expiry::At(expiry) => now.is_some_and(|now| now >= expiry),
+            Expiry::AfterFrames {
+                newer_key_id,
+                decrypted_frames,
+            } => self
+                .keys
+                .get(&newer_key_id)
+                .is_some_and(|newer_key| newer_key.decrypted_frames() >= decrypted_frames),
+        }
+    }
+
+    /// The frames of a key which replaces a newer key are counted from zero,
+    /// so retiring keys wait only for the frames the replaced key has not decrypted yet
+    fn restart_frame_count(&mut self, key_id: KeyId, decrypted_frames: u64) {
+        for expiry in self.expiring_keys.values_mut() {
+            if let Expiry::AfterFrames {
+                newer_key_id,
+                decrypted_frames: frames,
+            } = expiry
+            {
+                if *newer_key_id == key_id {
+                    *frames = frames.saturating_sub(decrypted_frames);
+                }
+            }
+        }
+    }
+
+    /// Forgets that the key of the KID belongs to an MLS epoch, so removing the epoch does not remove another key
+    fn remove_mls_member(&mut self, key_id: KeyId) {
+        self.mls_epochs.retain(|_, key_ids| {
+            key_ids.retain(|&other| other != key_id);
+            !key_ids.is_empty()
+        });
+    }
+}
+
+/// Derives the secrets of the given members of an MLS epoch, identified by their leaf index.
+/// Fails if the epoch has more members than `max_keys`.
+pub(crate) fn mls_member_secrets<Members>(
+    epoch: &MlsEpoch,
+    members: Members,
+    max_keys: Option<usize>,
+) -> Result<Vec<(KeyId, Secret)>>
+where
+    Members: IntoIterator<Item = u32>,
+{
+    let cipher_suite = epoch.cipher_suite();
+    let mut indices = members.into_iter().collect::<Vec<_>>();
+    indices.sort_unstable();
+    indices.dedup();
+    if let Some(max_keys) = max_keys.filter(|&max| indices.len() > max) {
+        return Err(SframeError::TooManyKeys {
+            keys: indices.len(),
+            max_keys,
+        });
+    }
+
+    indices
+        .into_iter()
+        .map(|index| {
+            let key_id = epoch.key_id(index)?;
+            let base_key = epoch.sender_base_key(index)?;
+            let secret = KeyMaterial(base_key.as_ref()).expand_as_secret(&cipher_suite, key_id)?;
+            Ok((key_id, secret))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[derive(Default)]
+    struct TestKey {
+        last_used: u64,
+        decrypted_frames: u64,
+    }
+
+    impl StoredKey for TestKey {
+        fn last_used(&self) -> u64 {
+            self.last_used
+        }
+
+        fn decrypted_frames(&self) -> u64 {
+            self.decrypted_frames
+        }
+    }
+
+    fn key_id(key_id: u64) -> KeyId {
+        KeyId::from(key_id)
+    }
+
+    #[test]
+    fn evict_least_recently_used_keys_except_new_ones() {
+        let mut key_store = KeyStore::new(Some(2));
+        for (id, last_used) in [(1, 5), (2, 3)] {
+            key_store.insert(vec![(
+                key_id(id),
+                TestKey {
+                    last_used,
+                    ..Default::default()
+                },
+            )]);
+        }
+
+        let evicted_key_ids = key_store.insert(vec![(key_id(3), TestKey::default())]);
+        assert_eq!(evicted_key_ids, [key_id(2)]);
+        assert_eq!(key_store.key_ids(), [key_id(1), key_id(3)]);
+        assert_eq!(
+            key_store.stats(),
+            KeyStoreStats {
+                keys: 2,
+                evicted_keys: 1
+            }
+        );
+    }
+
+    #[test]
+    fn count_frames_of_replaced_newer_key_from_zero() {
+        let mut key_store = KeyStore::new(None);
+        key_store.insert(vec![(key_id(1), TestKey::default())]);
+        key_store.insert(vec![(
+            key_id(2),
+            TestKey {
+                decrypted_frames: 7,
+                ..Default::default()
+            },
+        )]);
+        key_store
+            .retire(
+                key_id(1),
+                GracePeriod::Frames {
+                    newer_key_id: key_id(2),
+                    frames: 3,
+                },
+                None,
+            )
+            .unwrap();
+
+        key_store.get_mut(&key_id(2)).unwrap().decrypted_frames = 8;
+        key_store.insert(vec![(key_id(2), TestKey::default())]);
+        key_store.get_mut(&key_id(2)).unwrap().decrypted_frames = 1;
+        assert!(!key_store.has_expired_keys(None));
+
+        key_store.get_mut(&key_id(2)).unwrap().decrypted_frames = 2;
+        assert!(key_store.has_expired_keys(None));
+        assert_eq!(key_store.remove_expired_keys(None), [key_id(1)]);
+        assert_eq!(key_store.key_ids(), [key_id(2)]);
+        assert!(key_store.expiring_key_ids().is_empty());
+    }
+}
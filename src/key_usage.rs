@@ -0,0 +1,207 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+//! Usage limits of a key, after which a sender has to be rekeyed,
+//! see the confidentiality limits of [draft-irtf-cfrg-aead-limits](https://datatracker.ietf.org/doc/draft-irtf-cfrg-aead-limits/)
+
+use crate::{
+    error::{Result, SframeError},
+    CipherSuiteVariant,
+};
+
+/// Nof frames and payload bytes which have been protected with a key
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyUsage {
+    /// nof encrypted frames
+    pub frames: u64,
+    /// nof encrypted payload bytes, without the unencrypted leading bytes, sframe header and authentication tag
+    pub bytes: u64,
+}
+
+impl KeyUsage {
+    /// No limit besides the frame count
+    pub const UNLIMITED: KeyUsage = KeyUsage {
+        frames: u64::MAX,
+        bytes: u64::MAX,
+    };
+
+    fn add(&self, payload_len: usize) -> KeyUsage {
+        KeyUsage {
+            frames: self.frames.saturating_add(1),
+            bytes: self.bytes.saturating_add(payload_len as u64),
+        }
+    }
+
+    fn reaches(&self, limit: &KeyUsage) -> bool {
+        self.frames >= limit.frames || self.bytes >= limit.bytes
+    }
+
+    fn exceeds(&self, limit: &KeyUsage) -> bool {
+        self.frames > limit.frames || self.bytes > limit.bytes
+    }
+}
+
+/// Limits of the usage of a single key. Once the soft limit is reached, a rekey is signaled,
+/// while encrypting beyond the hard limit fails with [`SframeError::KeyUsageLimitExceeded`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyUsageLimits {
+    /// usage at which a new key should be set
+    pub soft: KeyUsage,
+    /// maximum usage of a key
+    pub hard: KeyUsage,
+}
+
+impl KeyUsageLimits {
+    /// No limits besides the frame count
+    pub const UNLIMITED: KeyUsageLimits = KeyUsageLimits {
+        soft: KeyUsage::UNLIMITED,
+        hard: KeyUsage::UNLIMITED,
+    };
+}
+
+/// Conservative defaults for an attacker advantage of 2^-60: AES based variants are limited to 2^32 frames
+/// and 2^37 bytes, with a soft limit at half of it. ChaCha20-Poly1305 is only limited by the frame count.
+impl From<CipherSuiteVariant> for KeyUsageLimits {
+    fn from(variant: CipherSuiteVariant) -> Self {
+        match variant {
+            CipherSuiteVariant::AesCtr128HmacSha256_80
+            | CipherSuiteVariant::AesCtr128HmacSha256_64
+            | CipherSuiteVariant::AesCtr128HmacSha256_32
+            | CipherSuiteVariant::AesGcm128Sha256
+            | CipherSuiteVariant::AesGcm256Sha512 => KeyUsageLimits {
+                soft: KeyUsage {
+                    frames: 1 << 31,
+                    bytes: 1 << 36,
+                },
+                hard: KeyUsage {
+                    frames: 1 << 32,
+                    bytes: 1 << 37,
+                },
+            },
+            CipherSuiteVariant::ChaCha20Poly1305Sha256 => KeyUsageLimits::UNLIMITED,
+        }
+    }
+}
+
+/// Tracks the usage of the current key of a sender
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct KeyUsageTracker {
+    pub usage: KeyUsage,
+    pub limits: KeyUsageLimits,
+}
+
+impl KeyUsageTracker {
+    pub fn new(limits: KeyUsageLimits) -> Self {
+        KeyUsageTracker {
+            usage: KeyUsage::default(),
+            limits,
+        }
+    }
+
+    /// fails if encrypting a payload of the given size would exceed the hard limit
+    pub fn check(&self, payload_len: usize) -> Result<()> {
+        if self.usage.add(payload_len).exceeds(&self.limits.hard) {
+            Err(SframeError::KeyUsageLimitExceeded)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// records an encrypted payload, returns `true` if the soft limit has been reached with it
+    pub fn record(&mut self, payload_len: usize) -> bool {
+        let was_rekey_needed = self.is_rekey_needed();
+        self.usage = self.usage.add(payload_len);
+        !was_rekey_needed && self.is_rekey_needed()
+    }
+
+    pub fn is_rekey_needed(&self) -> bool {
+        self.usage.reaches(&self.limits.soft)
+    }
+
+    pub fn reset(&mut self) {
+        self.usage = KeyUsage::default();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn tracker(soft: KeyUsage, hard: KeyUsage) -> KeyUsageTracker {
+        KeyUsageTracker::new(KeyUsageLimits { soft, hard })
+    }
+
+    #[test]
+    fn signal_rekey_once_at_soft_frame_limit() {
+        let mut tracker = tracker(
+            KeyUsage {
+                frames: 2,
+                bytes: u64::MAX,
+            },
+            KeyUsage::UNLIMITED,
+        );
+
+        assert!(!tracker.record(10));
+        assert!(tracker.record(10));
+        assert!(!tracker.record(10));
+        assert!(tracker.is_rekey_needed());
+        assert_eq!(
+            tracker.usage,
+            KeyUsage {
+                frames: 3,
+                bytes: 30
+            }
+        );
+    }
+
+    #[test]
+    fn signal_rekey_at_soft_byte_limit() {
+        let mut tracker = tracker(
+            KeyUsage {
+                frames: u64::MAX,
+                bytes: 100,
+            },
+            KeyUsage::UNLIMITED,
+        );
+
+        assert!(!tracker.record(99));
+        assert!(tracker.record(1));
+    }
+
+    #[test]
+    fn refuse_to_exceed_hard_limit() {
+        let mut tracker = tracker(
+            KeyUsage {
+                frames: 1,
+                bytes: 10,
+            },
+            KeyUsage {
+                frames: 2,
+                bytes: 20,
+            },
+        );
+
+        assert_eq!(tracker.check(21), Err(SframeError::KeyUsageLimitExceeded));
+        assert_eq!(tracker.check(20), Ok(()));
+        tracker.record(5);
+        tracker.record(5);
+        assert_eq!(tracker.check(0), Err(SframeError::KeyUsageLimitExceeded));
+
+        tracker.reset();
+        assert!(!tracker.is_rekey_needed());
+        assert_eq!(tracker.check(20), Ok(()));
+    }
+
+    #[test]
+    fn limit_aes_but_not_chacha20() {
+        let limits = KeyUsageLimits::from(CipherSuiteVariant::AesGcm128Sha256);
+        assert!(limits.soft.frames < limits.hard.frames);
+        assert!(limits.soft.bytes < limits.hard.bytes);
+
+        assert_eq!(
+            KeyUsageLimits::from(CipherSuiteVariant::ChaCha20Poly1305Sha256),
+            KeyUsageLimits::UNLIMITED
+        );
+    }
+}
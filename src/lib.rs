@@ -1,10 +1,12 @@
 // Copyright (c) 2023 GoTo Group, Inc
 // SPDX-License-Identifier: Apache-2.0 AND MIT
 //! # Secure Frame (`SFrame`)
-//! This library is an implementation of [draft-ietf-sframe-enc-latest](https://sframe-wg.github.io/sframe/draft-ietf-sframe-enc.html).
+//! This library is an implementation of [RFC 9605](https://www.rfc-editor.org/rfc/rfc9605.html).
 //!
-//! It is in it's current form a subset of the specification (e.g. Aes-CTR is not implemented).
+//! The crate is `no_std`. The [`header`] codec works without an allocator, everything else
+//! needs the `alloc` feature, which is enabled by any crypto backend (`ring`, `openssl` or `rust-crypto`).
 
+#![cfg_attr(not(test), no_std)]
 #![deny(clippy::missing_panics_doc)]
 #![deny(
     missing_copy_implementations,
@@ -24,14 +26,32 @@
     clippy::match_same_arms
 )]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
 mod crypto;
+#[cfg(feature = "alloc")]
+mod key_store;
 #[cfg(test)]
 mod test_vectors;
 mod util;
 
+pub mod clock;
+pub mod codec;
 pub mod error;
+#[cfg(feature = "alloc")]
 pub mod frame_validation;
 pub mod header;
+#[cfg(feature = "alloc")]
+pub mod key_usage;
+pub mod mls;
+pub mod ratchet;
+#[cfg(feature = "alloc")]
 pub mod receiver;
+#[cfg(feature = "alloc")]
 pub mod sender;
-pub use crypto::cipher_suite::CipherSuiteVariant;
+#[cfg(feature = "std")]
+mod shared_receiver;
+pub use crypto::cipher_suite::{CipherSuite, CipherSuiteVariant};
@@ -0,0 +1,425 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+//! Key management with MLS, see [RFC 9605 5.2](https://www.rfc-editor.org/rfc/rfc9605.html#name-mls)
+//!
+//! For each MLS epoch, the application exports a secret from its MLS group with
+//! `MLS-Exporter(EXPORTER_LABEL, "", Nh)`, from which the base keys of all members are derived.
+//! The KID of a member encodes its leaf index and the lowest bits of the epoch.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use core::fmt;
+#[cfg(feature = "alloc")]
+use zeroize::Zeroizing;
+
+#[cfg(feature = "alloc")]
+use crate::crypto::{cipher_suite::CipherSuite, key_expansion::KeyMaterial};
+use crate::{
+    error::{Result, SframeError},
+    header::KeyId,
+};
+
+/// Label of the MLS exporter for the sframe epoch secret
+pub const EXPORTER_LABEL: &str = "SFrame 1.0";
+
+/// Splits a KID into an application defined context, the leaf index of the sender
+/// and the lowest `n_epoch_bits` bits of the MLS epoch:
+/// ```txt
+/// KID = (context << (n_index_bits + n_epoch_bits)) | (index << n_epoch_bits) | (epoch mod 2^n_epoch_bits)
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MlsKeyIdLayout {
+    n_epoch_bits: u8,
+    n_index_bits: u8,
+}
+
+impl MlsKeyIdLayout {
+    /// Maximum number of bits for the leaf index, as MLS leaf indices are 32 bit
+    pub const MAX_INDEX_BITS: u8 = 32;
+
+    /// Fails if `n_index_bits` exceeds [`MlsKeyIdLayout::MAX_INDEX_BITS`]
+    /// or if epoch and index bits together exceed 64 bits
+    pub fn new(n_epoch_bits: u8, n_index_bits: u8) -> Result<Self> {
+        if n_index_bits <= Self::MAX_INDEX_BITS
+            && u16::from(n_epoch_bits) + u16::from(n_index_bits) <= 64
+        {
+            Ok(MlsKeyIdLayout {
+                n_epoch_bits,
+                n_index_bits,
+            })
+        } else {
+            Err(SframeError::InvalidMlsKeyId)
+        }
+    }
+
+    /// nof bits used for the epoch
+    pub fn n_epoch_bits(&self) -> u8 {
+        self.n_epoch_bits
+    }
+
+    /// nof bits used for the leaf index
+    pub fn n_index_bits(&self) -> u8 {
+        self.n_index_bits
+    }
+
+    /// Encodes the KID of a sender, fails if the index or context do not fit into their bits
+    pub fn key_id(&self, epoch: u64, index: u32, context: u64) -> Result<KeyId> {
+        let context_shift = self.n_epoch_bits + self.n_index_bits;
+        let fits = u64::from(index) & !mask(self.n_index_bits) == 0
+            && context & !mask(64 - context_shift) == 0;
+        if !fits {
+            return Err(SframeError::InvalidMlsKeyId);
+        }
+
+        Ok(KeyId::from(
+            context.checked_shl(u32::from(context_shift)).unwrap_or(0)
+                | u64::from(index)
+                    .checked_shl(u32::from(self.n_epoch_bits))
+                    .unwrap_or(0)
+                | epoch & mask(self.n_epoch_bits),
+        ))
+    }
+
+    /// the epoch of the KID, modulo `2^n_epoch_bits`
+    pub fn epoch<K>(&self, key_id: K) -> u64
+    where
+        K: Into<KeyId>,
+    {
+        u64::from(key_id.into()) & mask(self.n_epoch_bits)
+    }
+
+    /// the leaf index of the sender of the KID
+    pub fn index<K>(&self, key_id: K) -> u32
+    where
+        K: Into<KeyId>,
+    {
+        let index = u64::from(key_id.into())
+            .checked_shr(u32::from(self.n_epoch_bits))
+            .unwrap_or(0)
+            & mask(self.n_index_bits);
+        // the index has at most 32 bits
+        index as u32
+    }
+
+    /// the application defined context of the KID
+    pub fn context<K>(&self, key_id: K) -> u64
+    where
+        K: Into<KeyId>,
+    {
+        u64::from(key_id.into())
+            .checked_shr(u32::from(self.n_epoch_bits + self.n_index_bits))
+            .unwrap_or(0)
+    }
+}
+
+fn mask(n_bits: u8) -> u64 {
+    u64::MAX.checked_shr(64 - u32::from(n_bits)).unwrap_or(0)
+}
+
+/// The sframe secret of an MLS epoch, from which the base keys of all members are derived.
+/// The secret is wiped on drop.
+/// ```
+/// # use sframe::{mls::{MlsEpoch, MlsKeyIdLayout}, receiver::Receiver, sender::Sender, CipherSuiteVariant};
+/// let layout = MlsKeyIdLayout::new(4, 16).unwrap();
+/// let exporter_secret = [42_u8; 32]; // from the MLS group
+/// let epoch = MlsEpoch::new(7, &exporter_secret, layout, CipherSuiteVariant::AesGcm128Sha256).unwrap();
+///
+/// let mut sender = Sender::with_cipher_suite(epoch.key_id(1).unwrap(), CipherSuiteVariant::AesGcm128Sha256);
+/// sender.set_encryption_key(&epoch.sender_base_key(1).unwrap()).unwrap();
+///
+/// let mut receiver = Receiver::default();
+/// receiver.set_mls_epoch(&epoch, [0, 1, 2]).unwrap();
+///
+/// let encrypted = sender.encrypt("hello", 0).unwrap();
+/// assert_eq!(receiver.decrypt(encrypted, 0).unwrap(), b"hello");
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Clone)]
+pub struct MlsEpoch {
+    epoch: u64,
+    layout: MlsKeyIdLayout,
+    cipher_suite: CipherSuite,
+    exporter_secret: Zeroizing<Vec<u8>>,
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for MlsEpoch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // never print the secret
+        f.debug_struct("MlsEpoch")
+            .field("epoch", &self.epoch)
+            .field("layout", &self.layout)
+            .field("cipher_suite", &self.cipher_suite)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl MlsEpoch {
+    /// Creates an epoch from the output of `MLS-Exporter(EXPORTER_LABEL, "", Nh)`,
+    /// where `Nh` is the hash length of the cipher suite. Fails if the secret is shorter.
+    pub fn new<C>(
+        epoch: u64,
+        exporter_secret: &[u8],
+        layout: MlsKeyIdLayout,
+        cipher_suite: C,
+    ) -> Result<Self>
+    where
+        C: Into<CipherSuite>,
+    {
+        let cipher_suite = cipher_suite.into();
+        if exporter_secret.len() < cipher_suite.hash_len {
+            return Err(SframeError::KeyExpansion);
+        }
+
+        Ok(MlsEpoch {
+            epoch,
+            layout,
+            cipher_suite,
+            exporter_secret: Zeroizing::new(exporter_secret.to_vec()),
+        })
+    }
+
+    /// the MLS epoch number
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// the KID layout of all members
+    pub fn layout(&self) -> MlsKeyIdLayout {
+        self.layout
+    }
+
+    /// the cipher suite of all members
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.cipher_suite
+    }
+
+    /// the KID of the member with the given leaf index, with a context of 0
+    pub fn key_id(&self, index: u32) -> Result<KeyId> {
+        self.layout.key_id(self.epoch, index, 0)
+    }
+
+    /// derives the base key of the member with the given leaf index,
+    /// which is used as key material of its [`crate::sender::Sender`] and of the receivers
+    pub fn sender_base_key(&self, index: u32) -> Result<SenderBaseKey> {
+        KeyMaterial(&self.exporter_secret)
+            .mls_sender_base_key(&self.cipher_suite, index)
+            .map(SenderBaseKey)
+    }
+}
+
+/// The base key of an MLS group member, which is wiped on drop
+#[cfg(feature = "alloc")]
+#[derive(Clone)]
+pub struct SenderBaseKey(Zeroizing<Vec<u8>>);
+
+#[cfg(feature = "alloc")]
+impl AsRef<[u8]> for SenderBaseKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for SenderBaseKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // never print the key material
+        f.write_str("SenderBaseKey(..)")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn encode_key_id() {
+        let layout = MlsKeyIdLayout::new(4, 8).unwrap();
+        let key_id = layout.key_id(0x123, 0x45, 0x6).unwrap();
+
+        assert_eq!(u64::from(key_id), 0x6453);
+        assert_eq!(layout.epoch(key_id), 0x3);
+        assert_eq!(layout.index(key_id), 0x45);
+        assert_eq!(layout.context(key_id), 0x6);
+    }
+
+    #[test]
+    fn use_all_bits_of_key_id() {
+        let layout = MlsKeyIdLayout::new(32, 32).unwrap();
+        let key_id = layout.key_id(u64::MAX, u32::MAX, 0).unwrap();
+
+        assert_eq!(u64::from(key_id), u64::MAX);
+        assert_eq!(layout.epoch(key_id), u64::from(u32::MAX));
+        assert_eq!(layout.index(key_id), u32::MAX);
+        assert_eq!(layout.context(key_id), 0);
+        assert_eq!(layout.key_id(0, 0, 1), Err(SframeError::InvalidMlsKeyId));
+
+        let layout = MlsKeyIdLayout::new(0, 0).unwrap();
+        let key_id = layout.key_id(5, 0, u64::MAX).unwrap();
+        assert_eq!(u64::from(key_id), u64::MAX);
+        assert_eq!(layout.epoch(key_id), 0);
+        assert_eq!(layout.index(key_id), 0);
+    }
+
+    #[test]
+    fn encode_key_id_at_layout_boundaries() {
+        let layout = MlsKeyIdLayout::new(64, 0).unwrap();
+        let key_id = layout.key_id(5, 0, 0).unwrap();
+        assert_eq!(u64::from(key_id), 5);
+        assert_eq!(layout.epoch(key_id), 5);
+        assert_eq!(layout.index(key_id), 0);
+        assert_eq!(layout.context(key_id), 0);
+        assert_eq!(layout.key_id(5, 1, 0), Err(SframeError::InvalidMlsKeyId));
+        assert_eq!(layout.key_id(5, 0, 1), Err(SframeError::InvalidMlsKeyId));
+
+        let layout = MlsKeyIdLayout::new(0, 32).unwrap();
+        let key_id = layout.key_id(5, u32::MAX, 0xabc).unwrap();
+        assert_eq!(u64::from(key_id), 0xabc_ffff_ffff);
+        assert_eq!(layout.epoch(key_id), 0);
+        assert_eq!(layout.index(key_id), u32::MAX);
+        assert_eq!(layout.context(key_id), 0xabc);
+
+        let layout = MlsKeyIdLayout::new(32, 32).unwrap();
+        let key_id = layout.key_id(0x1_2345_6789, 0xabcd, 0).unwrap();
+        assert_eq!(u64::from(key_id), 0xabcd_2345_6789);
+        assert_eq!(layout.epoch(key_id), 0x2345_6789);
+        assert_eq!(layout.index(key_id), 0xabcd);
+    }
+
+    #[test]
+    fn fail_on_invalid_layout() {
+        assert_eq!(
+            MlsKeyIdLayout::new(8, 33),
+            Err(SframeError::InvalidMlsKeyId)
+        );
+        assert_eq!(
+            MlsKeyIdLayout::new(33, 32),
+            Err(SframeError::InvalidMlsKeyId)
+        );
+        assert_eq!(
+            MlsKeyIdLayout::new(255, 32),
+            Err(SframeError::InvalidMlsKeyId)
+        );
+    }
+
+    #[test]
+    fn fail_on_index_or_context_exceeding_layout() {
+        let layout = MlsKeyIdLayout::new(4, 8).unwrap();
+        assert_eq!(
+            layout.key_id(0, 0x100, 0),
+            Err(SframeError::InvalidMlsKeyId)
+        );
+        assert_eq!(
+            layout.key_id(0, 0, 1 << 52),
+            Err(SframeError::InvalidMlsKeyId)
+        );
+        assert!(layout.key_id(0, 0xff, (1 << 52) - 1).is_ok());
+    }
+
+    #[cfg(feature = "alloc")]
+    mod epoch {
+        use super::*;
+        use crate::CipherSuiteVariant;
+        use pretty_assertions::assert_eq;
+
+        fn epoch(epoch: u64) -> MlsEpoch {
+            MlsEpoch::new(
+                epoch,
+                &[0x42; 32],
+                MlsKeyIdLayout::new(4, 8).unwrap(),
+                CipherSuiteVariant::AesGcm128Sha256,
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn derive_base_key_per_member() {
+            let epoch = epoch(1);
+            assert_eq!(u64::from(epoch.key_id(2).unwrap()), 0x21);
+
+            let base_key = epoch.sender_base_key(1).unwrap();
+            assert_eq!(base_key.as_ref().len(), 32);
+            assert_ne!(
+                base_key.as_ref(),
+                epoch.sender_base_key(2).unwrap().as_ref()
+            );
+        }
+
+        /// `MLS-Exporter(label, "", Nh)` of RFC 9420 8.5 with SHA-256
+        fn mls_exporter(exporter_secret: &[u8], label: &str) -> Vec<u8> {
+            use crate::crypto::backend::{Backend, CryptoBackend, HashAlgorithm};
+
+            fn expand_with_label(secret: &[u8], label: &str, context: &[u8]) -> Vec<u8> {
+                let label = [b"MLS 1.0 ", label.as_bytes()].concat();
+                let prk = Backend::hkdf_prk(HashAlgorithm::Sha256, secret).unwrap();
+                Backend::hkdf_expand(
+                    &prk,
+                    &[
+                        &32_u16.to_be_bytes(),
+                        &[label.len() as u8],
+                        &label,
+                        &[context.len() as u8],
+                        context,
+                    ],
+                    32,
+                )
+                .unwrap()
+                .to_vec()
+            }
+
+            // SHA-256 of the empty exporter context
+            let context_hash =
+                hex::decode("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+                    .unwrap();
+            let derived_secret = expand_with_label(exporter_secret, label, &[]);
+            expand_with_label(&derived_secret, "exported", &context_hash)
+        }
+
+        #[test]
+        fn derive_base_key_from_mls_exporter() {
+            let sframe_epoch_secret = mls_exporter(&[0x11; 32], EXPORTER_LABEL);
+            assert_eq!(
+                hex::encode(&sframe_epoch_secret),
+                "92db07e54d49612817ad2c6c360e99bf36b88c4cdc3ee5380e5d3ed8f2619b6b"
+            );
+
+            let epoch = MlsEpoch::new(
+                1,
+                &sframe_epoch_secret,
+                MlsKeyIdLayout::new(4, 8).unwrap(),
+                CipherSuiteVariant::AesGcm128Sha256,
+            )
+            .unwrap();
+            assert_eq!(
+                hex::encode(epoch.sender_base_key(3).unwrap()),
+                "2e9f0faf627d7e5c4c9fc2b992c8f02b804c8c0bf7d611b9b0d5b8aaf8449e7e"
+            );
+        }
+
+        #[test]
+        fn fail_on_short_exporter_secret() {
+            assert_eq!(
+                MlsEpoch::new(
+                    1,
+                    &[0x42; 32],
+                    MlsKeyIdLayout::new(4, 8).unwrap(),
+                    CipherSuiteVariant::AesGcm256Sha512,
+                )
+                .map(|epoch| epoch.epoch()),
+                Err(SframeError::KeyExpansion)
+            );
+        }
+
+        #[test]
+        fn never_print_secrets() {
+            let epoch = epoch(1);
+            let debug = format!("{epoch:?} {:?}", epoch.sender_base_key(1).unwrap());
+            assert!(!debug.contains("66"));
+            assert!(debug.contains("SenderBaseKey(..)"));
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+//! Key ratcheting, see [RFC 9605 5.1](https://www.rfc-editor.org/rfc/rfc9605.html#name-sender-keys)
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use zeroize::Zeroizing;
+
+#[cfg(feature = "alloc")]
+use crate::crypto::{cipher_suite::CipherSuite, key_expansion::KeyMaterial, secret::Secret};
+use crate::{
+    error::{Result, SframeError},
+    header::KeyId,
+};
+
+/// A key id (KID) which is split into a sender specific part and a ratchet step (generation).
+/// The generation is stored in the lowest `n_ratchet_bits` bits of the KID:
+/// ```txt
+/// KID = (sender part << n_ratchet_bits) | (generation mod 2^n_ratchet_bits)
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RatchetingKeyId {
+    sender_part: u64,
+    generation: u64,
+    n_ratchet_bits: u8,
+}
+
+impl RatchetingKeyId {
+    /// Maximum number of bits which can be used for the generation
+    pub const MAX_RATCHET_BITS: u8 = 63;
+
+    /// Creates a [`RatchetingKeyId`] with the given sender specific part and generation 0.
+    /// Fails if `n_ratchet_bits` is not within `1..=MAX_RATCHET_BITS` or if the sender part
+    /// does not fit into the remaining bits of the KID.
+    pub fn new<K>(sender_part: K, n_ratchet_bits: u8) -> Result<Self>
+    where
+        K: Into<u64>,
+    {
+        let sender_part = sender_part.into();
+        let is_valid = (1..=Self::MAX_RATCHET_BITS).contains(&n_ratchet_bits)
+            && sender_part.leading_zeros() >= u32::from(n_ratchet_bits);
+
+        if is_valid {
+            Ok(RatchetingKeyId {
+                sender_part,
+                generation: 0,
+                n_ratchet_bits,
+            })
+        } else {
+            Err(SframeError::InvalidRatchetingKeyId)
+        }
+    }
+
+    /// Splits a received [`KeyId`] into sender part and generation
+    pub fn from_key_id<K>(key_id: K, n_ratchet_bits: u8) -> Result<Self>
+    where
+        K: Into<KeyId>,
+    {
+        if !(1..=Self::MAX_RATCHET_BITS).contains(&n_ratchet_bits) {
+            return Err(SframeError::InvalidRatchetingKeyId);
+        }
+        let key_id = u64::from(key_id.into());
+        Ok(RatchetingKeyId {
+            sender_part: key_id >> n_ratchet_bits,
+            generation: key_id & Self::generation_mask(n_ratchet_bits),
+            n_ratchet_bits,
+        })
+    }
+
+    /// the sender specific part of the KID
+    pub fn sender_part(&self) -> u64 {
+        self.sender_part
+    }
+
+    /// the ratchet step of the KID, modulo `2^n_ratchet_bits`
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// nof bits used for the generation
+    pub fn n_ratchet_bits(&self) -> u8 {
+        self.n_ratchet_bits
+    }
+
+    /// returns the key id of the next ratchet step, the generation wraps around after `2^n_ratchet_bits` steps
+    pub fn next(&self) -> Self {
+        RatchetingKeyId {
+            generation: self.generation.wrapping_add(1)
+                & Self::generation_mask(self.n_ratchet_bits),
+            ..*self
+        }
+    }
+
+    /// nof ratchet steps needed to get from this key id to the given one,
+    /// `None` if the key id belongs to another sender
+    #[cfg(feature = "alloc")]
+    pub(crate) fn steps_to(&self, key_id: KeyId) -> Option<u64> {
+        let other = Self::from_key_id(key_id, self.n_ratchet_bits).ok()?;
+        (other.sender_part == self.sender_part).then(|| {
+            other.generation.wrapping_sub(self.generation)
+                & Self::generation_mask(self.n_ratchet_bits)
+        })
+    }
+
+    fn generation_mask(n_ratchet_bits: u8) -> u64 {
+        (1 << n_ratchet_bits) - 1
+    }
+}
+
+impl From<RatchetingKeyId> for KeyId {
+    fn from(key_id: RatchetingKeyId) -> Self {
+        KeyId::from((key_id.sender_part << key_id.n_ratchet_bits) | key_id.generation)
+    }
+}
+
+impl From<RatchetingKeyId> for u64 {
+    fn from(key_id: RatchetingKeyId) -> Self {
+        KeyId::from(key_id).into()
+    }
+}
+
+/// The base key of the current ratchet step of a sender, which is wiped on drop
+#[cfg(feature = "alloc")]
+#[derive(Clone)]
+pub(crate) struct RatchetingBaseKey {
+    pub key_id: RatchetingKeyId,
+    base_key: Zeroizing<Vec<u8>>,
+}
+
+#[cfg(feature = "alloc")]
+impl RatchetingBaseKey {
+    pub fn new(key_id: RatchetingKeyId, key_material: &[u8]) -> Self {
+        RatchetingBaseKey {
+            key_id,
+            base_key: Zeroizing::new(key_material.to_vec()),
+        }
+    }
+
+    /// derives the base key of the next ratchet step
+    pub fn next(&self, cipher_suite: &CipherSuite) -> Result<Self> {
+        Ok(RatchetingBaseKey {
+            key_id: self.key_id.next(),
+            base_key: KeyMaterial(&self.base_key).ratchet(cipher_suite)?,
+        })
+    }
+
+    /// derives the secret for encryption/decryption of the current ratchet step
+    pub fn expand_as_secret(&self, cipher_suite: &CipherSuite) -> Result<Secret> {
+        KeyMaterial(&self.base_key).expand_as_secret(cipher_suite, self.key_id.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RatchetingKeyId;
+    use crate::{error::SframeError, header::KeyId};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn compose_key_id_from_sender_part_and_generation() {
+        let key_id = RatchetingKeyId::new(0b1011_u8, 4).unwrap();
+        assert_eq!(KeyId::from(key_id), KeyId::from(0b1011_0000_u64));
+
+        let key_id = key_id.next().next();
+        assert_eq!(key_id.generation(), 2);
+        assert_eq!(KeyId::from(key_id), KeyId::from(0b1011_0010_u64));
+    }
+
+    #[test]
+    fn split_key_id_into_sender_part_and_generation() {
+        let key_id = RatchetingKeyId::from_key_id(0b1011_0010_u64, 4).unwrap();
+        assert_eq!(key_id.sender_part(), 0b1011);
+        assert_eq!(key_id.generation(), 2);
+        assert_eq!(key_id.n_ratchet_bits(), 4);
+    }
+
+    #[test]
+    fn wrap_generation_around() {
+        let mut key_id = RatchetingKeyId::new(1_u8, 2).unwrap();
+        for _ in 0..4 {
+            key_id = key_id.next();
+        }
+        assert_eq!(key_id.generation(), 0);
+        assert_eq!(key_id.sender_part(), 1);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn count_steps_to_newer_generations() {
+        let key_id = RatchetingKeyId::from_key_id(0b1011_1110_u64, 4).unwrap();
+
+        assert_eq!(key_id.steps_to(KeyId::from(0b1011_1110_u64)), Some(0));
+        assert_eq!(key_id.steps_to(KeyId::from(0b1011_1111_u64)), Some(1));
+        // wrapped around
+        assert_eq!(key_id.steps_to(KeyId::from(0b1011_0001_u64)), Some(3));
+        // other sender
+        assert_eq!(key_id.steps_to(KeyId::from(0b1010_1111_u64)), None);
+    }
+
+    #[test]
+    fn reject_invalid_ratchet_bits() {
+        assert_eq!(
+            RatchetingKeyId::new(1_u8, 0),
+            Err(SframeError::InvalidRatchetingKeyId)
+        );
+        assert_eq!(
+            RatchetingKeyId::new(1_u8, 64),
+            Err(SframeError::InvalidRatchetingKeyId)
+        );
+        assert_eq!(
+            RatchetingKeyId::new(u64::MAX, 1),
+            Err(SframeError::InvalidRatchetingKeyId)
+        );
+    }
+}
@@ -1,9 +1,12 @@
 // Copyright (c) 2023 GoTo Group, Inc
 // SPDX-License-Identifier: Apache-2.0 AND MIT
 
-use std::collections::HashMap;
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::time::Duration;
 
 use crate::{
+    clock::Clock,
+    codec::UnencryptedPrefix,
     crypto::{
         aead::AeadDecrypt,
         cipher_suite::{CipherSuite, CipherSuiteVariant},
@@ -13,50 +16,242 @@ use crate::{
     error::{Result, SframeError},
     frame_validation::{FrameValidation, ReplayAttackProtection},
     header::{Deserialization, Header, HeaderFields, KeyId},
+    key_store::{mls_member_secrets, KeyStore, StoredKey},
+    mls::MlsEpoch,
+    ratchet::{RatchetingBaseKey, RatchetingKeyId},
+    util::check_skip,
 };
 
+#[cfg(feature = "std")]
+pub use crate::shared_receiver::SharedReceiver;
+
+/// Default for the maximum nof ratchet steps a [`Receiver`] derives automatically
+pub const DEFAULT_MAX_RATCHET_STEPS: u64 = 16;
+/// Default tolerance of the [`ReplayAttackProtection`] of a [`Receiver`]
+pub const DEFAULT_REPLAY_ATTACK_TOLERANCE: u64 = 128;
+
+/// Creates the [`FrameValidation`] for each key of a [`Receiver`]
+pub type FrameValidationFactory = Box<dyn Fn() -> Box<dyn FrameValidation> + Send + Sync>;
+
+/// Called with the KID of a key, which has been evicted as the maximum nof keys was exceeded
+pub type KeyEvictionCallback = Box<dyn FnMut(KeyId) + Send + Sync>;
+
+/// Options of a [`Receiver`], see [`ReceiverBuilder`]
 pub struct ReceiverOptions {
-    cipher_suite: CipherSuite,
-    frame_validation: Box<dyn FrameValidation>,
+    pub(crate) cipher_suite: CipherSuite,
+    pub(crate) frame_validation: FrameValidationFactory,
+    pub(crate) max_ratchet_steps: u64,
+    pub(crate) clock: Option<Box<dyn Clock>>,
+    pub(crate) max_keys: Option<usize>,
+    pub(crate) key_eviction_callback: Option<KeyEvictionCallback>,
 }
 
 impl Default for ReceiverOptions {
     fn default() -> Self {
         Self {
             cipher_suite: CipherSuiteVariant::AesGcm256Sha512.into(),
-            frame_validation: Box::new(ReplayAttackProtection::with_tolerance(128)),
+            frame_validation: replay_attack_protection(DEFAULT_REPLAY_ATTACK_TOLERANCE),
+            max_ratchet_steps: DEFAULT_MAX_RATCHET_STEPS,
+            #[cfg(feature = "std")]
+            clock: Some(Box::new(crate::clock::SystemClock::default())),
+            #[cfg(not(feature = "std"))]
+            clock: None,
+            max_keys: None,
+            key_eviction_callback: None,
         }
     }
 }
 
+/// Allows to configure the [`ReceiverOptions`] of a [`Receiver`]
+/// ```
+/// # use sframe::{receiver::Receiver, CipherSuiteVariant};
+/// let receiver = Receiver::builder()
+///     .cipher_suite(CipherSuiteVariant::AesGcm128Sha256)
+///     .replay_attack_tolerance(64)
+///     .max_ratchet_steps(4)
+///     .max_keys(64)
+///     .build();
+/// ```
 #[derive(Default)]
+pub struct ReceiverBuilder {
+    options: ReceiverOptions,
+}
+
+impl ReceiverBuilder {
+    /// Sets the cipher suite of keys which are set without a cipher suite,
+    /// defaults to [`CipherSuiteVariant::AesGcm256Sha512`]
+    pub fn cipher_suite<C>(mut self, cipher_suite: C) -> Self
+    where
+        C: Into<CipherSuite>,
+    {
+        self.options.cipher_suite = cipher_suite.into();
+        self
+    }
+
+    /// Validates frames with a [`ReplayAttackProtection`] with the given tolerance,
+    /// defaults to [`DEFAULT_REPLAY_ATTACK_TOLERANCE`]. Replaces a custom frame validation.
+    pub fn replay_attack_tolerance(mut self, tolerance: u64) -> Self {
+        self.options.frame_validation = replay_attack_protection(tolerance);
+        self
+    }
+
+    /// Validates frames with a custom [`FrameValidation`] instead of a [`ReplayAttackProtection`].
+    /// As the validation state is kept per KID, `create` is called whenever a key is added.
+    pub fn frame_validation<F, V>(mut self, create: F) -> Self
+    where
+        F: Fn() -> V + Send + Sync + 'static,
+        V: FrameValidation + 'static,
+    {
+        self.options.frame_validation = Box::new(move || Box::new(create()));
+        self
+    }
+
+    /// Sets the maximum nof ratchet steps which are derived automatically when a frame
+    /// with a KID of a newer generation is received, defaults to [`DEFAULT_MAX_RATCHET_STEPS`]
+    pub fn max_ratchet_steps(mut self, max_ratchet_steps: u64) -> Self {
+        self.options.max_ratchet_steps = max_ratchet_steps;
+        self
+    }
+
+    /// Sets the clock for the expiry of keys with [`GracePeriod::Time`],
+    /// defaults to [`crate::clock::SystemClock`] with the `std` feature
+    pub fn clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.options.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// Limits the nof keys of a [`Receiver`] or `SharedReceiver`, which are unlimited by default. When a key is added beyond the limit,
+    /// the key which was least recently used for a successful decryption is evicted. At least one key is kept.
+    /// A [`Receiver`] keeps the base key of a ratcheting sender, whose current generation is evicted,
+    /// and derives that key again for the next frame, with a new frame validation.
+    pub fn max_keys(mut self, max_keys: usize) -> Self {
+        self.options.max_keys = Some(max_keys.max(1));
+        self
+    }
+
+    /// Registers a callback, which is called whenever a [`Receiver`] evicts a key, see [`ReceiverBuilder::max_keys`]
+    pub fn on_key_eviction<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(KeyId) + Send + Sync + 'static,
+    {
+        self.options.key_eviction_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Creates the [`Receiver`] with the configured options
+    pub fn build(self) -> Receiver {
+        Receiver::from(self.options)
+    }
+
+    /// Creates a [`SharedReceiver`] with the configured options
+    #[cfg(feature = "std")]
+    pub fn build_shared(self) -> SharedReceiver {
+        SharedReceiver::from(self.options)
+    }
+}
+
+fn replay_attack_protection(tolerance: u64) -> FrameValidationFactory {
+    Box::new(move || Box::new(ReplayAttackProtection::with_tolerance(tolerance)))
+}
+
+/// How long a retiring key is kept for late frames, see [`Receiver::retire_encryption_key`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GracePeriod {
+    /// the key expires after the given time, measured by the [`ReceiverBuilder::clock`]
+    Time(Duration),
+    /// the key expires once the given nof frames have been decrypted with the newer key
+    Frames {
+        /// KID of the key which replaces the retiring one
+        newer_key_id: KeyId,
+        /// nof frames of the newer key
+        frames: u64,
+    },
+}
+
+/// A decryption key with its own cipher suite and validation state, so the frames of each sender are validated independently
+struct ReceiverKey {
+    secret: Secret,
+    cipher_suite: CipherSuite,
+    frame_validation: Box<dyn FrameValidation>,
+    /// value of [`Receiver::use_counter`] when the key was added or last decrypted a frame
+    last_used: u64,
+    decrypted_frames: u64,
+}
+
+impl StoredKey for ReceiverKey {
+    fn last_used(&self) -> u64 {
+        self.last_used
+    }
+
+    fn decrypted_frames(&self) -> u64 {
+        self.decrypted_frames
+    }
+}
+
+/// Nof keys of a [`Receiver`] and how many have been evicted, see [`ReceiverBuilder::max_keys`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyStoreStats {
+    /// nof keys currently stored
+    pub keys: usize,
+    /// nof keys which have been evicted as the maximum nof keys was exceeded
+    pub evicted_keys: u64,
+}
+
 pub struct Receiver {
-    secrets: HashMap<KeyId, Secret>,
+    keys: KeyStore<ReceiverKey>,
+    ratcheting_base_keys: Vec<(RatchetingBaseKey, CipherSuite)>,
+    use_counter: u64,
     options: ReceiverOptions,
     buffer: Vec<u8>,
 }
 
-impl Receiver {
-    pub fn with_cipher_suite(variant: CipherSuiteVariant) -> Receiver {
-        let cipher_suite: CipherSuite = variant.into();
-        let replay_attack_tolerance = 128;
+/// Keys of newer ratchet steps, which are only stored when a frame could be decrypted with them
+struct RatchetedKeys {
+    base_key_index: usize,
+    base_key: RatchetingBaseKey,
+    keys: Vec<(KeyId, ReceiverKey)>,
+}
+
+impl Default for Receiver {
+    fn default() -> Self {
+        Receiver::from(ReceiverOptions::default())
+    }
+}
+
+impl From<ReceiverOptions> for Receiver {
+    fn from(options: ReceiverOptions) -> Self {
         log::debug!("Setting up sframe Receiver");
         log::trace!(
-            "using ciphersuite {:?}, replay_attack_tolerance: {}",
-            cipher_suite.variant,
-            replay_attack_tolerance
+            "using ciphersuite {:?}, auth tag length {}, max ratchet steps {}",
+            options.cipher_suite.variant,
+            options.cipher_suite.auth_tag_len,
+            options.max_ratchet_steps
         );
         Self {
-            secrets: HashMap::default(),
-            options: ReceiverOptions {
-                cipher_suite,
-                frame_validation: Box::new(ReplayAttackProtection::with_tolerance(
-                    replay_attack_tolerance,
-                )),
-            },
+            keys: KeyStore::new(options.max_keys),
+            ratcheting_base_keys: Vec::new(),
+            use_counter: 0,
+            options,
             buffer: Default::default(),
         }
     }
+}
+
+impl Receiver {
+    /// Creates a [`ReceiverBuilder`] to configure a [`Receiver`]
+    pub fn builder() -> ReceiverBuilder {
+        ReceiverBuilder::default()
+    }
+
+    pub fn with_cipher_suite<C>(cipher_suite: C) -> Receiver
+    where
+        C: Into<CipherSuite>,
+    {
+        Self::builder().cipher_suite(cipher_suite).build()
+    }
 
     pub fn decrypt<EncryptedFrame>(
         &mut self,
@@ -67,37 +262,71 @@ impl Receiver {
         EncryptedFrame: AsRef<[u8]> + ?Sized,
     {
         let encrypted_frame = encrypted_frame.as_ref();
-        let header = Header::deserialize(&encrypted_frame[skip..])?;
 
-        self.options.frame_validation.validate(&header)?;
-        let key_id = header.key_id();
+        let mut buffer = core::mem::take(&mut self.buffer);
+        buffer.resize(encrypted_frame.len(), 0);
+        let result = self.decrypt_into(encrypted_frame, skip, &mut buffer);
+        self.buffer = buffer;
 
-        if let Some(secret) = self.secrets.get(&key_id) {
-            log::trace!(
-                "Receiver: Frame counter: {:?}, Key id: {:?}",
-                header.frame_count(),
-                header.key_id()
-            );
+        let frame_len = result?;
+        Ok(&self.buffer[..frame_len])
+    }
+
+    /// Decrypts a video frame, whose codec specific header has been left unencrypted, see [`crate::codec`].
+    /// The state of the codec is only updated if the frame is decrypted successfully.
+    pub fn decrypt_video_frame<EncryptedFrame, Codec>(
+        &mut self,
+        encrypted_frame: &EncryptedFrame,
+        codec: &mut Codec,
+    ) -> Result<&[u8]>
+    where
+        EncryptedFrame: AsRef<[u8]> + ?Sized,
+        Codec: UnencryptedPrefix + ?Sized,
+    {
+        let encrypted_frame = encrypted_frame.as_ref();
+        let skip = codec.unencrypted_len(encrypted_frame)?;
+        let decrypted_frame = self.decrypt(encrypted_frame, skip)?;
+        codec.update(decrypted_frame);
+        Ok(decrypted_frame)
+    }
 
-            let payload_begin = skip + header.size();
-            self.buffer.clear();
-            self.buffer.extend(&encrypted_frame[..skip]);
-            self.buffer.extend(&encrypted_frame[payload_begin..]);
+    /// Decrypts a frame into the given buffer and returns the size of the decrypted frame.
+    /// Besides the decrypted frame, the buffer also needs room for the authentication tag during decryption,
+    /// i.e. it has to be at least as large as the encrypted frame without the sframe header.
+    /// Hence a buffer of the size of the encrypted frame is always sufficient.
+    pub fn decrypt_into<EncryptedFrame>(
+        &mut self,
+        encrypted_frame: &EncryptedFrame,
+        skip: usize,
+        decrypted_frame: &mut [u8],
+    ) -> Result<usize>
+    where
+        EncryptedFrame: AsRef<[u8]> + ?Sized,
+    {
+        decrypt_frame_into(
+            encrypted_frame.as_ref(),
+            skip,
+            decrypted_frame,
+            |header, aad, encrypted_payload| self.decrypt_payload(header, aad, encrypted_payload),
+        )
+    }
 
-            self.options.cipher_suite.decrypt(
-                &mut self.buffer[skip..],
-                secret,
-                &encrypted_frame[skip..payload_begin],
-                header.frame_count(),
-            )?;
+    /// Decrypts a frame in place and returns the size of the decrypted frame, which is placed at the beginning of `frame`.
+    /// The content of `frame` is unspecified if the decryption fails.
+    pub fn decrypt_in_place(&mut self, frame: &mut [u8], skip: usize) -> Result<usize> {
+        check_skip(skip, frame.len())?;
+        let header = Header::deserialize(&frame[skip..])?;
+        let payload_begin = skip + header.size();
 
-            let payload_end = self.buffer.len() - self.options.cipher_suite.auth_tag_len;
-            Ok(&self.buffer[..payload_end])
-        } else {
-            Err(SframeError::MissingDecryptionKey(key_id))
-        }
+        let (leading_buffer, decrypt_buffer) = frame.split_at_mut(payload_begin);
+        let decrypted_len =
+            self.decrypt_payload(&header, &leading_buffer[skip..], decrypt_buffer)?;
+        frame.copy_within(payload_begin..payload_begin + decrypted_len, skip);
+
+        Ok(skip + decrypted_len)
     }
 
+    /// Sets the key for the given KID, using the cipher suite configured in the [`ReceiverOptions`]
     pub fn set_encryption_key<Id, KeyMaterial>(
         &mut self,
         key_id: Id,
@@ -107,10 +336,69 @@ impl Receiver {
         Id: Into<KeyId>,
         KeyMaterial: AsRef<[u8]> + ?Sized,
     {
-        self.secrets.insert(
-            key_id.into(),
-            KeyMaterial(key_material.as_ref()).expand_as_secret(&self.options.cipher_suite)?,
-        );
+        self.set_encryption_key_with_cipher_suite(key_id, key_material, self.options.cipher_suite)
+    }
+
+    /// Sets the key for the given KID, which is used with its own cipher suite,
+    /// e.g. when senders with different cipher suites take part in the same session
+    pub fn set_encryption_key_with_cipher_suite<Id, KeyMaterial, C>(
+        &mut self,
+        key_id: Id,
+        key_material: &KeyMaterial,
+        cipher_suite: C,
+    ) -> Result<()>
+    where
+        Id: Into<KeyId>,
+        KeyMaterial: AsRef<[u8]> + ?Sized,
+        C: Into<CipherSuite>,
+    {
+        let key_id = key_id.into();
+        let cipher_suite = cipher_suite.into();
+        let secret = KeyMaterial(key_material.as_ref()).expand_as_secret(&cipher_suite, key_id)?;
+        self.insert_key(key_id, self.create_key(secret, cipher_suite));
+        Ok(())
+    }
+
+    /// Sets the key of a sender which ratchets its key, see [`crate::sender::Sender::ratchet_encryption_key`].
+    /// Frames with a KID of a newer generation of this sender are decrypted by deriving the keys
+    /// of the following ratchet steps automatically, up to [`ReceiverBuilder::max_ratchet_steps`] steps.
+    pub fn set_ratcheting_encryption_key<KeyMaterial>(
+        &mut self,
+        key_id: RatchetingKeyId,
+        key_material: &KeyMaterial,
+    ) -> Result<()>
+    where
+        KeyMaterial: AsRef<[u8]> + ?Sized,
+    {
+        self.set_ratcheting_encryption_key_with_cipher_suite(
+            key_id,
+            key_material,
+            self.options.cipher_suite,
+        )
+    }
+
+    /// Same as [`Receiver::set_ratcheting_encryption_key`], but all generations of this sender use the given cipher suite
+    pub fn set_ratcheting_encryption_key_with_cipher_suite<KeyMaterial, C>(
+        &mut self,
+        key_id: RatchetingKeyId,
+        key_material: &KeyMaterial,
+        cipher_suite: C,
+    ) -> Result<()>
+    where
+        KeyMaterial: AsRef<[u8]> + ?Sized,
+        C: Into<CipherSuite>,
+    {
+        let cipher_suite = cipher_suite.into();
+        let base_key = RatchetingBaseKey::new(key_id, key_material.as_ref());
+        let secret = base_key.expand_as_secret(&cipher_suite)?;
+        self.insert_key(key_id.into(), self.create_key(secret, cipher_suite));
+
+        self.remove_future_generations(key_id);
+        self.ratcheting_base_keys.retain(|(other, _)| {
+            other.key_id.sender_part() != key_id.sender_part()
+                || other.key_id.n_ratchet_bits() != key_id.n_ratchet_bits()
+        });
+        self.ratcheting_base_keys.push((base_key, cipher_suite));
         Ok(())
     }
 
@@ -118,13 +406,300 @@ impl Receiver {
     where
         Id: Into<KeyId>,
     {
-        self.secrets.remove(&key_id.into()).is_some()
+        let key_id = key_id.into();
+        self.remove_ratcheting_base_key(key_id);
+        self.keys.remove(key_id)
+    }
+
+    /// Keeps the key of the given KID only for a grace period, e.g. for late frames after its sender
+    /// switched to a newer key. Expired keys are removed when the next frame is decrypted.
+    /// Fails if the key, or the newer key of [`GracePeriod::Frames`], is not set.
+    pub fn retire_encryption_key<Id>(&mut self, key_id: Id, grace_period: GracePeriod) -> Result<()>
+    where
+        Id: Into<KeyId>,
+    {
+        let key_id = key_id.into();
+        let expiry = self
+            .keys
+            .retire(key_id, grace_period, self.options.clock.as_deref())?;
+        log::debug!("Receiver: retiring KeyID {:?} ({:?})", key_id, expiry);
+        Ok(())
+    }
+
+    /// Sets the keys of the given members of an MLS epoch, identified by their leaf index.
+    /// Epochs whose KIDs collide with the new ones, e.g. as the epoch bits of the KID wrapped around, are removed.
+    /// Fails if the epoch has more members than [`ReceiverBuilder::max_keys`].
+    pub fn set_mls_epoch<Members>(&mut self, epoch: &MlsEpoch, members: Members) -> Result<()>
+    where
+        Members: IntoIterator<Item = u32>,
+    {
+        let cipher_suite = epoch.cipher_suite();
+        let secrets = mls_member_secrets(epoch, members, self.options.max_keys)?;
+        let key_ids = secrets
+            .iter()
+            .map(|(key_id, _)| *key_id)
+            .collect::<Vec<_>>();
+        for key_id in self
+            .keys
+            .remove_colliding_mls_epochs(epoch.epoch(), &key_ids)
+        {
+            self.remove_ratcheting_base_key(key_id);
+        }
+
+        log::debug!(
+            "Receiver: setting {} members of MLS epoch {}",
+            secrets.len(),
+            epoch.epoch()
+        );
+        let keys = secrets
+            .into_iter()
+            .map(|(key_id, secret)| (key_id, self.create_key(secret, cipher_suite)))
+            .collect();
+        let evicted_key_ids = self.keys.insert_mls_epoch(epoch.epoch(), keys);
+        self.on_evicted_keys(evicted_key_ids);
+        Ok(())
+    }
+
+    /// Removes the keys of all members of an MLS epoch, returns `false` if the epoch was not set
+    pub fn remove_mls_epoch(&mut self, epoch: u64) -> bool {
+        let Some(key_ids) = self.keys.remove_mls_epoch(epoch) else {
+            return false;
+        };
+        for key_id in key_ids {
+            self.remove_ratcheting_base_key(key_id);
+        }
+        true
+    }
+
+    /// Retires all MLS epochs older than the given one
+    pub fn remove_mls_epochs_before(&mut self, epoch: u64) {
+        for key_id in self.keys.remove_mls_epochs_before(epoch) {
+            self.remove_ratcheting_base_key(key_id);
+        }
+    }
+
+    /// Nof stored and evicted keys
+    pub fn key_store_stats(&self) -> KeyStoreStats {
+        self.keys.stats()
+    }
+
+    /// Inserts a new key, which replaces a retiring or MLS key with the same KID,
+    /// and evicts the least recently used other keys beyond [`ReceiverBuilder::max_keys`]
+    fn insert_key(&mut self, key_id: KeyId, key: ReceiverKey) {
+        let evicted_key_ids = self.keys.insert(vec![(key_id, key)]);
+        self.on_evicted_keys(evicted_key_ids);
+    }
+
+    fn on_evicted_keys(&mut self, evicted_key_ids: Vec<KeyId>) {
+        for evicted_key_id in evicted_key_ids {
+            log::debug!(
+                "Receiver: evicting least recently used KeyID {:?}",
+                evicted_key_id
+            );
+            if let Some(callback) = &mut self.options.key_eviction_callback {
+                callback(evicted_key_id);
+            }
+        }
+    }
+
+    /// No further ratcheting, if the current generation of a sender is removed
+    fn remove_ratcheting_base_key(&mut self, key_id: KeyId) {
+        self.ratcheting_base_keys
+            .retain(|(base_key, _)| KeyId::from(base_key.key_id) != key_id);
+    }
+
+    fn remove_expired_keys(&mut self) {
+        for key_id in self.keys.remove_expired_keys(self.options.clock.as_deref()) {
+            log::debug!("Receiver: KeyID {:?} expired", key_id);
+            self.remove_ratcheting_base_key(key_id);
+        }
+    }
+
+    fn create_key(&self, secret: Secret, cipher_suite: CipherSuite) -> ReceiverKey {
+        ReceiverKey {
+            secret,
+            cipher_suite,
+            frame_validation: (self.options.frame_validation)(),
+            last_used: self.use_counter,
+            decrypted_frames: 0,
+        }
+    }
+
+    fn decrypt_payload(
+        &mut self,
+        header: &Header,
+        aad: &[u8],
+        encrypted_payload: &mut [u8],
+    ) -> Result<usize> {
+        self.remove_expired_keys();
+        self.use_counter += 1;
+        let use_counter = self.use_counter;
+        let key_id = header.key_id();
+
+        let mut ratcheted_keys = if self.keys.contains_key(&key_id) {
+            None
+        } else {
+            Some(
+                self.ratchet_to(key_id)?
+                    .ok_or(SframeError::MissingDecryptionKey(key_id))?,
+            )
+        };
+        let key = match &mut ratcheted_keys {
+            Some(RatchetedKeys { keys, .. }) => keys.last_mut().map(|(_, key)| key),
+            None => self.keys.get_mut(&key_id),
+        }
+        .ok_or(SframeError::MissingDecryptionKey(key_id))?;
+
+        key.frame_validation.validate(header)?;
+
+        log::trace!(
+            "Receiver: Frame counter: {:?}, Key id: {:?}",
+            header.frame_count(),
+            header.key_id()
+        );
+
+        let decrypted_len = key
+            .cipher_suite
+            .decrypt(encrypted_payload, &key.secret, aad, header.frame_count())?
+            .len();
+
+        key.frame_validation.accept(header);
+        key.last_used = use_counter;
+        key.decrypted_frames += 1;
+        if let Some(ratcheted_keys) = ratcheted_keys {
+            self.store_ratcheted_keys(ratcheted_keys);
+        }
+
+        Ok(decrypted_len)
+    }
+
+    fn ratchet_to(&self, key_id: KeyId) -> Result<Option<RatchetedKeys>> {
+        let Some((base_key_index, (base_key, &cipher_suite), steps)) =
+            self.ratcheting_base_keys.iter().enumerate().find_map(
+                |(index, (base_key, cipher_suite))| {
+                    base_key
+                        .key_id
+                        .steps_to(key_id)
+                        .map(|steps| (index, (base_key, cipher_suite), steps))
+                },
+            )
+        else {
+            return Ok(None);
+        };
+
+        if steps > self.options.max_ratchet_steps {
+            log::debug!(
+                "Receiver: not ratcheting {} steps from {:?} to {:?}",
+                steps,
+                base_key.key_id,
+                key_id
+            );
+            return Ok(None);
+        }
+
+        log::trace!(
+            "Receiver: ratcheting {} steps from {:?} to {:?}",
+            steps,
+            base_key.key_id,
+            key_id
+        );
+        let mut keys = Vec::new();
+        // the key of the current generation is derived again, if it has been evicted
+        let mut base_key = if steps == 0 {
+            base_key.clone()
+        } else {
+            base_key.next(&cipher_suite)?
+        };
+        keys.push((
+            base_key.key_id.into(),
+            self.create_key(base_key.expand_as_secret(&cipher_suite)?, cipher_suite),
+        ));
+        for _ in 1..steps {
+            base_key = base_key.next(&cipher_suite)?;
+            keys.push((
+                base_key.key_id.into(),
+                self.create_key(base_key.expand_as_secret(&cipher_suite)?, cipher_suite),
+            ));
+        }
+
+        Ok(Some(RatchetedKeys {
+            base_key_index,
+            base_key,
+            keys,
+        }))
+    }
+
+    fn store_ratcheted_keys(&mut self, ratcheted_keys: RatchetedKeys) {
+        log::debug!(
+            "Receiver: ratcheted to KeyID {:?}",
+            ratcheted_keys.base_key.key_id
+        );
+        let key_id = ratcheted_keys.base_key.key_id;
+        self.ratcheting_base_keys[ratcheted_keys.base_key_index].0 = ratcheted_keys.base_key;
+        for (key_id, key) in ratcheted_keys.keys {
+            self.insert_key(key_id, key);
+        }
+        self.remove_future_generations(key_id);
+    }
+
+    /// As the generation wraps around, the KIDs of the next ratchet steps might still
+    /// be associated with the keys of older generations, which have to be derived again.
+    fn remove_future_generations(&mut self, key_id: RatchetingKeyId) {
+        let max_generation = u64::MAX >> (u64::BITS - u32::from(key_id.n_ratchet_bits()));
+        let mut future_key_id = key_id;
+        for _ in 0..self.options.max_ratchet_steps.min(max_generation) {
+            future_key_id = future_key_id.next();
+            self.keys.remove(future_key_id.into());
+        }
     }
 }
 
+/// Copies the leading `skip` bytes and the encrypted payload of a frame into the buffer,
+/// where the payload is decrypted with the header and AAD of the frame
+pub(crate) fn decrypt_frame_into<F>(
+    encrypted_frame: &[u8],
+    skip: usize,
+    decrypted_frame: &mut [u8],
+    decrypt_payload: F,
+) -> Result<usize>
+where
+    F: FnOnce(&Header, &[u8], &mut [u8]) -> Result<usize>,
+{
+    check_skip(skip, encrypted_frame.len())?;
+    let header = Header::deserialize(&encrypted_frame[skip..])?;
+    let payload_begin = skip + header.size();
+    let buffer_len = encrypted_frame.len() - header.size();
+    if decrypted_frame.len() < buffer_len {
+        return Err(SframeError::BufferTooSmall {
+            needed: buffer_len,
+            available: decrypted_frame.len(),
+        });
+    }
+
+    decrypted_frame[..skip].copy_from_slice(&encrypted_frame[..skip]);
+    decrypted_frame[skip..buffer_len].copy_from_slice(&encrypted_frame[payload_begin..]);
+
+    let decrypted_len = decrypt_payload(
+        &header,
+        &encrypted_frame[skip..payload_begin],
+        &mut decrypted_frame[skip..buffer_len],
+    )?;
+
+    Ok(skip + decrypted_len)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{header::FrameCount, sender::Sender};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Receiver>();
+        assert_send_sync::<ReceiverOptions>();
+    }
 
     #[test]
     fn remove_key() {
@@ -156,4 +731,782 @@ mod test {
             Err(SframeError::MissingDecryptionKey(KeyId::from(6u8)))
         );
     }
+
+    fn encrypted_frame() -> Vec<u8> {
+        let mut sender = Sender::new(1234_u64);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+        sender
+            .encrypt("skip this foobar is unsafe", 10)
+            .unwrap()
+            .to_vec()
+    }
+
+    fn receiver_with_key() -> Receiver {
+        let mut receiver = Receiver::default();
+        receiver
+            .set_encryption_key(1234_u64, "foobar is unsafe")
+            .unwrap();
+        receiver
+    }
+
+    #[test]
+    fn decrypt_into_buffer() {
+        let encrypted_frame = encrypted_frame();
+        let mut receiver = receiver_with_key();
+
+        let mut buffer = vec![0; encrypted_frame.len()];
+        let frame_len = receiver
+            .decrypt_into(&encrypted_frame, 10, &mut buffer)
+            .unwrap();
+        assert_eq!(&buffer[..frame_len], b"skip this foobar is unsafe");
+    }
+
+    #[test]
+    fn decrypt_in_place() {
+        let mut frame = encrypted_frame();
+        let mut receiver = receiver_with_key();
+
+        let frame_len = receiver.decrypt_in_place(&mut frame, 10).unwrap();
+        assert_eq!(&frame[..frame_len], b"skip this foobar is unsafe");
+    }
+
+    #[test]
+    fn fail_to_decrypt_into_too_small_buffer() {
+        let encrypted_frame = encrypted_frame();
+        let mut receiver = receiver_with_key();
+
+        // 3 byte header with extended KID
+        let buffer_len = encrypted_frame.len() - 3;
+        let mut buffer = vec![0; buffer_len - 1];
+        assert_eq!(
+            receiver.decrypt_into(&encrypted_frame, 10, &mut buffer),
+            Err(SframeError::BufferTooSmall {
+                needed: buffer_len,
+                available: buffer_len - 1
+            })
+        );
+    }
+
+    #[test]
+    fn fail_to_decrypt_with_skip_larger_than_frame() {
+        let mut encrypted_frame = encrypted_frame();
+        let frame_len = encrypted_frame.len();
+        let mut receiver = receiver_with_key();
+
+        let expected = SframeError::SkipTooLarge {
+            skip: frame_len + 1,
+            frame_len,
+        };
+        assert_eq!(
+            receiver.decrypt(&encrypted_frame, frame_len + 1),
+            Err(expected.clone())
+        );
+        assert_eq!(
+            receiver.decrypt_in_place(&mut encrypted_frame, frame_len + 1),
+            Err(expected)
+        );
+    }
+
+    #[test]
+    fn fail_to_decrypt_truncated_header() {
+        let encrypted_frame = encrypted_frame();
+        let mut receiver = receiver_with_key();
+
+        // the extended KID 1234 needs 2 bytes after the config byte
+        assert_eq!(
+            receiver.decrypt(&encrypted_frame[..12], 10),
+            Err(SframeError::HeaderTooShort {
+                needed: 3,
+                available: 2
+            })
+        );
+        assert_eq!(
+            receiver.decrypt(&encrypted_frame[..10], 10),
+            Err(SframeError::HeaderTooShort {
+                needed: 1,
+                available: 0
+            })
+        );
+    }
+
+    #[test]
+    fn fail_to_decrypt_truncated_frames() {
+        let variants = [
+            CipherSuiteVariant::AesCtr128HmacSha256_80,
+            CipherSuiteVariant::AesCtr128HmacSha256_64,
+            CipherSuiteVariant::AesCtr128HmacSha256_32,
+            CipherSuiteVariant::AesGcm128Sha256,
+            CipherSuiteVariant::AesGcm256Sha512,
+            CipherSuiteVariant::ChaCha20Poly1305Sha256,
+        ];
+
+        for variant in variants {
+            let mut sender = Sender::with_cipher_suite(1234_u64, variant);
+            sender.set_encryption_key("foobar is unsafe").unwrap();
+            let mut receiver = Receiver::with_cipher_suite(variant);
+            receiver
+                .set_encryption_key(1234_u64, "foobar is unsafe")
+                .unwrap();
+            let encrypted_frame = sender.encrypt("skip this foobar", 10).unwrap();
+
+            // also shorter than the skipped bytes, the header and the authentication tag
+            for frame_len in 0..encrypted_frame.len() {
+                let mut truncated = encrypted_frame[..frame_len].to_vec();
+                assert!(receiver.decrypt(&truncated, 10).is_err());
+                assert!(receiver.decrypt_in_place(&mut truncated, 10).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn validate_frame_counts_per_sender() {
+        let mut receiver = Receiver::default();
+        let mut senders = [1_u64, 2_u64].map(|key_id| {
+            receiver
+                .set_encryption_key(key_id, "foobar is unsafe")
+                .unwrap();
+            let mut sender = Sender::new(key_id);
+            sender.set_encryption_key("foobar is unsafe").unwrap();
+            sender
+        });
+
+        // the first sender is far ahead of the second sender
+        for _ in 0..1000 {
+            senders[0].encrypt("foobar is unsafe", 0).unwrap();
+        }
+        let encrypted = senders[0].encrypt("foobar is unsafe", 0).unwrap();
+        assert!(receiver.decrypt(encrypted, 0).is_ok());
+
+        let encrypted = senders[1].encrypt("foobar is unsafe", 0).unwrap();
+        assert!(receiver.decrypt(encrypted, 0).is_ok());
+    }
+
+    #[test]
+    fn reject_replayed_frames() {
+        let encrypted_frame = encrypted_frame();
+        let mut receiver = receiver_with_key();
+
+        assert!(receiver.decrypt(&encrypted_frame, 10).is_ok());
+        assert_eq!(
+            receiver.decrypt(&encrypted_frame, 10),
+            Err(SframeError::ReplayedFrame {
+                frame_count: FrameCount::from(0),
+                newest_frame_count: FrameCount::from(0),
+            })
+        );
+    }
+
+    #[test]
+    fn reset_frame_validation_with_new_key() {
+        let mut receiver = receiver_with_key();
+        let mut sender = Sender::new(1234_u64);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+
+        for _ in 0..1000 {
+            sender.encrypt("foobar is unsafe", 0).unwrap();
+        }
+        let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+        assert!(receiver.decrypt(encrypted, 0).is_ok());
+
+        // a rejoining sender starts with frame count 0 again
+        receiver.remove_encryption_key(1234_u64);
+        receiver
+            .set_encryption_key(1234_u64, "foobar is unsafe")
+            .unwrap();
+        let encrypted_frame = encrypted_frame();
+        assert!(receiver.decrypt(&encrypted_frame, 10).is_ok());
+    }
+
+    #[test]
+    fn decrypt_with_cipher_suite_per_key() {
+        let mut receiver = Receiver::with_cipher_suite(CipherSuiteVariant::AesGcm128Sha256);
+        let variants = [
+            CipherSuiteVariant::AesGcm128Sha256,
+            CipherSuiteVariant::AesGcm256Sha512,
+            CipherSuiteVariant::AesCtr128HmacSha256_32,
+        ];
+
+        for (key_id, variant) in variants.into_iter().enumerate() {
+            let key_id = key_id as u64;
+            let mut sender = Sender::with_cipher_suite(key_id, variant);
+            sender.set_encryption_key("foobar is unsafe").unwrap();
+            receiver
+                .set_encryption_key_with_cipher_suite(key_id, "foobar is unsafe", variant)
+                .unwrap();
+
+            let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+            let decrypted = receiver.decrypt(encrypted, 0).unwrap();
+            assert_eq!(decrypted, b"foobar is unsafe");
+        }
+    }
+
+    #[test]
+    fn use_custom_frame_validation_per_key() {
+        struct RejectFrameCountsAbove(u64);
+        impl FrameValidation for RejectFrameCountsAbove {
+            fn validate(&self, header: &Header) -> Result<()> {
+                if header.frame_count() > self.0 {
+                    Err(SframeError::FrameValidationFailed(format!(
+                        "frame count {} is above {}",
+                        u64::from(header.frame_count()),
+                        self.0
+                    )))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+
+        let nof_validations = std::sync::Arc::new(AtomicUsize::new(0));
+        let mut receiver = Receiver::builder()
+            .frame_validation({
+                let nof_validations = nof_validations.clone();
+                move || {
+                    nof_validations.fetch_add(1, Ordering::Relaxed);
+                    RejectFrameCountsAbove(1)
+                }
+            })
+            .build();
+        receiver
+            .set_encryption_key(1234_u64, "foobar is unsafe")
+            .unwrap();
+        assert_eq!(nof_validations.load(Ordering::Relaxed), 1);
+
+        let mut sender = Sender::new(1234_u64);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+        for _ in 0..2 {
+            let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+            assert!(receiver.decrypt(encrypted, 0).is_ok());
+        }
+        let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+        assert_eq!(
+            receiver.decrypt(encrypted, 0),
+            Err(SframeError::FrameValidationFailed(
+                "frame count 2 is above 1".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn configure_replay_attack_tolerance() {
+        let mut receiver = Receiver::builder().replay_attack_tolerance(4).build();
+        receiver
+            .set_encryption_key(1234_u64, "foobar is unsafe")
+            .unwrap();
+        let mut sender = Sender::new(1234_u64);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+
+        let late_frame = sender.encrypt("foobar is unsafe", 0).unwrap().to_vec();
+        for _ in 0..5 {
+            sender.encrypt("foobar is unsafe", 0).unwrap();
+        }
+        let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+        assert!(receiver.decrypt(encrypted, 0).is_ok());
+        assert_eq!(
+            receiver.decrypt(&late_frame, 0),
+            Err(SframeError::FrameTooOld {
+                frame_count: FrameCount::from(0),
+                newest_frame_count: FrameCount::from(6),
+            })
+        );
+    }
+
+    fn encrypt_with_key_id(key_id: u64) -> Vec<u8> {
+        let mut sender = Sender::new(key_id);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+        sender.encrypt("foobar is unsafe", 0).unwrap().to_vec()
+    }
+
+    #[test]
+    fn evict_least_recently_used_key() {
+        let evicted_key_ids = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut receiver = Receiver::builder()
+            .max_keys(2)
+            .on_key_eviction({
+                let evicted_key_ids = evicted_key_ids.clone();
+                move |key_id| evicted_key_ids.lock().unwrap().push(key_id)
+            })
+            .build();
+        receiver
+            .set_encryption_key(1_u64, "foobar is unsafe")
+            .unwrap();
+        receiver
+            .set_encryption_key(2_u64, "foobar is unsafe")
+            .unwrap();
+        assert!(receiver.decrypt(&encrypt_with_key_id(1), 0).is_ok());
+
+        receiver
+            .set_encryption_key(3_u64, "foobar is unsafe")
+            .unwrap();
+        assert_eq!(*evicted_key_ids.lock().unwrap(), [KeyId::from(2_u64)]);
+        assert_eq!(
+            receiver.key_store_stats(),
+            KeyStoreStats {
+                keys: 2,
+                evicted_keys: 1
+            }
+        );
+        assert_eq!(
+            receiver.decrypt(&encrypt_with_key_id(2), 0),
+            Err(SframeError::MissingDecryptionKey(KeyId::from(2_u64)))
+        );
+        assert!(receiver.decrypt(&encrypt_with_key_id(3), 0).is_ok());
+
+        // key 1 was used before key 3
+        receiver
+            .set_encryption_key(4_u64, "foobar is unsafe")
+            .unwrap();
+        assert_eq!(
+            *evicted_key_ids.lock().unwrap(),
+            [KeyId::from(2_u64), KeyId::from(1_u64)]
+        );
+    }
+
+    #[test]
+    fn keep_all_keys_by_default() {
+        let mut receiver = Receiver::default();
+        for key_id in 0..100_u64 {
+            receiver
+                .set_encryption_key(key_id, "foobar is unsafe")
+                .unwrap();
+        }
+        assert_eq!(
+            receiver.key_store_stats(),
+            KeyStoreStats {
+                keys: 100,
+                evicted_keys: 0
+            }
+        );
+    }
+
+    mod ratcheting {
+        use super::*;
+
+        const KEY_MATERIAL: &str = "foobar is unsafe";
+
+        fn sender_and_receiver(max_ratchet_steps: u64) -> (Sender, Receiver) {
+            let key_id = RatchetingKeyId::new(5_u8, 4).unwrap();
+            let mut sender =
+                Sender::with_ratcheting_key_id(key_id, CipherSuiteVariant::AesGcm256Sha512);
+            sender.set_encryption_key(KEY_MATERIAL).unwrap();
+
+            let mut receiver = Receiver::builder()
+                .max_ratchet_steps(max_ratchet_steps)
+                .build();
+            receiver
+                .set_ratcheting_encryption_key(key_id, KEY_MATERIAL)
+                .unwrap();
+
+            (sender, receiver)
+        }
+
+        #[test]
+        fn ratchet_within_max_keys() {
+            let (mut sender, _) = sender_and_receiver(4);
+            let mut receiver = Receiver::builder().max_keys(1).build();
+            receiver
+                .set_ratcheting_encryption_key(RatchetingKeyId::new(5_u8, 4).unwrap(), KEY_MATERIAL)
+                .unwrap();
+
+            for _ in 0..2 {
+                sender.ratchet_encryption_key().unwrap();
+                sender.ratchet_encryption_key().unwrap();
+                let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+                assert!(receiver.decrypt(encrypted, 0).is_ok());
+                assert_eq!(receiver.key_store_stats().keys, 1);
+            }
+        }
+
+        #[test]
+        fn derive_evicted_key_of_current_generation_again() {
+            let (mut sender, _) = sender_and_receiver(4);
+            let mut receiver = Receiver::builder().max_keys(1).build();
+            receiver
+                .set_ratcheting_encryption_key(RatchetingKeyId::new(5_u8, 4).unwrap(), KEY_MATERIAL)
+                .unwrap();
+            sender.ratchet_encryption_key().unwrap();
+            let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+            assert!(receiver.decrypt(encrypted, 0).is_ok());
+
+            receiver.set_encryption_key(0x100_u64, "foobar").unwrap();
+            assert!(!receiver.keys.contains_key(&KeyId::from(0x51_u64)));
+            assert_eq!(receiver.key_store_stats().evicted_keys, 2);
+
+            let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+            assert!(receiver.decrypt(encrypted, 0).is_ok());
+            sender.ratchet_encryption_key().unwrap();
+            let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+            assert!(receiver.decrypt(encrypted, 0).is_ok());
+        }
+
+        #[test]
+        fn follow_ratchet_steps_of_sender() {
+            let (mut sender, mut receiver) = sender_and_receiver(1);
+
+            // more steps than the generation can represent
+            for _ in 0..20 {
+                let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+                let decrypted = receiver.decrypt(encrypted, 0).unwrap();
+                assert_eq!(decrypted, b"foobar is unsafe");
+
+                sender.ratchet_encryption_key().unwrap();
+            }
+        }
+
+        #[test]
+        fn ratchet_multiple_steps_within_limit() {
+            let (mut sender, mut receiver) = sender_and_receiver(3);
+            let mut old_encrypted = Vec::new();
+            for _ in 0..3 {
+                old_encrypted.push(sender.encrypt("foobar is unsafe", 0).unwrap().to_vec());
+                sender.ratchet_encryption_key().unwrap();
+            }
+
+            let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+            let decrypted = receiver.decrypt(encrypted, 0).unwrap();
+            assert_eq!(decrypted, b"foobar is unsafe");
+
+            // frames of skipped generations can still be decrypted
+            for encrypted in old_encrypted {
+                assert!(receiver.decrypt(&encrypted, 0).is_ok());
+            }
+        }
+
+        #[test]
+        fn fail_to_ratchet_beyond_limit() {
+            let (mut sender, mut receiver) = sender_and_receiver(2);
+            for _ in 0..3 {
+                sender.ratchet_encryption_key().unwrap();
+            }
+
+            let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+            assert_eq!(
+                receiver.decrypt(encrypted, 0),
+                Err(SframeError::MissingDecryptionKey(KeyId::from(0x53_u64)))
+            );
+        }
+
+        #[test]
+        fn keep_generation_on_failed_decryption() {
+            let (mut sender, mut receiver) = sender_and_receiver(2);
+            sender.ratchet_encryption_key().unwrap();
+
+            let mut encrypted = sender.encrypt("foobar is unsafe", 0).unwrap().to_vec();
+            *encrypted.last_mut().unwrap() ^= 1;
+            assert_eq!(
+                receiver.decrypt(&encrypted, 0),
+                Err(SframeError::DecryptionFailure)
+            );
+            assert!(!receiver.keys.contains_key(&KeyId::from(0x51_u64)));
+        }
+    }
+
+    mod key_expiry {
+        use super::*;
+        use alloc::sync::Arc;
+        use core::sync::atomic::AtomicU64;
+
+        #[derive(Clone, Default)]
+        struct TestClock(Arc<AtomicU64>);
+
+        impl TestClock {
+            fn advance(&self, millis: u64) {
+                self.0.fetch_add(millis, Ordering::SeqCst);
+            }
+        }
+
+        impl Clock for TestClock {
+            fn now(&self) -> Duration {
+                Duration::from_millis(self.0.load(Ordering::SeqCst))
+            }
+        }
+
+        fn senders() -> (Sender, Sender) {
+            let mut old_sender = Sender::new(1_u8);
+            old_sender.set_encryption_key("foobar is unsafe").unwrap();
+            let mut new_sender = Sender::new(2_u8);
+            new_sender
+                .set_encryption_key("foobar is still unsafe")
+                .unwrap();
+            (old_sender, new_sender)
+        }
+
+        fn receiver(clock: &TestClock) -> Receiver {
+            let mut receiver = Receiver::builder().clock(clock.clone()).build();
+            receiver
+                .set_encryption_key(1_u8, "foobar is unsafe")
+                .unwrap();
+            receiver
+                .set_encryption_key(2_u8, "foobar is still unsafe")
+                .unwrap();
+            receiver
+        }
+
+        #[test]
+        fn expire_key_after_grace_time() {
+            let clock = TestClock::default();
+            let mut receiver = receiver(&clock);
+            let (mut old_sender, mut new_sender) = senders();
+            receiver
+                .retire_encryption_key(1_u8, GracePeriod::Time(Duration::from_millis(100)))
+                .unwrap();
+
+            clock.advance(99);
+            assert!(receiver
+                .decrypt(old_sender.encrypt("late", 0).unwrap(), 0)
+                .is_ok());
+
+            clock.advance(1);
+            assert!(receiver
+                .decrypt(new_sender.encrypt("new", 0).unwrap(), 0)
+                .is_ok());
+            assert!(!receiver.keys.contains_key(&KeyId::from(1_u8)));
+            assert_eq!(
+                receiver.decrypt(old_sender.encrypt("too late", 0).unwrap(), 0),
+                Err(SframeError::MissingDecryptionKey(KeyId::from(1_u8)))
+            );
+        }
+
+        #[test]
+        fn expire_key_after_frames_of_newer_key() {
+            let mut receiver = receiver(&TestClock::default());
+            let (mut old_sender, mut new_sender) = senders();
+            receiver
+                .retire_encryption_key(
+                    1_u8,
+                    GracePeriod::Frames {
+                        newer_key_id: KeyId::from(2_u8),
+                        frames: 2,
+                    },
+                )
+                .unwrap();
+
+            assert!(receiver
+                .decrypt(new_sender.encrypt("new", 0).unwrap(), 0)
+                .is_ok());
+            assert!(receiver
+                .decrypt(old_sender.encrypt("late", 0).unwrap(), 0)
+                .is_ok());
+            assert!(receiver
+                .decrypt(new_sender.encrypt("new", 0).unwrap(), 0)
+                .is_ok());
+            assert_eq!(
+                receiver.decrypt(old_sender.encrypt("too late", 0).unwrap(), 0),
+                Err(SframeError::MissingDecryptionKey(KeyId::from(1_u8)))
+            );
+        }
+
+        #[test]
+        fn keep_key_which_is_set_again() {
+            let clock = TestClock::default();
+            let mut receiver = receiver(&clock);
+            let (mut old_sender, _) = senders();
+            receiver
+                .retire_encryption_key(1_u8, GracePeriod::Time(Duration::from_millis(100)))
+                .unwrap();
+            receiver
+                .set_encryption_key(1_u8, "foobar is unsafe")
+                .unwrap();
+
+            clock.advance(200);
+            assert!(receiver
+                .decrypt(old_sender.encrypt("late", 0).unwrap(), 0)
+                .is_ok());
+        }
+
+        #[test]
+        fn fail_to_retire_unknown_keys() {
+            let mut receiver = receiver(&TestClock::default());
+            assert_eq!(
+                receiver.retire_encryption_key(3_u8, GracePeriod::Time(Duration::ZERO)),
+                Err(SframeError::MissingDecryptionKey(KeyId::from(3_u8)))
+            );
+            assert_eq!(
+                receiver.retire_encryption_key(
+                    1_u8,
+                    GracePeriod::Frames {
+                        newer_key_id: KeyId::from(3_u8),
+                        frames: 1
+                    }
+                ),
+                Err(SframeError::MissingDecryptionKey(KeyId::from(3_u8)))
+            );
+        }
+
+        #[test]
+        fn fail_to_retire_by_time_without_clock() {
+            let mut receiver = receiver_with_key();
+            receiver.options.clock = None;
+            assert_eq!(
+                receiver.retire_encryption_key(1234_u64, GracePeriod::Time(Duration::ZERO)),
+                Err(SframeError::ClockNotConfigured)
+            );
+        }
+    }
+
+    mod mls {
+        use super::*;
+        use crate::mls::MlsKeyIdLayout;
+
+        fn epoch(epoch: u64) -> MlsEpoch {
+            let exporter_secret = [epoch as u8; 32];
+            MlsEpoch::new(
+                epoch,
+                &exporter_secret,
+                MlsKeyIdLayout::new(2, 8).unwrap(),
+                CipherSuiteVariant::AesGcm128Sha256,
+            )
+            .unwrap()
+        }
+
+        fn encrypt(epoch: &MlsEpoch, index: u32) -> Vec<u8> {
+            let mut sender = Sender::with_cipher_suite(
+                epoch.key_id(index).unwrap(),
+                CipherSuiteVariant::AesGcm128Sha256,
+            );
+            sender
+                .set_encryption_key(&epoch.sender_base_key(index).unwrap())
+                .unwrap();
+            sender.encrypt("foobar is unsafe", 0).unwrap().to_vec()
+        }
+
+        #[test]
+        fn decrypt_frames_of_all_members() {
+            let epoch = epoch(1);
+            let mut receiver = Receiver::default();
+            receiver.set_mls_epoch(&epoch, [0, 1, 5]).unwrap();
+
+            for index in [0, 1, 5] {
+                let decrypted = receiver.decrypt(&encrypt(&epoch, index), 0).unwrap();
+                assert_eq!(decrypted, b"foobar is unsafe");
+            }
+            assert_eq!(
+                receiver.decrypt(&encrypt(&epoch, 2), 0),
+                Err(SframeError::MissingDecryptionKey(KeyId::from(0x09_u64)))
+            );
+        }
+
+        #[test]
+        fn retire_old_epochs() {
+            let mut receiver = Receiver::default();
+            for n in 1..=3 {
+                receiver.set_mls_epoch(&epoch(n), [0, 1]).unwrap();
+            }
+
+            receiver.remove_mls_epochs_before(3);
+            assert!(receiver.decrypt(&encrypt(&epoch(3), 1), 0).is_ok());
+            assert_eq!(
+                receiver.decrypt(&encrypt(&epoch(2), 1), 0),
+                Err(SframeError::MissingDecryptionKey(KeyId::from(0x06_u64)))
+            );
+            assert!(!receiver.remove_mls_epoch(1));
+
+            assert!(receiver.remove_mls_epoch(3));
+            assert!(receiver.keys.key_ids().is_empty());
+        }
+
+        #[test]
+        fn replace_epoch_with_colliding_key_ids() {
+            let mut receiver = Receiver::default();
+            receiver.set_mls_epoch(&epoch(1), [0, 1]).unwrap();
+            // the epoch bits of the KID wrap around after 4 epochs
+            receiver.set_mls_epoch(&epoch(5), [1]).unwrap();
+
+            assert_eq!(
+                receiver.decrypt(&encrypt(&epoch(1), 0), 0),
+                Err(SframeError::MissingDecryptionKey(KeyId::from(0x01_u64)))
+            );
+            assert_eq!(
+                receiver.decrypt(&encrypt(&epoch(1), 1), 0),
+                Err(SframeError::DecryptionFailure)
+            );
+            assert!(receiver.decrypt(&encrypt(&epoch(5), 1), 0).is_ok());
+
+            // removing the old epoch keeps the keys of the new one
+            assert!(!receiver.remove_mls_epoch(1));
+            receiver.remove_mls_epochs_before(5);
+            assert!(receiver.keys.contains_key(&KeyId::from(0x05_u64)));
+        }
+
+        #[test]
+        fn keep_key_which_replaced_member_when_removing_epoch() {
+            let epoch = epoch(1);
+            let mut receiver = Receiver::default();
+            receiver.set_mls_epoch(&epoch, [0, 1]).unwrap();
+            receiver
+                .retire_encryption_key(
+                    epoch.key_id(1).unwrap(),
+                    GracePeriod::Frames {
+                        newer_key_id: epoch.key_id(0).unwrap(),
+                        frames: 10,
+                    },
+                )
+                .unwrap();
+            receiver
+                .set_encryption_key(epoch.key_id(0).unwrap(), "foobar")
+                .unwrap();
+
+            assert!(receiver.remove_mls_epoch(1));
+            assert!(receiver.keys.contains_key(&epoch.key_id(0).unwrap()));
+            assert!(!receiver.keys.contains_key(&epoch.key_id(1).unwrap()));
+            assert!(receiver.keys.expiring_key_ids().is_empty());
+        }
+
+        #[test]
+        fn keep_key_which_replaced_evicted_member_when_removing_epoch() {
+            let epoch = epoch(1);
+            let mut receiver = Receiver::builder().max_keys(3).build();
+            receiver.set_mls_epoch(&epoch, [0, 1]).unwrap();
+            receiver.set_encryption_key(0x100_u64, "foobar").unwrap();
+            assert!(receiver.decrypt(&encrypt(&epoch, 1), 0).is_ok());
+
+            // evicts member 0, whose KID is then reused by another key
+            receiver.set_encryption_key(0x200_u64, "foobar").unwrap();
+            receiver
+                .set_encryption_key(epoch.key_id(0).unwrap(), "foobar")
+                .unwrap();
+            assert_eq!(receiver.key_store_stats().evicted_keys, 2);
+
+            assert!(receiver.remove_mls_epoch(1));
+            assert_eq!(
+                receiver.keys.key_ids(),
+                [epoch.key_id(0).unwrap(), KeyId::from(0x200_u64)]
+            );
+        }
+
+        #[test]
+        fn fail_to_set_epoch_with_more_members_than_max_keys() {
+            let epoch = epoch(1);
+            let mut receiver = Receiver::builder().max_keys(2).build();
+
+            assert_eq!(
+                receiver.set_mls_epoch(&epoch, [0, 1, 2]),
+                Err(SframeError::TooManyKeys {
+                    keys: 3,
+                    max_keys: 2
+                })
+            );
+            assert!(receiver.keys.key_ids().is_empty());
+
+            receiver.set_mls_epoch(&epoch, [0, 1, 1]).unwrap();
+            for index in [0, 1] {
+                assert!(receiver.decrypt(&encrypt(&epoch, index), 0).is_ok());
+            }
+            assert_eq!(receiver.key_store_stats().evicted_keys, 0);
+        }
+
+        #[test]
+        fn evict_other_keys_instead_of_members() {
+            let epoch = epoch(1);
+            let mut receiver = Receiver::builder().max_keys(3).build();
+            receiver.set_encryption_key(0x100_u64, "foobar").unwrap();
+            receiver.set_encryption_key(0x200_u64, "foobar").unwrap();
+
+            receiver.set_mls_epoch(&epoch, [0, 1, 2]).unwrap();
+            for index in [0, 1, 2] {
+                assert!(receiver.decrypt(&encrypt(&epoch, index), 0).is_ok());
+            }
+            assert_eq!(receiver.key_store_stats().evicted_keys, 2);
+        }
+    }
 }
@@ -1,7 +1,10 @@
 // Copyright (c) 2023 GoTo Group, Inc
 // SPDX-License-Identifier: Apache-2.0 AND MIT
 
+use alloc::{boxed::Box, vec::Vec};
+
 use crate::{
+    codec::UnencryptedPrefix,
     crypto::{
         aead::AeadEncrypt,
         cipher_suite::{CipherSuite, CipherSuiteVariant},
@@ -9,7 +12,10 @@ use crate::{
         secret::Secret,
     },
     error::{Result, SframeError},
-    header::{FrameCountGenerator, Header, HeaderFields, KeyId},
+    header::{FrameCount, FrameCountGenerator, Header, HeaderFields, KeyId, Serialization},
+    key_usage::{KeyUsage, KeyUsageLimits, KeyUsageTracker},
+    ratchet::{RatchetingBaseKey, RatchetingKeyId},
+    util::check_skip,
 };
 
 pub struct Sender {
@@ -17,9 +23,26 @@ pub struct Sender {
     key_id: KeyId,
     cipher_suite: CipherSuite,
     secret: Option<Secret>,
+    ratcheting_key_id: Option<RatchetingKeyId>,
+    ratcheting_base_key: Option<RatchetingBaseKey>,
+    key_usage: KeyUsageTracker,
+    rekey_callback: Option<RekeyCallback>,
+    staged_key: Option<StagedKey>,
+    key_switch_after: Option<u64>,
     buffer: Vec<u8>,
 }
 
+/// A key which replaces the current one on [`Sender::switch_encryption_key`]
+/// or after the nof frames configured with [`Sender::with_key_switch_after`]
+struct StagedKey {
+    key_id: KeyId,
+    secret: Secret,
+    frames_until_switch: Option<u64>,
+}
+
+/// Called with the KID of the current key, once its soft usage limit is reached
+pub type RekeyCallback = Box<dyn FnMut(KeyId) + Send + Sync>;
+
 impl Sender {
     pub fn new<K>(key_id: K) -> Sender
     where
@@ -28,27 +51,109 @@ impl Sender {
         Self::with_cipher_suite(key_id, CipherSuiteVariant::AesGcm256Sha512)
     }
 
-    pub fn with_cipher_suite<K>(key_id: K, variant: CipherSuiteVariant) -> Sender
+    pub fn with_cipher_suite<K, C>(key_id: K, cipher_suite: C) -> Sender
     where
         K: Into<KeyId>,
+        C: Into<CipherSuite>,
     {
-        let cipher_suite: CipherSuite = variant.into();
+        let cipher_suite: CipherSuite = cipher_suite.into();
         let key_id = key_id.into();
         log::debug!("Setting up sframe Sender");
         log::trace!(
-            "KeyID {:?} (ciphersuite {:?})",
+            "KeyID {:?} (ciphersuite {:?}, auth tag length {})",
             key_id,
-            cipher_suite.variant
+            cipher_suite.variant,
+            cipher_suite.auth_tag_len
         );
         Sender {
             frame_count: Default::default(),
             key_id,
             cipher_suite,
             secret: None,
+            ratcheting_key_id: None,
+            ratcheting_base_key: None,
+            key_usage: KeyUsageTracker::new(cipher_suite.variant.into()),
+            rekey_callback: None,
+            staged_key: None,
+            key_switch_after: None,
             buffer: Default::default(),
         }
     }
 
+    /// Creates a [`Sender`] whose key can be ratcheted with [`Sender::ratchet_encryption_key`],
+    /// where the generation of the key is signaled in the KID
+    pub fn with_ratcheting_key_id<C>(key_id: RatchetingKeyId, cipher_suite: C) -> Sender
+    where
+        C: Into<CipherSuite>,
+    {
+        Sender {
+            ratcheting_key_id: Some(key_id),
+            ..Self::with_cipher_suite(key_id, cipher_suite)
+        }
+    }
+
+    /// Continues with the given frame count instead of 0, e.g. with the [`Sender::current_frame_count`]
+    /// persisted before a restart, so no nonce is reused with the same key
+    #[must_use]
+    pub fn with_frame_count<F>(mut self, frame_count: F) -> Sender
+    where
+        F: Into<FrameCount>,
+    {
+        self.frame_count = FrameCountGenerator::new(frame_count.into());
+        self
+    }
+
+    /// Overrides the usage limits of this [`Sender`], which default to the limits of its
+    /// [`CipherSuiteVariant`], see [`KeyUsageLimits`]. As a [`Sender`] has a single cipher suite,
+    /// the override is per sender and not per variant, it also applies to staged and ratcheted keys.
+    #[must_use]
+    pub fn with_key_usage_limits(mut self, limits: KeyUsageLimits) -> Sender {
+        self.key_usage.limits = limits;
+        self
+    }
+
+    /// Registers a callback, which is called once the soft usage limit of the current key is reached
+    #[must_use]
+    pub fn with_rekey_callback<F>(mut self, callback: F) -> Sender
+    where
+        F: FnMut(KeyId) + Send + Sync + 'static,
+    {
+        self.rekey_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Switches to a staged key automatically, once the given nof frames have been encrypted after staging it
+    #[must_use]
+    pub fn with_key_switch_after(mut self, frames: u64) -> Sender {
+        self.key_switch_after = Some(frames);
+        self
+    }
+
+    /// The KID of the key which is currently used for encryption
+    pub fn key_id(&self) -> KeyId {
+        self.key_id
+    }
+
+    /// The KID of the staged key, which is not yet used for encryption
+    pub fn staged_key_id(&self) -> Option<KeyId> {
+        self.staged_key.as_ref().map(|staged_key| staged_key.key_id)
+    }
+
+    /// Nof frames and bytes which have been encrypted with the current key
+    pub fn key_usage(&self) -> KeyUsage {
+        self.key_usage.usage
+    }
+
+    /// Returns `true` once the soft usage limit of the current key is reached and a new key should be set
+    pub fn is_rekey_needed(&self) -> bool {
+        self.key_usage.is_rekey_needed()
+    }
+
+    /// The frame count of the next encrypted frame
+    pub fn current_frame_count(&self) -> FrameCount {
+        self.frame_count.current()
+    }
+
     pub fn encrypt<Plaintext>(
         &mut self,
         unencrypted_payload: &Plaintext,
@@ -59,63 +164,297 @@ impl Sender {
     {
         let unencrypted_payload = unencrypted_payload.as_ref();
 
-        log::trace!("Encrypt frame # {:#?}!", self.frame_count);
-        if let Some(ref secret) = self.secret {
-            log::trace!("Skipping first {} bytes in frame", skip);
-
-            let frame_count = self.frame_count.increment();
-            log::trace!("frame count: {:?}", frame_count);
+        let mut buffer = core::mem::take(&mut self.buffer);
+        buffer.resize(self.encrypted_frame_len(unencrypted_payload.len()), 0);
+        let result = self.encrypt_into(unencrypted_payload, skip, &mut buffer);
+        self.buffer = buffer;
 
-            log::trace!("Creating SFrame Header");
-            let header = Header::with_frame_count(self.key_id, frame_count);
-
-            log::trace!(
-                "Sender: FrameCount: {:?}, FrameCount length: {:?}, KeyId: {:?}, Extend: {:?}",
-                header.frame_count(),
-                header.frame_count().length_in_bytes(),
-                header.key_id(),
-                header.is_extended()
-            );
+        let frame_len = result?;
+        Ok(&self.buffer[..frame_len])
+    }
 
-            let skipped_payload = &unencrypted_payload[0..skip];
-            let to_be_encrypted_payload = &unencrypted_payload[skip..];
+    /// Encrypts a video frame, whose codec specific header is left unencrypted, see [`crate::codec`].
+    /// The state of the codec is only updated if the frame is encrypted successfully.
+    /// ```
+    /// # use sframe::{codec::Vp8, receiver::Receiver, sender::Sender};
+    /// let mut sender = Sender::new(42_u64);
+    /// sender.set_encryption_key("pw123").unwrap();
+    /// let mut receiver = Receiver::default();
+    /// receiver.set_encryption_key(42_u64, "pw123").unwrap();
+    ///
+    /// let delta_frame = [0x31, 0x02, 0x00, 0xde, 0xad, 0xbe, 0xef];
+    /// let encrypted = sender.encrypt_video_frame(&delta_frame, &mut Vp8).unwrap();
+    /// assert_eq!(encrypted[..3], delta_frame[..3]);
+    ///
+    /// let decrypted = receiver.decrypt_video_frame(encrypted, &mut Vp8).unwrap();
+    /// assert_eq!(decrypted, delta_frame);
+    /// ```
+    pub fn encrypt_video_frame<Plaintext, Codec>(
+        &mut self,
+        unencrypted_frame: &Plaintext,
+        codec: &mut Codec,
+    ) -> Result<&[u8]>
+    where
+        Plaintext: AsRef<[u8]> + ?Sized,
+        Codec: UnencryptedPrefix + ?Sized,
+    {
+        let unencrypted_frame = unencrypted_frame.as_ref();
+        let skip = codec.unencrypted_len(unencrypted_frame)?;
+        let encrypted_frame = self.encrypt(unencrypted_frame, skip)?;
+        codec.update(unencrypted_frame);
+        Ok(encrypted_frame)
+    }
 
-            self.buffer.clear();
-            let frame = &mut self.buffer;
-            frame.extend_from_slice(skipped_payload);
-            frame.extend(Vec::from(&header));
-            frame.extend(to_be_encrypted_payload);
+    /// Encrypts a frame into the given buffer and returns the size of the encrypted frame.
+    /// The buffer needs to be at least of the size returned by [`Sender::encrypted_frame_len`].
+    pub fn encrypt_into<Plaintext>(
+        &mut self,
+        unencrypted_payload: &Plaintext,
+        skip: usize,
+        encrypted_frame: &mut [u8],
+    ) -> Result<usize>
+    where
+        Plaintext: AsRef<[u8]> + ?Sized,
+    {
+        let unencrypted_payload = unencrypted_payload.as_ref();
+        check_skip(skip, unencrypted_payload.len())?;
+        let frame_len = self.encrypted_frame_len(unencrypted_payload.len());
+        if encrypted_frame.len() < frame_len {
+            return Err(SframeError::BufferTooSmall {
+                needed: frame_len,
+                available: encrypted_frame.len(),
+            });
+        }
+        // the switch may renew the frame count, which is already accounted for in `frame_len`
+        self.switch_to_staged_key_if_due();
+        let secret = self
+            .secret
+            .as_ref()
+            .ok_or(SframeError::MissingEncryptionKey)?;
+        let payload_len = unencrypted_payload.len() - skip;
+        self.key_usage.check(payload_len)?;
+
+        let header = Self::next_header(&mut self.frame_count, self.key_id)?;
+        let payload_begin = skip + header.size();
+        let payload_end = frame_len - self.cipher_suite.auth_tag_len;
+
+        log::trace!("Skipping first {} bytes in frame", skip);
+        encrypted_frame[..skip].copy_from_slice(&unencrypted_payload[..skip]);
+        header.serialize(&mut encrypted_frame[skip..payload_begin])?;
+        encrypted_frame[payload_begin..payload_end].copy_from_slice(&unencrypted_payload[skip..]);
+
+        let (leading_buffer, encrypt_buffer) =
+            encrypted_frame[..payload_end].split_at_mut(payload_begin);
+
+        log::trace!("Encrypting Frame of size {}", unencrypted_payload.len());
+        let tag = self.cipher_suite.encrypt(
+            encrypt_buffer,
+            secret,
+            &leading_buffer[skip..],
+            header.frame_count(),
+        )?;
+        encrypted_frame[payload_end..frame_len].copy_from_slice(&tag);
+        self.record_key_usage(payload_len);
+        self.count_down_key_switch();
+
+        Ok(frame_len)
+    }
 
-            let (leading_buffer, encrypt_buffer) = frame.split_at_mut(skip + header.size());
+    /// Encrypts a frame in place, i.e. the sframe header is inserted after the first `skip` bytes
+    /// and the authentication tag is appended. No reallocation happens if `frame` has a capacity
+    /// of at least [`Sender::encrypted_frame_len`].
+    pub fn encrypt_in_place(&mut self, frame: &mut Vec<u8>, skip: usize) -> Result<()> {
+        check_skip(skip, frame.len())?;
+        self.switch_to_staged_key_if_due();
+        let secret = self
+            .secret
+            .as_ref()
+            .ok_or(SframeError::MissingEncryptionKey)?;
+        let payload_len = frame.len() - skip;
+        self.key_usage.check(payload_len)?;
+
+        let header = Self::next_header(&mut self.frame_count, self.key_id)?;
+        let payload_begin = skip + header.size();
+        let payload_end = frame.len() + header.size();
+
+        log::trace!("Skipping first {} bytes in frame", skip);
+        frame.reserve(header.size() + self.cipher_suite.auth_tag_len);
+        frame.resize(payload_end, 0);
+        frame.copy_within(skip..payload_end - header.size(), payload_begin);
+        header.serialize(&mut frame[skip..payload_begin])?;
+
+        let (leading_buffer, encrypt_buffer) = frame.split_at_mut(payload_begin);
+
+        log::trace!("Encrypting Frame of size {}", encrypt_buffer.len());
+        let tag = self.cipher_suite.encrypt(
+            encrypt_buffer,
+            secret,
+            &leading_buffer[skip..],
+            header.frame_count(),
+        )?;
+        frame.extend(tag);
+        self.record_key_usage(payload_len);
+        self.count_down_key_switch();
 
-            log::trace!("Encrypting Frame of size {}", unencrypted_payload.len(),);
-            let tag = self.cipher_suite.encrypt(
-                encrypt_buffer,
-                secret,
-                &leading_buffer[skip..],
-                header.frame_count(),
-            )?;
+        Ok(())
+    }
 
-            frame.extend(tag.as_ref());
+    /// Returns the size of the next encrypted frame for an unencrypted frame of the given size,
+    /// i.e. the unencrypted size plus the size of the sframe header and the authentication tag
+    pub fn encrypted_frame_len(&self, unencrypted_len: usize) -> usize {
+        let header = match &self.staged_key {
+            Some(staged_key) if staged_key.frames_until_switch == Some(0) => {
+                let mut frame_count = self.frame_count;
+                frame_count.renew();
+                Header::with_frame_count(staged_key.key_id, frame_count.current())
+            }
+            _ => Header::with_frame_count(self.key_id, self.frame_count.current()),
+        };
+        unencrypted_len + header.size() + self.cipher_suite.auth_tag_len
+    }
 
-            Ok(frame)
+    /// Sets a new key, which resets the [`Sender::key_usage`] and is needed to continue
+    /// with frame count 0 once the frame counts are exhausted.
+    /// A staged key is discarded, so a scheduled switch cannot override the new key.
+    pub fn set_encryption_key<KeyMaterial>(&mut self, key_material: &KeyMaterial) -> Result<()>
+    where
+        KeyMaterial: AsRef<[u8]> + ?Sized,
+    {
+        if let Some(ratcheting_key_id) = self.ratcheting_key_id {
+            let base_key = RatchetingBaseKey::new(ratcheting_key_id, key_material.as_ref());
+            self.secret = Some(base_key.expand_as_secret(&self.cipher_suite)?);
+            self.ratcheting_base_key = Some(base_key);
         } else {
-            Err(SframeError::MissingEncryptionKey)
+            self.secret = Some(
+                KeyMaterial(key_material.as_ref())
+                    .expand_as_secret(&self.cipher_suite, self.key_id)?,
+            );
+        }
+        if let Some(staged_key) = self.staged_key.take() {
+            log::debug!(
+                "Discarding staged KeyID {:?} in sframe Sender",
+                staged_key.key_id
+            );
         }
+        self.frame_count.renew();
+        self.key_usage.reset();
+        Ok(())
     }
 
-    pub fn set_encryption_key<KeyMaterial>(&mut self, key_material: &KeyMaterial) -> Result<()>
+    /// Advances the base key to the next ratchet step and increments the generation in the KID.
+    /// Fails if the [`Sender`] was not created with [`Sender::with_ratcheting_key_id`]
+    /// or no encryption key has been set yet.
+    pub fn ratchet_encryption_key(&mut self) -> Result<()> {
+        if self.ratcheting_key_id.is_none() {
+            return Err(SframeError::RatchetingNotConfigured);
+        }
+        let base_key = self
+            .ratcheting_base_key
+            .as_ref()
+            .ok_or(SframeError::MissingEncryptionKey)?
+            .next(&self.cipher_suite)?;
+        let secret = base_key.expand_as_secret(&self.cipher_suite)?;
+        log::debug!("Ratcheting sframe Sender to KeyID {:?}", base_key.key_id);
+
+        self.key_id = base_key.key_id.into();
+        self.ratcheting_key_id = Some(base_key.key_id);
+        self.ratcheting_base_key = Some(base_key);
+        self.secret = Some(secret);
+        self.frame_count.renew();
+        self.key_usage.reset();
+        Ok(())
+    }
+
+    /// Stages the key for the given KID, while frames are still encrypted with the current key,
+    /// e.g. until all receivers have the new key. Replaces a previously staged key.
+    pub fn stage_encryption_key<K, KeyMaterial>(
+        &mut self,
+        key_id: K,
+        key_material: &KeyMaterial,
+    ) -> Result<()>
     where
+        K: Into<KeyId>,
         KeyMaterial: AsRef<[u8]> + ?Sized,
     {
-        self.secret =
-            Some(KeyMaterial(key_material.as_ref()).expand_as_secret(&self.cipher_suite)?);
+        let key_id = key_id.into();
+        let secret =
+            KeyMaterial(key_material.as_ref()).expand_as_secret(&self.cipher_suite, key_id)?;
+        log::debug!("Staging KeyID {:?} in sframe Sender", key_id);
+
+        self.staged_key = Some(StagedKey {
+            key_id,
+            secret,
+            frames_until_switch: self.key_switch_after,
+        });
         Ok(())
     }
+
+    /// Switches to the staged key, which resets the [`Sender::key_usage`].
+    /// A ratcheting [`Sender`] does not ratchet the staged key.
+    /// Returns `false` if no key is staged.
+    pub fn switch_encryption_key(&mut self) -> bool {
+        let Some(staged_key) = self.staged_key.take() else {
+            return false;
+        };
+        log::debug!(
+            "Switching sframe Sender from KeyID {:?} to {:?}",
+            self.key_id,
+            staged_key.key_id
+        );
+
+        self.key_id = staged_key.key_id;
+        self.secret = Some(staged_key.secret);
+        self.ratcheting_key_id = None;
+        self.ratcheting_base_key = None;
+        self.frame_count.renew();
+        self.key_usage.reset();
+        true
+    }
+
+    fn switch_to_staged_key_if_due(&mut self) {
+        let is_due = self
+            .staged_key
+            .as_ref()
+            .is_some_and(|staged_key| staged_key.frames_until_switch == Some(0));
+        if is_due {
+            self.switch_encryption_key();
+        }
+    }
+
+    fn count_down_key_switch(&mut self) {
+        if let Some(frames) = self
+            .staged_key
+            .as_mut()
+            .and_then(|staged_key| staged_key.frames_until_switch.as_mut())
+        {
+            *frames = frames.saturating_sub(1);
+        }
+    }
+
+    fn record_key_usage(&mut self, payload_len: usize) {
+        if self.key_usage.record(payload_len) {
+            log::debug!("Soft usage limit of KeyID {:?} reached", self.key_id);
+            if let Some(rekey_callback) = &mut self.rekey_callback {
+                rekey_callback(self.key_id);
+            }
+        }
+    }
+
+    fn next_header(frame_count: &mut FrameCountGenerator, key_id: KeyId) -> Result<Header> {
+        let frame_count = frame_count.increment()?;
+        let header = Header::with_frame_count(key_id, frame_count);
+        log::trace!(
+            "Sender: FrameCount: {:?}, FrameCount length: {:?}, KeyId: {:?}, Extend: {:?}",
+            header.frame_count(),
+            header.frame_count().length_in_bytes(),
+            header.key_id(),
+            header.is_extended()
+        );
+        Ok(header)
+    }
 }
 
 #[cfg(test)]
-#[cfg(not(feature = "verify-test-vectors"))]
 mod test_on_wire_format {
     use super::*;
     use crate::receiver::Receiver;
@@ -154,7 +493,7 @@ mod test_on_wire_format {
 
         assert_eq!(
             hex::encode(encrypted),
-            "deadbeaf0000a160a9176ba4ce7ca128df74907d422e5064d1c23529"
+            "deadbeaf0032e82b29a3a700410aa8795fa10e70c797dff19501e9"
         );
     }
 
@@ -173,7 +512,7 @@ mod test_on_wire_format {
 
         assert_eq!(
             hex::encode(encrypted),
-            "deadbeaf0000a160a9176b6ebe53f594a64faa1f48a5246b202d13416bf671b3edae7704a862"
+            "deadbeaf0032e82b29a36d368abf57aad30b744f0c1d6803a354d3f43c244d7beb6a1dd8ed"
         );
     }
 }
@@ -181,6 +520,13 @@ mod test_on_wire_format {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::header::Deserialization;
+
+    #[test]
+    fn is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Sender>();
+    }
 
     #[test]
     fn fail_on_missing_secret() {
@@ -190,4 +536,356 @@ mod test {
 
         assert_eq!(encrypted, Err(SframeError::MissingEncryptionKey));
     }
+
+    fn sender_with_key() -> Sender {
+        let mut sender = Sender::new(1234_u64);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+        sender
+    }
+
+    #[test]
+    fn encrypt_into_buffer() {
+        let mut sender = sender_with_key();
+        let mut other_sender = sender_with_key();
+        let unencrypted_frame = b"skip this foobar is unsafe";
+
+        for _ in 0..10 {
+            let mut buffer = vec![0; sender.encrypted_frame_len(unencrypted_frame.len())];
+            let frame_len = sender
+                .encrypt_into(unencrypted_frame, 10, &mut buffer)
+                .unwrap();
+
+            let expected = other_sender.encrypt(unencrypted_frame, 10).unwrap();
+            assert_eq!(frame_len, buffer.len());
+            assert_eq!(buffer, expected);
+        }
+    }
+
+    #[test]
+    fn encrypt_in_place() {
+        let mut sender = sender_with_key();
+        let mut other_sender = sender_with_key();
+        let unencrypted_frame = b"skip this foobar is unsafe";
+
+        for _ in 0..10 {
+            let mut frame = Vec::with_capacity(sender.encrypted_frame_len(unencrypted_frame.len()));
+            frame.extend_from_slice(unencrypted_frame);
+            let capacity = frame.capacity();
+            sender.encrypt_in_place(&mut frame, 10).unwrap();
+
+            let expected = other_sender.encrypt(unencrypted_frame, 10).unwrap();
+            assert_eq!(frame, expected);
+            assert_eq!(frame.capacity(), capacity);
+        }
+    }
+
+    #[test]
+    fn fail_to_encrypt_with_skip_larger_than_frame() {
+        let mut sender = sender_with_key();
+        let expected = SframeError::SkipTooLarge {
+            skip: 17,
+            frame_len: 16,
+        };
+
+        assert_eq!(
+            sender.encrypt("foobar is unsafe", 17),
+            Err(expected.clone())
+        );
+        let mut frame = b"foobar is unsafe".to_vec();
+        assert_eq!(sender.encrypt_in_place(&mut frame, 17), Err(expected));
+        assert_eq!(frame, b"foobar is unsafe");
+    }
+
+    #[test]
+    fn fail_to_encrypt_into_too_small_buffer() {
+        let mut sender = sender_with_key();
+        let frame_len = sender.encrypted_frame_len(16);
+        let mut buffer = vec![0; frame_len - 1];
+
+        assert_eq!(
+            sender.encrypt_into("foobar is unsafe", 0, &mut buffer),
+            Err(SframeError::BufferTooSmall {
+                needed: frame_len,
+                available: frame_len - 1
+            })
+        );
+        // the frame count is not incremented
+        let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+        assert_eq!(Header::deserialize(encrypted).unwrap().frame_count(), 0);
+    }
+
+    #[test]
+    fn fail_to_ratchet_without_ratcheting_key_id() {
+        let mut sender = Sender::new(1_u8);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+
+        assert_eq!(
+            sender.ratchet_encryption_key(),
+            Err(SframeError::RatchetingNotConfigured)
+        );
+    }
+
+    #[test]
+    fn fail_to_ratchet_without_secret() {
+        let key_id = RatchetingKeyId::new(1_u8, 4).unwrap();
+        let mut sender =
+            Sender::with_ratcheting_key_id(key_id, CipherSuiteVariant::AesGcm128Sha256);
+
+        assert_eq!(
+            sender.ratchet_encryption_key(),
+            Err(SframeError::MissingEncryptionKey)
+        );
+    }
+
+    #[test]
+    fn signal_generation_in_key_id() {
+        let key_id = RatchetingKeyId::new(1_u8, 4).unwrap();
+        let mut sender =
+            Sender::with_ratcheting_key_id(key_id, CipherSuiteVariant::AesGcm128Sha256);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+
+        for generation in 0..20_u64 {
+            let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+            let header = Header::deserialize(encrypted).unwrap();
+            assert_eq!(header.key_id(), KeyId::from(0x10 | (generation % 16)));
+
+            sender.ratchet_encryption_key().unwrap();
+        }
+    }
+
+    #[test]
+    fn continue_with_persisted_frame_count() {
+        let mut sender = sender_with_key();
+        for _ in 0..3 {
+            sender.encrypt("foobar is unsafe", 0).unwrap();
+        }
+        let frame_count = sender.current_frame_count();
+        assert_eq!(frame_count, 3);
+
+        let mut sender = Sender::new(1234_u64).with_frame_count(frame_count);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+        let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+        assert_eq!(Header::deserialize(encrypted).unwrap().frame_count(), 3);
+        assert_eq!(sender.current_frame_count(), 4);
+    }
+
+    #[test]
+    fn refuse_to_encrypt_with_exhausted_frame_count() {
+        let mut sender = Sender::new(1234_u64).with_frame_count(u64::MAX - 1);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+
+        let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+        assert_eq!(
+            Header::deserialize(encrypted).unwrap().frame_count(),
+            u64::MAX - 1
+        );
+        assert_eq!(
+            sender.encrypt("foobar is unsafe", 0),
+            Err(SframeError::FrameCountExhausted)
+        );
+        let mut frame = b"foobar is unsafe".to_vec();
+        assert_eq!(
+            sender.encrypt_in_place(&mut frame, 0),
+            Err(SframeError::FrameCountExhausted)
+        );
+        assert_eq!(frame, b"foobar is unsafe");
+
+        // a new key allows to start again
+        sender.set_encryption_key("foobar is still unsafe").unwrap();
+        let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+        assert_eq!(Header::deserialize(encrypted).unwrap().frame_count(), 0);
+    }
+
+    #[test]
+    fn track_key_usage_per_key() {
+        let mut sender = sender_with_key();
+        sender.encrypt("skip this foobar is unsafe", 10).unwrap();
+        sender.encrypt_in_place(&mut b"foobar".to_vec(), 0).unwrap();
+        assert_eq!(
+            sender.key_usage(),
+            KeyUsage {
+                frames: 2,
+                bytes: 22
+            }
+        );
+
+        sender.set_encryption_key("foobar is still unsafe").unwrap();
+        assert_eq!(sender.key_usage(), KeyUsage::default());
+    }
+
+    #[test]
+    fn signal_rekey_and_refuse_to_exceed_key_usage_limits() {
+        let rekeyed_key_ids = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut sender = Sender::new(1234_u64)
+            .with_key_usage_limits(KeyUsageLimits {
+                soft: KeyUsage {
+                    frames: 2,
+                    bytes: u64::MAX,
+                },
+                hard: KeyUsage {
+                    frames: 3,
+                    bytes: u64::MAX,
+                },
+            })
+            .with_rekey_callback({
+                let rekeyed_key_ids = rekeyed_key_ids.clone();
+                move |key_id| rekeyed_key_ids.lock().unwrap().push(key_id)
+            });
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+
+        for _ in 0..3 {
+            sender.encrypt("foobar is unsafe", 0).unwrap();
+        }
+        assert!(sender.is_rekey_needed());
+        assert_eq!(*rekeyed_key_ids.lock().unwrap(), [KeyId::from(1234_u64)]);
+        assert_eq!(
+            sender.encrypt("foobar is unsafe", 0),
+            Err(SframeError::KeyUsageLimitExceeded)
+        );
+        // the frame count is not incremented
+        assert_eq!(sender.current_frame_count(), 3);
+
+        sender.set_encryption_key("foobar is still unsafe").unwrap();
+        assert!(!sender.is_rekey_needed());
+        assert!(sender.encrypt("foobar is unsafe", 0).is_ok());
+    }
+
+    #[test]
+    fn switch_to_staged_key_on_command() {
+        let mut sender = sender_with_key();
+        let mut receiver = crate::receiver::Receiver::default();
+        receiver
+            .set_encryption_key(1234_u64, "foobar is unsafe")
+            .unwrap();
+
+        assert!(!sender.switch_encryption_key());
+        sender
+            .stage_encryption_key(5678_u64, "foobar is still unsafe")
+            .unwrap();
+        assert_eq!(sender.staged_key_id(), Some(KeyId::from(5678_u64)));
+
+        // the current key stays active until the switch
+        let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+        assert_eq!(
+            Header::deserialize(encrypted).unwrap().key_id(),
+            KeyId::from(1234_u64)
+        );
+        assert!(receiver.decrypt(encrypted, 0).is_ok());
+
+        receiver
+            .set_encryption_key(5678_u64, "foobar is still unsafe")
+            .unwrap();
+        assert!(sender.switch_encryption_key());
+        assert_eq!(sender.key_id(), KeyId::from(5678_u64));
+        assert_eq!(sender.staged_key_id(), None);
+        assert_eq!(sender.key_usage(), KeyUsage::default());
+
+        let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+        assert_eq!(
+            Header::deserialize(encrypted).unwrap().key_id(),
+            KeyId::from(5678_u64)
+        );
+        assert!(receiver.decrypt(encrypted, 0).is_ok());
+    }
+
+    #[test]
+    fn switch_to_staged_key_after_configured_frames() {
+        let mut sender = Sender::new(1_u8).with_key_switch_after(2);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+        sender
+            .stage_encryption_key(0x1234_u64, "foobar is still unsafe")
+            .unwrap();
+
+        let mut frame = b"foobar".to_vec();
+        sender.encrypt_in_place(&mut frame, 0).unwrap();
+        let encrypted = sender.encrypt("foobar is unsafe", 0).unwrap();
+        assert_eq!(
+            Header::deserialize(encrypted).unwrap().key_id(),
+            KeyId::from(1_u64)
+        );
+        assert_eq!(sender.key_id(), KeyId::from(1_u8));
+
+        // the header with the new KID is larger
+        let encrypted_len = sender.encrypted_frame_len(6);
+        assert_eq!(encrypted_len, sender.encrypt("foobar", 0).unwrap().len());
+        let encrypted = sender.encrypt("foobar", 0).unwrap();
+        assert_eq!(
+            Header::deserialize(encrypted).unwrap().key_id(),
+            KeyId::from(0x1234_u64)
+        );
+        assert_eq!(sender.key_id(), KeyId::from(0x1234_u64));
+        assert_eq!(sender.staged_key_id(), None);
+    }
+
+    #[test]
+    fn discard_staged_key_when_setting_key() {
+        let mut sender = Sender::new(1_u8).with_key_switch_after(1);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+        sender
+            .stage_encryption_key(2_u8, "foobar is still unsafe")
+            .unwrap();
+
+        sender.set_encryption_key("foobar is safe").unwrap();
+        assert_eq!(sender.staged_key_id(), None);
+        assert!(!sender.switch_encryption_key());
+
+        let mut receiver = crate::receiver::Receiver::default();
+        receiver.set_encryption_key(1_u8, "foobar is safe").unwrap();
+        for _ in 0..3 {
+            let encrypted = sender.encrypt("foobar", 0).unwrap();
+            assert_eq!(
+                Header::deserialize(encrypted).unwrap().key_id(),
+                KeyId::from(1_u8)
+            );
+            assert_eq!(receiver.decrypt(encrypted, 0).unwrap(), b"foobar");
+        }
+    }
+
+    #[test]
+    fn switch_to_staged_key_with_exhausted_frame_count() {
+        let mut sender = Sender::new(1_u8)
+            .with_key_switch_after(1)
+            .with_frame_count(u64::MAX - 1);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+        sender
+            .stage_encryption_key(2_u8, "foobar is still unsafe")
+            .unwrap();
+        let mut receiver = crate::receiver::Receiver::default();
+        receiver
+            .set_encryption_key(1_u8, "foobar is unsafe")
+            .unwrap();
+        receiver
+            .set_encryption_key(2_u8, "foobar is still unsafe")
+            .unwrap();
+
+        let encrypted = sender.encrypt("foobar", 0).unwrap().to_vec();
+        assert_eq!(receiver.decrypt(&encrypted, 0).unwrap(), b"foobar");
+
+        // the switch renews the frame count, which shrinks the header
+        let encrypted_len = sender.encrypted_frame_len(6);
+        let encrypted = sender.encrypt("foobar", 0).unwrap().to_vec();
+        assert_eq!(encrypted.len(), encrypted_len);
+        assert_eq!(
+            Header::deserialize(&encrypted).unwrap().frame_count(),
+            FrameCount::from(0_u64)
+        );
+        assert_eq!(receiver.decrypt(&encrypted, 0).unwrap(), b"foobar");
+    }
+
+    #[test]
+    fn stop_ratcheting_after_switching_key() {
+        let key_id = RatchetingKeyId::new(1_u8, 4).unwrap();
+        let mut sender =
+            Sender::with_ratcheting_key_id(key_id, CipherSuiteVariant::AesGcm256Sha512);
+        sender.set_encryption_key("foobar is unsafe").unwrap();
+        sender
+            .stage_encryption_key(2_u8, "foobar is still unsafe")
+            .unwrap();
+
+        assert!(sender.switch_encryption_key());
+        assert_eq!(
+            sender.ratchet_encryption_key(),
+            Err(SframeError::RatchetingNotConfigured)
+        );
+    }
 }
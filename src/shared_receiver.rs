@@ -0,0 +1,592 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+use alloc::{boxed::Box, sync::Arc, vec, vec::Vec};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+};
+
+use crate::{
+    crypto::{
+        aead::AeadDecrypt, cipher_suite::CipherSuite, key_expansion::KeyMaterial, secret::Secret,
+    },
+    error::{Result, SframeError},
+    frame_validation::FrameValidation,
+    header::{Header, HeaderFields, KeyId},
+    key_store::{mls_member_secrets, KeyStore, StoredKey},
+    mls::MlsEpoch,
+    receiver::{
+        decrypt_frame_into, GracePeriod, KeyEvictionCallback, KeyStoreStats, ReceiverOptions,
+    },
+};
+
+/// A decryption key, whose validation state is locked only for validating and accepting a frame,
+/// so frames of the same sender are decrypted concurrently
+struct SharedKey {
+    secret: Secret,
+    cipher_suite: CipherSuite,
+    frame_validation: Mutex<Box<dyn FrameValidation>>,
+    /// value of [`SharedReceiver::use_counter`] when the key was added or last decrypted a frame
+    last_used: AtomicU64,
+    decrypted_frames: AtomicU64,
+}
+
+impl StoredKey for Arc<SharedKey> {
+    fn last_used(&self) -> u64 {
+        self.last_used.load(Ordering::Relaxed)
+    }
+
+    fn decrypted_frames(&self) -> u64 {
+        self.decrypted_frames.load(Ordering::Relaxed)
+    }
+}
+
+/// A receiver which is shared between threads, e.g. in an `Arc`: frames are decrypted concurrently
+/// while the keys are updated from another thread.
+/// It is configured like a [`crate::receiver::Receiver`] with [`crate::receiver::ReceiverBuilder::build_shared`],
+/// including the maximum nof keys and the clock of grace periods, and shares its key store, including MLS epochs.
+/// Unlike a [`crate::receiver::Receiver`], it does not support ratcheting senders, i.e. it has no
+/// `set_ratcheting_encryption_key` and ignores [`crate::receiver::ReceiverBuilder::max_ratchet_steps`].
+/// ```
+/// # use std::{sync::Arc, thread};
+/// # use sframe::{receiver::SharedReceiver, sender::Sender};
+/// let receiver = Arc::new(SharedReceiver::default());
+/// receiver.set_encryption_key(42_u64, "pw123").unwrap();
+///
+/// let mut sender = Sender::new(42_u64);
+/// sender.set_encryption_key("pw123").unwrap();
+/// let encrypted = sender.encrypt("hello", 0).unwrap().to_vec();
+///
+/// let worker = thread::spawn({
+///     let receiver = receiver.clone();
+///     move || receiver.decrypt(&encrypted, 0)
+/// });
+/// assert_eq!(worker.join().unwrap().unwrap(), b"hello");
+/// ```
+pub struct SharedReceiver {
+    keys: RwLock<KeyStore<Arc<SharedKey>>>,
+    use_counter: AtomicU64,
+    key_eviction_callback: Mutex<Option<KeyEvictionCallback>>,
+    options: ReceiverOptions,
+}
+
+impl Default for SharedReceiver {
+    fn default() -> Self {
+        SharedReceiver::from(ReceiverOptions::default())
+    }
+}
+
+impl From<ReceiverOptions> for SharedReceiver {
+    fn from(mut options: ReceiverOptions) -> Self {
+        log::debug!("Setting up shared sframe Receiver");
+        Self {
+            keys: RwLock::new(KeyStore::new(options.max_keys)),
+            use_counter: AtomicU64::new(0),
+            key_eviction_callback: Mutex::new(options.key_eviction_callback.take()),
+            options,
+        }
+    }
+}
+
+impl SharedReceiver {
+    /// Decrypts a frame into a newly allocated buffer
+    pub fn decrypt<EncryptedFrame>(
+        &self,
+        encrypted_frame: &EncryptedFrame,
+        skip: usize,
+    ) -> Result<Vec<u8>>
+    where
+        EncryptedFrame: AsRef<[u8]> + ?Sized,
+    {
+        let encrypted_frame = encrypted_frame.as_ref();
+        let mut buffer = vec![0; encrypted_frame.len()];
+        let frame_len = self.decrypt_into(encrypted_frame, skip, &mut buffer)?;
+        buffer.truncate(frame_len);
+        Ok(buffer)
+    }
+
+    /// Decrypts a frame into the given buffer and returns the size of the decrypted frame,
+    /// see [`crate::receiver::Receiver::decrypt_into`]
+    pub fn decrypt_into<EncryptedFrame>(
+        &self,
+        encrypted_frame: &EncryptedFrame,
+        skip: usize,
+        decrypted_frame: &mut [u8],
+    ) -> Result<usize>
+    where
+        EncryptedFrame: AsRef<[u8]> + ?Sized,
+    {
+        decrypt_frame_into(
+            encrypted_frame.as_ref(),
+            skip,
+            decrypted_frame,
+            |header, aad, encrypted_payload| self.decrypt_payload(header, aad, encrypted_payload),
+        )
+    }
+
+    /// Sets the key for the given KID, using the cipher suite configured in the [`ReceiverOptions`]
+    pub fn set_encryption_key<Id, KeyMaterial>(
+        &self,
+        key_id: Id,
+        key_material: &KeyMaterial,
+    ) -> Result<()>
+    where
+        Id: Into<KeyId>,
+        KeyMaterial: AsRef<[u8]> + ?Sized,
+    {
+        self.set_encryption_key_with_cipher_suite(key_id, key_material, self.options.cipher_suite)
+    }
+
+    /// Sets the key for the given KID, which is used with its own cipher suite.
+    /// Evicts the least recently used keys beyond [`crate::receiver::ReceiverBuilder::max_keys`].
+    pub fn set_encryption_key_with_cipher_suite<Id, KeyMaterial, C>(
+        &self,
+        key_id: Id,
+        key_material: &KeyMaterial,
+        cipher_suite: C,
+    ) -> Result<()>
+    where
+        Id: Into<KeyId>,
+        KeyMaterial: AsRef<[u8]> + ?Sized,
+        C: Into<CipherSuite>,
+    {
+        let key_id = key_id.into();
+        let cipher_suite = cipher_suite.into();
+        // the key is derived before locking, so decryption is not blocked by the key expansion
+        let secret = KeyMaterial(key_material.as_ref()).expand_as_secret(&cipher_suite, key_id)?;
+        let key = self.create_key(secret, cipher_suite);
+
+        let evicted_key_ids = self.write_keys().insert(vec![(key_id, key)]);
+        self.on_evicted_keys(evicted_key_ids);
+        Ok(())
+    }
+
+    pub fn remove_encryption_key<Id>(&self, key_id: Id) -> bool
+    where
+        Id: Into<KeyId>,
+    {
+        self.write_keys().remove(key_id.into())
+    }
+
+    /// Keeps the key of the given KID only for a grace period, see [`crate::receiver::Receiver::retire_encryption_key`]
+    pub fn retire_encryption_key<Id>(&self, key_id: Id, grace_period: GracePeriod) -> Result<()>
+    where
+        Id: Into<KeyId>,
+    {
+        let key_id = key_id.into();
+        let expiry =
+            self.write_keys()
+                .retire(key_id, grace_period, self.options.clock.as_deref())?;
+        log::debug!("SharedReceiver: retiring KeyID {:?} ({:?})", key_id, expiry);
+        Ok(())
+    }
+
+    /// Sets the keys of the given members of an MLS epoch, see [`crate::receiver::Receiver::set_mls_epoch`]
+    pub fn set_mls_epoch<Members>(&self, epoch: &MlsEpoch, members: Members) -> Result<()>
+    where
+        Members: IntoIterator<Item = u32>,
+    {
+        let cipher_suite = epoch.cipher_suite();
+        let keys = mls_member_secrets(epoch, members, self.options.max_keys)?
+            .into_iter()
+            .map(|(key_id, secret)| (key_id, self.create_key(secret, cipher_suite)))
+            .collect::<Vec<_>>();
+        let key_ids = keys.iter().map(|(key_id, _)| *key_id).collect::<Vec<_>>();
+
+        log::debug!(
+            "SharedReceiver: setting {} members of MLS epoch {}",
+            keys.len(),
+            epoch.epoch()
+        );
+        let evicted_key_ids = {
+            let mut store = self.write_keys();
+            store.remove_colliding_mls_epochs(epoch.epoch(), &key_ids);
+            store.insert_mls_epoch(epoch.epoch(), keys)
+        };
+        self.on_evicted_keys(evicted_key_ids);
+        Ok(())
+    }
+
+    /// Removes the keys of all members of an MLS epoch, returns `false` if the epoch was not set
+    pub fn remove_mls_epoch(&self, epoch: u64) -> bool {
+        self.write_keys().remove_mls_epoch(epoch).is_some()
+    }
+
+    /// Retires all MLS epochs older than the given one
+    pub fn remove_mls_epochs_before(&self, epoch: u64) {
+        self.write_keys().remove_mls_epochs_before(epoch);
+    }
+
+    /// Nof stored and evicted keys
+    pub fn key_store_stats(&self) -> KeyStoreStats {
+        self.read_keys().stats()
+    }
+
+    fn read_keys(&self) -> RwLockReadGuard<'_, KeyStore<Arc<SharedKey>>> {
+        self.keys.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_keys(&self) -> RwLockWriteGuard<'_, KeyStore<Arc<SharedKey>>> {
+        self.keys.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn create_key(&self, secret: Secret, cipher_suite: CipherSuite) -> Arc<SharedKey> {
+        Arc::new(SharedKey {
+            secret,
+            cipher_suite,
+            frame_validation: Mutex::new((self.options.frame_validation)()),
+            last_used: AtomicU64::new(self.use_counter.load(Ordering::Relaxed)),
+            decrypted_frames: AtomicU64::new(0),
+        })
+    }
+
+    /// Notifies about evicted keys, after the key store has been unlocked
+    fn on_evicted_keys(&self, evicted_key_ids: Vec<KeyId>) {
+        for evicted_key_id in evicted_key_ids {
+            log::debug!(
+                "SharedReceiver: evicting least recently used KeyID {:?}",
+                evicted_key_id
+            );
+            if let Some(callback) = self
+                .key_eviction_callback
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .as_mut()
+            {
+                callback(evicted_key_id);
+            }
+        }
+    }
+
+    /// Looks up the key of the KID, expired keys are only removed under the write lock if there are any
+    fn key(&self, key_id: KeyId) -> Result<Arc<SharedKey>> {
+        let clock = self.options.clock.as_deref();
+        let keys = self.read_keys();
+        let key = if keys.has_expired_keys(clock) {
+            drop(keys);
+            let mut keys = self.write_keys();
+            for expired_key_id in keys.remove_expired_keys(clock) {
+                log::debug!("SharedReceiver: KeyID {:?} expired", expired_key_id);
+            }
+            keys.get(&key_id).cloned()
+        } else {
+            keys.get(&key_id).cloned()
+        };
+        key.ok_or(SframeError::MissingDecryptionKey(key_id))
+    }
+
+    fn decrypt_payload(
+        &self,
+        header: &Header,
+        aad: &[u8],
+        encrypted_payload: &mut [u8],
+    ) -> Result<usize> {
+        let key = self.key(header.key_id())?;
+
+        key.frame_validation
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .validate(header)?;
+
+        let decrypted_len = key
+            .cipher_suite
+            .decrypt(encrypted_payload, &key.secret, aad, header.frame_count())?
+            .len();
+
+        // validated again, as the same frame might have been accepted by another thread meanwhile
+        let mut frame_validation = key
+            .frame_validation
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        frame_validation.validate(header)?;
+        frame_validation.accept(header);
+        drop(frame_validation);
+
+        key.last_used.store(
+            self.use_counter.fetch_add(1, Ordering::Relaxed) + 1,
+            Ordering::Relaxed,
+        );
+        key.decrypted_frames.fetch_add(1, Ordering::Relaxed);
+
+        Ok(decrypted_len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::time::Duration;
+    use std::{sync::Arc, thread};
+
+    use super::*;
+    use crate::{
+        clock::Clock, mls::MlsKeyIdLayout, receiver::Receiver, sender::Sender, CipherSuiteVariant,
+    };
+
+    const KEY_MATERIAL: &str = "foobar is unsafe";
+
+    fn sender(key_id: u64) -> Sender {
+        let mut sender = Sender::new(key_id);
+        sender.set_encryption_key(KEY_MATERIAL).unwrap();
+        sender
+    }
+
+    #[test]
+    fn decrypt_frames_of_senders_concurrently() {
+        let receiver = Arc::new(SharedReceiver::default());
+        let workers = (1..=4_u64)
+            .map(|key_id| {
+                receiver.set_encryption_key(key_id, KEY_MATERIAL).unwrap();
+                let receiver = receiver.clone();
+                thread::spawn(move || {
+                    let mut sender = sender(key_id);
+                    for _ in 0..100 {
+                        let encrypted = sender.encrypt(KEY_MATERIAL, 0).unwrap();
+                        assert_eq!(
+                            receiver.decrypt(encrypted, 0).unwrap(),
+                            KEY_MATERIAL.as_bytes()
+                        );
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn update_keys_while_decrypting() {
+        let receiver = Arc::new(SharedReceiver::default());
+        receiver.set_encryption_key(1_u64, KEY_MATERIAL).unwrap();
+        let mut sender = sender(1);
+        let encrypted = (0..100)
+            .map(|_| sender.encrypt(KEY_MATERIAL, 0).unwrap().to_vec())
+            .collect::<Vec<_>>();
+
+        let worker = thread::spawn({
+            let receiver = receiver.clone();
+            move || {
+                for frame in encrypted {
+                    receiver.decrypt(&frame, 0).unwrap();
+                }
+            }
+        });
+        for key_id in 2..100_u64 {
+            receiver.set_encryption_key(key_id, KEY_MATERIAL).unwrap();
+        }
+        worker.join().unwrap();
+
+        assert!(receiver.remove_encryption_key(1_u64));
+        let encrypted = sender.encrypt(KEY_MATERIAL, 0).unwrap();
+        assert_eq!(
+            receiver.decrypt(encrypted, 0),
+            Err(SframeError::MissingDecryptionKey(KeyId::from(1_u8)))
+        );
+    }
+
+    #[test]
+    fn accept_each_frame_only_once_across_threads() {
+        let receiver = Arc::new(SharedReceiver::default());
+        receiver.set_encryption_key(1_u64, KEY_MATERIAL).unwrap();
+        let encrypted = Arc::new(sender(1).encrypt(KEY_MATERIAL, 0).unwrap().to_vec());
+
+        let nof_decrypted = (0..4)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let encrypted = encrypted.clone();
+                thread::spawn(move || receiver.decrypt(encrypted.as_slice(), 0).is_ok())
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .filter(|decrypted| *decrypted)
+            .count();
+
+        assert_eq!(nof_decrypted, 1);
+    }
+
+    #[test]
+    fn configure_with_builder() {
+        let receiver = Receiver::builder()
+            .cipher_suite(CipherSuiteVariant::AesGcm128Sha256)
+            .build_shared();
+        receiver.set_encryption_key(1_u64, KEY_MATERIAL).unwrap();
+
+        let mut sender = Sender::with_cipher_suite(1_u64, CipherSuiteVariant::AesGcm128Sha256);
+        sender.set_encryption_key(KEY_MATERIAL).unwrap();
+        let encrypted = sender.encrypt(KEY_MATERIAL, 0).unwrap();
+
+        assert_eq!(
+            receiver.decrypt(encrypted, 0).unwrap(),
+            KEY_MATERIAL.as_bytes()
+        );
+    }
+
+    #[test]
+    fn evict_least_recently_used_key() {
+        let evicted_key_ids = Arc::new(Mutex::new(Vec::new()));
+        let receiver = Receiver::builder()
+            .max_keys(2)
+            .on_key_eviction({
+                let evicted_key_ids = evicted_key_ids.clone();
+                move |key_id| evicted_key_ids.lock().unwrap().push(key_id)
+            })
+            .build_shared();
+        receiver.set_encryption_key(1_u64, KEY_MATERIAL).unwrap();
+        receiver.set_encryption_key(2_u64, KEY_MATERIAL).unwrap();
+        let encrypted = sender(1).encrypt(KEY_MATERIAL, 0).unwrap().to_vec();
+        assert!(receiver.decrypt(&encrypted, 0).is_ok());
+
+        receiver.set_encryption_key(3_u64, KEY_MATERIAL).unwrap();
+        assert_eq!(*evicted_key_ids.lock().unwrap(), [KeyId::from(2_u64)]);
+        assert_eq!(
+            receiver.key_store_stats(),
+            KeyStoreStats {
+                keys: 2,
+                evicted_keys: 1
+            }
+        );
+        let encrypted = sender(2).encrypt(KEY_MATERIAL, 0).unwrap().to_vec();
+        assert_eq!(
+            receiver.decrypt(&encrypted, 0),
+            Err(SframeError::MissingDecryptionKey(KeyId::from(2_u64)))
+        );
+    }
+
+    #[derive(Clone, Default)]
+    struct TestClock(Arc<AtomicU64>);
+
+    impl Clock for TestClock {
+        fn now(&self) -> Duration {
+            Duration::from_millis(self.0.load(Ordering::SeqCst))
+        }
+    }
+
+    #[test]
+    fn expire_retired_keys() {
+        let clock = TestClock::default();
+        let receiver = Receiver::builder().clock(clock.clone()).build_shared();
+        for key_id in 1..=3_u64 {
+            receiver.set_encryption_key(key_id, KEY_MATERIAL).unwrap();
+        }
+        receiver
+            .retire_encryption_key(1_u64, GracePeriod::Time(Duration::from_millis(100)))
+            .unwrap();
+        receiver
+            .retire_encryption_key(
+                2_u64,
+                GracePeriod::Frames {
+                    newer_key_id: KeyId::from(3_u64),
+                    frames: 1,
+                },
+            )
+            .unwrap();
+        let (mut sender_1, mut sender_2, mut sender_3) = (sender(1), sender(2), sender(3));
+
+        assert!(receiver
+            .decrypt(sender_1.encrypt(KEY_MATERIAL, 0).unwrap(), 0)
+            .is_ok());
+        assert!(receiver
+            .decrypt(sender_3.encrypt(KEY_MATERIAL, 0).unwrap(), 0)
+            .is_ok());
+        assert_eq!(
+            receiver.decrypt(sender_2.encrypt(KEY_MATERIAL, 0).unwrap(), 0),
+            Err(SframeError::MissingDecryptionKey(KeyId::from(2_u64)))
+        );
+
+        clock.0.store(100, Ordering::SeqCst);
+        assert_eq!(
+            receiver.decrypt(sender_1.encrypt(KEY_MATERIAL, 0).unwrap(), 0),
+            Err(SframeError::MissingDecryptionKey(KeyId::from(1_u64)))
+        );
+        assert_eq!(receiver.key_store_stats().keys, 1);
+    }
+
+    #[test]
+    fn decrypt_frames_of_mls_epochs() {
+        let epoch = |epoch: u64| {
+            MlsEpoch::new(
+                epoch,
+                &[epoch as u8; 32],
+                MlsKeyIdLayout::new(2, 8).unwrap(),
+                CipherSuiteVariant::AesGcm128Sha256,
+            )
+            .unwrap()
+        };
+        let encrypt = |epoch: &MlsEpoch, index: u32| {
+            let mut sender = Sender::with_cipher_suite(
+                epoch.key_id(index).unwrap(),
+                CipherSuiteVariant::AesGcm128Sha256,
+            );
+            sender
+                .set_encryption_key(&epoch.sender_base_key(index).unwrap())
+                .unwrap();
+            sender.encrypt(KEY_MATERIAL, 0).unwrap().to_vec()
+        };
+        let receiver = SharedReceiver::default();
+        receiver.set_mls_epoch(&epoch(1), [0, 1]).unwrap();
+        receiver.set_mls_epoch(&epoch(2), [0, 1]).unwrap();
+
+        for index in [0, 1] {
+            assert!(receiver.decrypt(&encrypt(&epoch(1), index), 0).is_ok());
+            assert!(receiver.decrypt(&encrypt(&epoch(2), index), 0).is_ok());
+        }
+
+        receiver.remove_mls_epochs_before(2);
+        assert_eq!(
+            receiver.decrypt(&encrypt(&epoch(1), 1), 0),
+            Err(SframeError::MissingDecryptionKey(
+                epoch(1).key_id(1).unwrap()
+            ))
+        );
+        assert!(receiver.remove_mls_epoch(2));
+        assert_eq!(receiver.key_store_stats().keys, 0);
+    }
+
+    /// Counts how often the time is read
+    #[derive(Clone, Default)]
+    struct CountingClock(Arc<AtomicU64>);
+
+    impl Clock for CountingClock {
+        fn now(&self) -> Duration {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Duration::ZERO
+        }
+    }
+
+    #[test]
+    fn read_clock_only_while_keys_retire_by_time() {
+        let clock = CountingClock::default();
+        let receiver = Receiver::builder().clock(clock.clone()).build_shared();
+        receiver.set_encryption_key(1_u64, KEY_MATERIAL).unwrap();
+        receiver.set_encryption_key(2_u64, KEY_MATERIAL).unwrap();
+        let mut sender = sender(1);
+
+        assert!(receiver
+            .decrypt(sender.encrypt(KEY_MATERIAL, 0).unwrap(), 0)
+            .is_ok());
+        receiver
+            .retire_encryption_key(
+                2_u64,
+                GracePeriod::Frames {
+                    newer_key_id: KeyId::from(1_u64),
+                    frames: 10,
+                },
+            )
+            .unwrap();
+        assert!(receiver
+            .decrypt(sender.encrypt(KEY_MATERIAL, 0).unwrap(), 0)
+            .is_ok());
+        assert_eq!(clock.0.load(Ordering::SeqCst), 0);
+
+        receiver
+            .retire_encryption_key(2_u64, GracePeriod::Time(Duration::from_millis(100)))
+            .unwrap();
+        assert!(receiver
+            .decrypt(sender.encrypt(KEY_MATERIAL, 0).unwrap(), 0)
+            .is_ok());
+        assert_eq!(clock.0.load(Ordering::SeqCst), 2);
+    }
+}
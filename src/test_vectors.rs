@@ -1,28 +1,105 @@
 // Copyright (c) 2023 GoTo Group, Inc
 // SPDX-License-Identifier: Apache-2.0 AND MIT
 
+//! Test vectors of [RFC 9605 Appendix C](https://www.rfc-editor.org/rfc/rfc9605.html#name-test-vectors)
+
+// without alloc only the header test vectors are used
+#![cfg_attr(not(feature = "alloc"), allow(dead_code))]
+
 use crate::crypto::cipher_suite::CipherSuiteVariant;
 
+/// Test vector for the encryption of a whole frame, covering key derivation,
+/// nonce creation, header serialization and AEAD encryption
 #[derive(Debug, Clone)]
 pub struct TestVector {
     pub cipher_suite_variant: CipherSuiteVariant,
-    pub key_material: Vec<u8>,
+    pub base_key: Vec<u8>,
+    pub key_id: u64,
+    pub frame_count: u64,
+    pub metadata: Vec<u8>,
+    pub plain_text: Vec<u8>,
+
     pub key: Vec<u8>,
     pub salt: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub cipher_text: Vec<u8>,
+}
+
+/// Test vector for the AEAD algorithm of a cipher suite alone,
+/// see [RFC 9605 C.4](https://www.rfc-editor.org/rfc/rfc9605.html#name-aead-encryption-decryption-)
+#[derive(Debug, Clone)]
+pub struct AeadTestVector {
+    pub cipher_suite_variant: CipherSuiteVariant,
+    pub key: Vec<u8>,
+    pub nonce: Vec<u8>,
+    pub aad: Vec<u8>,
     pub plain_text: Vec<u8>,
+    pub cipher_text: Vec<u8>,
+}
 
+/// Test vector for the header encoding,
+/// see [RFC 9605 C.1](https://www.rfc-editor.org/rfc/rfc9605.html#name-header-encoding-decoding)
+#[derive(Debug, Clone)]
+pub struct HeaderTestVector {
     pub key_id: u64,
     pub frame_count: u64,
-    pub header: Vec<u8>,
-    pub nonce: Vec<u8>,
-    pub cipher_text: Vec<u8>,
+    pub encoded: Vec<u8>,
 }
 
 fn vec_from_hex_str(hex_str: &str) -> Vec<u8> {
     hex::decode(hex_str).unwrap()
 }
 
-pub mod aes_gcm_128_sha256 {
+pub mod header {
+
+    use std::vec;
+
+    use super::{vec_from_hex_str, HeaderTestVector};
+
+    fn create_test_vector(key_id: u64, frame_count: u64, encoded: &str) -> HeaderTestVector {
+        HeaderTestVector {
+            key_id,
+            frame_count,
+            encoded: vec_from_hex_str(encoded),
+        }
+    }
+
+    pub fn get_test_vectors() -> Vec<HeaderTestVector> {
+        vec![
+            create_test_vector(0x0, 0x0, "00"),
+            create_test_vector(0x0, 0x7, "07"),
+            create_test_vector(0x0, 0x8, "0808"),
+            create_test_vector(0x0, 0x100, "090100"),
+            create_test_vector(0x0, 0xffffffffffffffff, "0fffffffffffffffff"),
+            create_test_vector(0x7, 0x0, "70"),
+            create_test_vector(0x7, 0x7, "77"),
+            create_test_vector(0x7, 0x8, "7808"),
+            create_test_vector(0x7, 0x100, "790100"),
+            create_test_vector(0x7, 0xffffffffffffffff, "7fffffffffffffffff"),
+            create_test_vector(0x8, 0x0, "8008"),
+            create_test_vector(0x8, 0x7, "8708"),
+            create_test_vector(0x8, 0x8, "880808"),
+            create_test_vector(0x8, 0x100, "89080100"),
+            create_test_vector(0x8, 0xffffffffffffffff, "8f08ffffffffffffffff"),
+            create_test_vector(0x100, 0x0, "900100"),
+            create_test_vector(0x100, 0x7, "970100"),
+            create_test_vector(0x100, 0x8, "98010008"),
+            create_test_vector(0x100, 0x100, "9901000100"),
+            create_test_vector(0x100, 0xffffffffffffffff, "9f0100ffffffffffffffff"),
+            create_test_vector(0xffffffffffffffff, 0x0, "f0ffffffffffffffff"),
+            create_test_vector(0xffffffffffffffff, 0x7, "f7ffffffffffffffff"),
+            create_test_vector(0xffffffffffffffff, 0x8, "f8ffffffffffffffff08"),
+            create_test_vector(0xffffffffffffffff, 0x100, "f9ffffffffffffffff0100"),
+            create_test_vector(
+                0xffffffffffffffff,
+                0xffffffffffffffff,
+                "ffffffffffffffffffffffffffffffffff",
+            ),
+        ]
+    }
+}
+
+pub mod sframe {
 
     use std::vec;
 
@@ -31,21 +108,21 @@ pub mod aes_gcm_128_sha256 {
     use super::{vec_from_hex_str, TestVector};
 
     fn create_test_vector(
-        key_id: u64,
-        frame_count: u64,
-        header: &str,
+        cipher_suite_variant: CipherSuiteVariant,
+        key: &str,
+        salt: &str,
         nonce: &str,
         cipher_text: &str,
     ) -> TestVector {
         TestVector {
-            cipher_suite_variant: CipherSuiteVariant::AesGcm128Sha256,
-            key_material: vec_from_hex_str("303132333435363738393a3b3c3d3e3f"),
-            key: vec_from_hex_str("2ea2e8163ff56c0613e6fa9f20a213da"),
-            salt: vec_from_hex_str("a80478b3f6fba19983d540d5"),
-            plain_text: vec_from_hex_str("46726f6d2068656176656e6c79206861726d6f6e79202f2f205468697320756e6976657273616c206672616d6520626567616e"),
-            key_id,
-            frame_count,
-            header: vec_from_hex_str(header),
+            cipher_suite_variant,
+            base_key: vec_from_hex_str("000102030405060708090a0b0c0d0e0f"),
+            key_id: 0x123,
+            frame_count: 0x4567,
+            metadata: vec_from_hex_str("4945544620534672616d65205747"),
+            plain_text: vec_from_hex_str("64726166742d696574662d736672616d652d656e63"),
+            key: vec_from_hex_str(key),
+            salt: vec_from_hex_str(salt),
             nonce: vec_from_hex_str(nonce),
             cipher_text: vec_from_hex_str(cipher_text),
         }
@@ -53,55 +130,86 @@ pub mod aes_gcm_128_sha256 {
 
     pub fn get_test_vectors() -> Vec<TestVector> {
         vec![
-        create_test_vector(0x7, 0x0, "1700",            "a80478b3f6fba19983d540d5", "17000e426255e47ed70dd7d15d69d759bf459032ca15f5e8b2a91e7d348aa7c186d403f620801c495b1717a35097411aa97cbb140671eb3b49ac3775926db74d57b91e8e6c"),
-        create_test_vector(0x7, 0x1, "1701",            "a80478b3f6fba19983d540d4", "170103bbafa34ada8a6b9f2066bc34a1959d87384c9f4b1ce34fed58e938bde143393910b1aeb55b48d91d5b0db3ea67e3d0e02b843afd41630c940b1948e72dd45396a43a"),
-        create_test_vector(0x7, 0x2, "1702",            "a80478b3f6fba19983d540d7", "170258d58adebd8bf6f3cc0c1fcacf34ba4d7a763b2683fe302a57f1be7f2a274bf81b2236995fec1203cadb146cd402e1c52d5e6a10989dfe0f4116da1ee4c2fad0d21f8f"),
-        create_test_vector(0xf, 0xaa, "190faa",         "a80478b3f6fba19983d5407f", "190faad0b1743bf5248f90869c9456366d55724d16bbe08060875815565e90b114f9ccbdba192422b33848a1ae1e3bd266a001b2f5bb727112772e0072ea8679ca1850cf11d8"),
-        create_test_vector(0x1ff, 0xaa, "1a01ffaa",     "a80478b3f6fba19983d5407f", "1a01ffaad0b1743bf5248f90869c9456366d55724d16bbe08060875815565e90b114f9ccbdba192422b33848a1ae1e3bd266a001b2f5bbc9c63bd3973c19bd57127f565380ed4a"),
-        create_test_vector(0x1ff, 0xaaaa, "2a01ffaaaa", "a80478b3f6fba19983d5ea7f", "2a01ffaaaa9de65e21e4f1ca2247b87943c03c5cb7b182090e93d508dcfb76e08174c6397356e682d2eaddabc0b3c1018d2c13c3570f61c1beaab805f27b565e1329a823a7a649b6"),
-        create_test_vector(0xffffffffffffff, 0xffffffffffffff, "7fffffffffffffffffffffffffffff", "a80478b3f6045e667c2abf2a", "7fffffffffffffffffffffffffffff09981bdcdad80e380b6f74cf6afdbce946839bedadd57578bfcd809dbcea535546cc24660613d2761adea852155785011e633534f4ecc3b8257c8d34321c27854a1422"),
+        create_test_vector(
+            CipherSuiteVariant::AesCtr128HmacSha256_80,
+            "3f7d9a7c83ae8e1c8a11ae695ab59314b367e359fadac7b9c46b2bc6f81f46e16b96f0811868d59402b7e870102720b3",
+            "50b29329a04dc0f184ac3168",
+            "50b29329a04dc0f184ac740f",
+            "9901234567449408b6f490086165b9d6f62b24ae1a59a56486b4ae8ed036b88912e24f11",
+        ),
+        create_test_vector(
+            CipherSuiteVariant::AesCtr128HmacSha256_64,
+            "e2ec5c797540310483b16bf6e7a570d2a27d192fe869c7ccd8584a8d9dab91549fbe553f5113461ec6aa83bf3865553e",
+            "e68ac8dd3d02fbcd368c5577",
+            "e68ac8dd3d02fbcd368c1010",
+            "99012345673f31438db4d09434e43afa0f8a2f00867a2be085046a9f5cb4f101d607",
+        ),
+        create_test_vector(
+            CipherSuiteVariant::AesCtr128HmacSha256_32,
+            "2c5703089cbb8c583475e4fc461d97d18809df79b6d550f78eb6d50ffa80d89211d57909934f46f5405e38cd583c69fe",
+            "38c16e4f5159700c00c7f350",
+            "38c16e4f5159700c00c7b637",
+            "990123456717fc8af28a5a695afcfc6c8df6358a17e26b2fcb3bae32e443",
+        ),
+        create_test_vector(
+            CipherSuiteVariant::AesGcm128Sha256,
+            "d34f547f4ca4f9a7447006fe7fcbf768",
+            "75234edefe07819026751816",
+            "75234edefe07819026755d71",
+            "9901234567b7412c2513a1b66dbb48841bbaf17f598751176ad847681a69c6d0b091c07018ce4adb34eb",
+        ),
+        create_test_vector(
+            CipherSuiteVariant::AesGcm256Sha512,
+            "d3e27b0d4a5ae9e55df01a70e6d4d28d969b246e2936f4b7a5d9b494da6b9633",
+            "84991c167b8cd23c93708ec7",
+            "84991c167b8cd23c9370cba0",
+            "990123456794f509d36e9beacb0e261d99c7d1e972f1fed787d4049f17ca21353c1cc24d56ceabced279",
+        ),
+        // not part of RFC 9605, created with the same inputs by an independent implementation
+        create_test_vector(
+            CipherSuiteVariant::ChaCha20Poly1305Sha256,
+            "d8387a30392a431e7cad06f76e5b249dc7c11c7d12540af1c91ac15fbf6d823a",
+            "9001dd56e34cb31f9421ab9e",
+            "9001dd56e34cb31f9421eef9",
+            "99012345676fb3d5a3d455b58b9dccbfd0f60c74459877bbad94e3930f70b7277f06ccf38381a2b4f445",
+        ),
     ]
     }
 }
 
-pub mod aes_gcm_256_sha512 {
+pub mod aes_ctr_128_hmac_sha256 {
 
     use std::vec;
 
     use crate::crypto::cipher_suite::CipherSuiteVariant;
 
-    use super::{vec_from_hex_str, TestVector};
+    use super::{vec_from_hex_str, AeadTestVector};
 
-    fn create_test_vector(
-        key_id: u64,
-        frame_count: u64,
-        header: &str,
-        nonce: &str,
-        cipher_text: &str,
-    ) -> TestVector {
-        TestVector {
-            cipher_suite_variant: CipherSuiteVariant::AesGcm256Sha512,
-            key_material: vec_from_hex_str("404142434445464748494a4b4c4d4e4f505152535455565758595a5b5c5d5e5f"),
-            key: vec_from_hex_str("436774b0b5ae45633d96547f8f3cb06c8e6628eff2e4255b5c4d77e721aa3355"),
-            salt: vec_from_hex_str("31ed26f90a072e6aee646298"),
-            plain_text: vec_from_hex_str("46726f6d2068656176656e6c79206861726d6f6e79202f2f205468697320756e6976657273616c206672616d6520626567616e"),
-            key_id,
-            frame_count,
-            header: vec_from_hex_str(header),
-            nonce: vec_from_hex_str(nonce),
+    fn create_test_vector(variant: CipherSuiteVariant, cipher_text: &str) -> AeadTestVector {
+        AeadTestVector {
+            cipher_suite_variant: variant,
+            key: vec_from_hex_str("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728292a2b2c2d2e2f"),
+            nonce: vec_from_hex_str("101112131415161718191a1b"),
+            aad: vec_from_hex_str("4945544620534672616d65205747"),
+            plain_text: vec_from_hex_str("64726166742d696574662d736672616d652d656e63"),
             cipher_text: vec_from_hex_str(cipher_text),
         }
     }
 
-    pub fn get_test_vectors() -> Vec<TestVector> {
+    pub fn get_test_vectors() -> Vec<AeadTestVector> {
         vec![
-        create_test_vector(0x7, 0x0, "1700", "31ed26f90a072e6aee646298", "1700f3e297c1e95207710bd31ccc4ba396fbef7b257440bde638ff0f3c8911540136df61b26220249d6c432c245ae8d55ef45bfccf32530a15aeaaf313a03838e51bd45652"),
-        create_test_vector(0x7, 0x1, "1701", "31ed26f90a072e6aee646299", "170193268b0bf030071bff443bb6b4471bdfb1cc81bc9625f4697b0336ff4665d15f152f02169448d8a967fb06359a87d2145398de0ce3fbe257b0992a3da1537590459f3c"),
-        create_test_vector(0x7, 0x2, "1702", "31ed26f90a072e6aee64629a", "1702649691ba27c4c01a41280fba4657c03fa7fe21c8f5c862e9094227c3ca3ec0d9468b1a2cb060ff0978f25a24e6b106f5a6e1053c1b8f5fce794d88a0e4818c081e18ea"),
-        create_test_vector(0xf, 0xaa, "190faa", "31ed26f90a072e6aee646232", "190faa2858c10b5ddd231c1f26819490521678603a050448d563c503b1fd890d02ead01d754f074ecb6f32da9b2f3859f380b4f47d4edd1e15f42f9a2d7ecfac99067e238321"),
-        create_test_vector(0x1ff, 0xaa, "1a01ffaa", "31ed26f90a072e6aee646232", "1a01ffaa2858c10b5ddd231c1f26819490521678603a050448d563c503b1fd890d02ead01d754f074ecb6f32da9b2f3859f380b4f47d4e3bf7040eb10ec25b8126b2ce7b1d9d31"),
-        create_test_vector(0x1ff, 0xaaaa, "2a01ffaaaa", "31ed26f90a072e6aee64c832", "2a01ffaaaad9bc6a258a07d210a814d545eca70321c0e87498ada6e5c708b7ead162ffcf4fbaba1eb82650590a87122b4d95fe36bd88b278812166d26e046ed0a530b7ee232ee0f2"),
-        create_test_vector(0xffffffffffffff, 0xffffffffffffff, "7fffffffffffffffffffffffffffff", "31ed26f90af8d195119b9d67", "7fffffffffffffffffffffffffffffaf480d4779ce0c02b5137ee6a61e026c04ac999cb0c97319feceeb258d58df23bce14979e5c67a431777b34498062e72f939ca42ec84ffbc7b50eff923f515a2df760c"),
-    ]
+            create_test_vector(
+                CipherSuiteVariant::AesCtr128HmacSha256_80,
+                "6339af04ada1d064688a442b8dc69d5b6bfa40f4bef0583e8081069cc60705",
+            ),
+            create_test_vector(
+                CipherSuiteVariant::AesCtr128HmacSha256_64,
+                "6339af04ada1d064688a442b8dc69d5b6bfa40f4be6e93b7da076927bb",
+            ),
+            create_test_vector(
+                CipherSuiteVariant::AesCtr128HmacSha256_32,
+                "6339af04ada1d064688a442b8dc69d5b6bfa40f4be09480509",
+            ),
+        ]
     }
 }
@@ -1,6 +1,19 @@
 // Copyright (c) 2023 GoTo Group, Inc
 // SPDX-License-Identifier: Apache-2.0 AND MIT
 
+#[cfg(feature = "alloc")]
+use crate::error::{Result, SframeError};
+
+/// checks that the leading bytes which are not encrypted are part of the frame
+#[cfg(feature = "alloc")]
+pub(crate) fn check_skip(skip: usize, frame_len: usize) -> Result<()> {
+    if skip > frame_len {
+        Err(SframeError::SkipTooLarge { skip, frame_len })
+    } else {
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 pub(crate) fn bin2string(bin: &[u8]) -> String {
     bin.iter().map(|x| format!("{x:08b} ")).collect()
@@ -4,7 +4,10 @@
 use pretty_assertions::assert_eq;
 use rand::{thread_rng, Rng};
 
-use sframe::{receiver::Receiver, sender::Sender};
+use sframe::{
+    error::SframeError, ratchet::RatchetingKeyId, receiver::Receiver, sender::Sender, CipherSuite,
+    CipherSuiteVariant,
+};
 
 fn encrypt_decrypt_1000_frames(participant_id: u64, skipped_payload: usize) {
     let mut sender = Sender::new(participant_id);
@@ -57,3 +60,233 @@ fn decrypt_encrypted_frames_with_extended_key_id_and_skipped_payload() {
     let skipped_payload = 10;
     encrypt_decrypt_1000_frames(sender_id, skipped_payload);
 }
+
+#[test]
+fn decrypt_encrypted_frames_with_aes_ctr_cipher_suites() {
+    for variant in [
+        CipherSuiteVariant::AesCtr128HmacSha256_80,
+        CipherSuiteVariant::AesCtr128HmacSha256_64,
+        CipherSuiteVariant::AesCtr128HmacSha256_32,
+    ] {
+        let sender_id = 40_u64;
+        let key_material = "THIS_IS_SOME_MATERIAL";
+        let mut sender = Sender::with_cipher_suite(sender_id, variant);
+        sender.set_encryption_key(key_material).unwrap();
+
+        let mut receiver = Receiver::with_cipher_suite(variant);
+        receiver
+            .set_encryption_key(sender_id, key_material)
+            .unwrap();
+
+        let media_frame = b"draft-ietf-sframe-enc";
+        let encrypted_frame = sender.encrypt(media_frame, 4).unwrap();
+        let decrypted_frame = receiver.decrypt(encrypted_frame, 4).unwrap();
+
+        assert_eq!(media_frame.as_slice(), decrypted_frame);
+    }
+}
+
+#[test]
+fn decrypt_encrypted_frames_with_chacha20_poly1305() {
+    let sender_id = 40_u64;
+    let key_material = "THIS_IS_SOME_MATERIAL";
+    let variant = CipherSuiteVariant::ChaCha20Poly1305Sha256;
+    let mut sender = Sender::with_cipher_suite(sender_id, variant);
+    sender.set_encryption_key(key_material).unwrap();
+
+    let mut receiver = Receiver::with_cipher_suite(variant);
+    receiver
+        .set_encryption_key(sender_id, key_material)
+        .unwrap();
+
+    (0..100).for_each(|_| {
+        let mut media_frame = vec![0u8; 64];
+        thread_rng().fill(media_frame.as_mut_slice());
+
+        let encrypted_frame = sender.encrypt(&media_frame, 10).unwrap();
+        let decrypted_frame = receiver.decrypt(encrypted_frame, 10).unwrap();
+
+        assert_eq!(media_frame, decrypted_frame);
+    });
+}
+
+#[test]
+fn decrypt_encrypted_frames_with_truncated_auth_tag() {
+    let sender_id = 4_u64;
+    let key_material = "THIS_IS_SOME_MATERIAL";
+    let cipher_suite =
+        CipherSuite::with_auth_tag_len(CipherSuiteVariant::AesGcm128Sha256, 4).unwrap();
+    let mut sender = Sender::with_cipher_suite(sender_id, cipher_suite);
+    sender.set_encryption_key(key_material).unwrap();
+
+    let mut receiver = Receiver::with_cipher_suite(cipher_suite);
+    receiver
+        .set_encryption_key(sender_id, key_material)
+        .unwrap();
+
+    let media_frame = b"draft-ietf-sframe-enc";
+    let encrypted_frame = sender.encrypt(media_frame, 0).unwrap();
+    // 1 byte header + payload + 4 byte tag
+    assert_eq!(encrypted_frame.len(), 1 + media_frame.len() + 4);
+
+    let decrypted_frame = receiver.decrypt(encrypted_frame, 0).unwrap();
+    assert_eq!(media_frame.as_slice(), decrypted_frame);
+}
+
+#[test]
+fn fail_to_decrypt_frames_with_different_auth_tag_length() {
+    let sender_id = 4_u64;
+    let key_material = "THIS_IS_SOME_MATERIAL";
+    let cipher_suite =
+        CipherSuite::with_auth_tag_len(CipherSuiteVariant::AesGcm256Sha512, 8).unwrap();
+    let mut sender = Sender::with_cipher_suite(sender_id, cipher_suite);
+    sender.set_encryption_key(key_material).unwrap();
+
+    let mut receiver = Receiver::with_cipher_suite(CipherSuiteVariant::AesGcm256Sha512);
+    receiver
+        .set_encryption_key(sender_id, key_material)
+        .unwrap();
+
+    let encrypted_frame = sender.encrypt("draft-ietf-sframe-enc", 0).unwrap();
+    assert_eq!(
+        receiver.decrypt(encrypted_frame, 0),
+        Err(SframeError::DecryptionFailure)
+    );
+}
+
+#[test]
+fn receiver_follows_ratcheting_sender() {
+    let key_id = RatchetingKeyId::new(42_u64, 8).unwrap();
+    let key_material = "THIS_IS_SOME_MATERIAL";
+    let mut sender = Sender::with_ratcheting_key_id(key_id, CipherSuiteVariant::AesGcm128Sha256);
+    sender.set_encryption_key(key_material).unwrap();
+
+    let mut receiver = Receiver::with_cipher_suite(CipherSuiteVariant::AesGcm128Sha256);
+    receiver
+        .set_ratcheting_encryption_key(key_id, key_material)
+        .unwrap();
+
+    for _ in 0..3 {
+        sender.ratchet_encryption_key().unwrap();
+    }
+    let media_frame = b"draft-ietf-sframe-enc";
+    let encrypted_frame = sender.encrypt(media_frame, 0).unwrap();
+    let decrypted_frame = receiver.decrypt(encrypted_frame, 0).unwrap();
+    assert_eq!(media_frame.as_slice(), decrypted_frame);
+}
+
+#[test]
+fn encrypt_decrypt_in_place() {
+    let sender_id = 4_u64;
+    let key_material = "THIS_IS_SOME_MATERIAL";
+    let mut sender = Sender::new(sender_id);
+    sender.set_encryption_key(key_material).unwrap();
+    let mut receiver = Receiver::default();
+    receiver
+        .set_encryption_key(sender_id, key_material)
+        .unwrap();
+
+    let media_frame = b"draft-ietf-sframe-enc";
+    let mut frame = Vec::with_capacity(sender.encrypted_frame_len(media_frame.len()));
+    for _ in 0..10 {
+        frame.clear();
+        frame.extend_from_slice(media_frame);
+        sender.encrypt_in_place(&mut frame, 5).unwrap();
+
+        let frame_len = receiver.decrypt_in_place(&mut frame, 5).unwrap();
+        assert_eq!(media_frame.as_slice(), &frame[..frame_len]);
+    }
+}
+
+#[test]
+fn validate_frame_counts_of_senders_independently() {
+    let key_material = "THIS_IS_SOME_MATERIAL";
+    let mut receiver = Receiver::default();
+    let mut senders = (0..3_u64)
+        .map(|sender_id| {
+            receiver
+                .set_encryption_key(sender_id, key_material)
+                .unwrap();
+            let mut sender = Sender::new(sender_id);
+            sender.set_encryption_key(key_material).unwrap();
+            sender
+        })
+        .collect::<Vec<_>>();
+
+    let media_frame = b"draft-ietf-sframe-enc";
+    for (nof_frames, sender) in [1000, 10, 500].into_iter().zip(senders.iter_mut()) {
+        for _ in 0..nof_frames {
+            let encrypted_frame = sender.encrypt(media_frame, 0).unwrap();
+            let decrypted_frame = receiver.decrypt(encrypted_frame, 0).unwrap();
+            assert_eq!(media_frame.as_slice(), decrypted_frame);
+        }
+    }
+}
+
+#[test]
+fn configure_receiver_with_builder() {
+    let sender_id = 4_u64;
+    let key_material = "THIS_IS_SOME_MATERIAL";
+    let mut sender = Sender::with_cipher_suite(sender_id, CipherSuiteVariant::AesGcm128Sha256);
+    sender.set_encryption_key(key_material).unwrap();
+
+    let mut receiver = Receiver::builder()
+        .cipher_suite(CipherSuiteVariant::AesGcm128Sha256)
+        .replay_attack_tolerance(16)
+        .build();
+    receiver
+        .set_encryption_key(sender_id, key_material)
+        .unwrap();
+
+    let media_frame = b"draft-ietf-sframe-enc";
+    let encrypted_frame = sender.encrypt(media_frame, 0).unwrap();
+    let decrypted_frame = receiver.decrypt(encrypted_frame, 0).unwrap();
+    assert_eq!(media_frame.as_slice(), decrypted_frame);
+}
+
+#[test]
+fn decrypt_senders_with_different_cipher_suites() {
+    let key_material = "THIS_IS_SOME_MATERIAL";
+    let legacy_sender_id = 1_u64;
+    let mut legacy_sender =
+        Sender::with_cipher_suite(legacy_sender_id, CipherSuiteVariant::AesGcm256Sha512);
+    legacy_sender.set_encryption_key(key_material).unwrap();
+    let sender_id = 2_u64;
+    let mut sender = Sender::with_cipher_suite(sender_id, CipherSuiteVariant::AesGcm128Sha256);
+    sender.set_encryption_key(key_material).unwrap();
+
+    let mut receiver = Receiver::default();
+    receiver
+        .set_encryption_key(legacy_sender_id, key_material)
+        .unwrap();
+    receiver
+        .set_encryption_key_with_cipher_suite(
+            sender_id,
+            key_material,
+            CipherSuiteVariant::AesGcm128Sha256,
+        )
+        .unwrap();
+
+    let media_frame = b"draft-ietf-sframe-enc";
+    for _ in 0..10 {
+        for sender in [&mut legacy_sender, &mut sender] {
+            let encrypted_frame = sender.encrypt(media_frame, 0).unwrap();
+            let decrypted_frame = receiver.decrypt(encrypted_frame, 0).unwrap();
+            assert_eq!(media_frame.as_slice(), decrypted_frame);
+        }
+    }
+}
+
+#[test]
+fn decrypt_on_worker_thread() {
+    let key_material = "THIS_IS_SOME_MATERIAL";
+    let mut sender = Sender::new(42_u64);
+    sender.set_encryption_key(key_material).unwrap();
+    let mut receiver = Receiver::default();
+    receiver.set_encryption_key(42_u64, key_material).unwrap();
+
+    let encrypted = sender.encrypt("hello worker", 0).unwrap().to_vec();
+    let worker = std::thread::spawn(move || receiver.decrypt(&encrypted, 0).map(<[u8]>::to_vec));
+
+    assert_eq!(worker.join().unwrap().unwrap(), b"hello worker");
+}
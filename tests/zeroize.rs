@@ -0,0 +1,151 @@
+// Copyright (c) 2023 GoTo Group, Inc
+// SPDX-License-Identifier: Apache-2.0 AND MIT
+
+//! Checks that derived key material is wiped before its memory is freed, by scanning every
+//! allocation on deallocation for the expected keys, salts and base keys.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use sframe::{
+    mls::{MlsEpoch, MlsKeyIdLayout},
+    receiver::Receiver,
+    sender::Sender,
+    CipherSuiteVariant,
+};
+
+const KEY_ID: u64 = 42;
+const KEY_MATERIAL: &[u8] = b"THIS_IS_SOME_MATERIAL";
+const OTHER_KEY_MATERIAL: &[u8] = b"THIS_IS_OTHER_MATERIAL";
+const EXPORTER_SECRET: [u8; 32] = [0x22; 32];
+const MLS_MEMBER: u32 = 3;
+
+/// salts of `KEY_MATERIAL` and `OTHER_KEY_MATERIAL` for `KEY_ID` with AES-GCM-128,
+/// and the base key of `MLS_MEMBER` derived from `EXPORTER_SECRET`
+const WIPED_SECRETS: &[&str] = &[
+    "e1c59adc4b94214658ef1442",
+    "138f6cbc4207c93ee231ee4b",
+    "a0487ba6e855693a4dcd2c5ad06052d0625b5bbb847899640ebb9e79be07dc9b",
+];
+
+/// keys of `KEY_MATERIAL` and `OTHER_KEY_MATERIAL` for `KEY_ID` with AES-GCM-128,
+/// which are only wiped by backends supporting it for their AEAD keys
+#[cfg(any(feature = "openssl", feature = "rust-crypto"))]
+const WIPED_KEYS: &[&str] = &[
+    "7eab9b920551e2f9b153fe7c1e9189d6",
+    "afd1e79a85e007768f31e302f7d183e7",
+];
+#[cfg(not(any(feature = "openssl", feature = "rust-crypto")))]
+const WIPED_KEYS: &[&str] = &[];
+
+thread_local! {
+    static FREED_SECRET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Forwards to the system allocator and records if a freed allocation still contains a secret
+struct ScanningAllocator;
+
+#[global_allocator]
+static ALLOCATOR: ScanningAllocator = ScanningAllocator;
+
+unsafe impl GlobalAlloc for ScanningAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let memory = std::slice::from_raw_parts(ptr, layout.size());
+        if contains_secret(memory) {
+            let _ = FREED_SECRET.try_with(|freed| freed.set(true));
+        }
+        System.dealloc(ptr, layout);
+    }
+}
+
+/// compares hex digit by hex digit, as decoding the secrets would allocate
+fn contains_secret(memory: &[u8]) -> bool {
+    WIPED_SECRETS.iter().chain(WIPED_KEYS).any(|secret| {
+        let secret = secret.as_bytes();
+        memory.windows(secret.len() / 2).any(|window| {
+            window.iter().zip(secret.chunks(2)).all(|(byte, hex)| {
+                let hex = core::str::from_utf8(hex).unwrap();
+                u8::from_str_radix(hex, 16) == Ok(*byte)
+            })
+        })
+    })
+}
+
+fn freed_secret() -> bool {
+    FREED_SECRET.with(Cell::take)
+}
+
+#[test]
+fn detect_freed_secrets() {
+    let salt = vec![
+        0xe1_u8, 0xc5, 0x9a, 0xdc, 0x4b, 0x94, 0x21, 0x46, 0x58, 0xef, 0x14, 0x42,
+    ];
+    drop(salt);
+    assert!(freed_secret());
+}
+
+#[test]
+fn wipe_replaced_and_dropped_sender_keys() {
+    freed_secret();
+    let mut sender = Box::new(Sender::with_cipher_suite(
+        KEY_ID,
+        CipherSuiteVariant::AesGcm128Sha256,
+    ));
+
+    sender.set_encryption_key(KEY_MATERIAL).unwrap();
+    sender.encrypt(b"foobar", 0).unwrap();
+    sender.set_encryption_key(OTHER_KEY_MATERIAL).unwrap();
+    sender.encrypt(b"foobar", 0).unwrap();
+    drop(sender);
+
+    assert!(!freed_secret());
+}
+
+#[test]
+fn wipe_replaced_removed_and_dropped_receiver_keys() {
+    freed_secret();
+    let mut receiver = Receiver::with_cipher_suite(CipherSuiteVariant::AesGcm128Sha256);
+
+    receiver.set_encryption_key(KEY_ID, KEY_MATERIAL).unwrap();
+    receiver
+        .set_encryption_key(KEY_ID, OTHER_KEY_MATERIAL)
+        .unwrap();
+    assert!(receiver.remove_encryption_key(KEY_ID));
+    receiver.set_encryption_key(KEY_ID, KEY_MATERIAL).unwrap();
+    drop(receiver);
+
+    assert!(!freed_secret());
+}
+
+#[test]
+fn wipe_mls_base_keys() {
+    freed_secret();
+    let epoch = MlsEpoch::new(
+        1,
+        &EXPORTER_SECRET,
+        MlsKeyIdLayout::new(4, 8).unwrap(),
+        CipherSuiteVariant::AesGcm128Sha256,
+    )
+    .unwrap();
+
+    let base_key = epoch.sender_base_key(MLS_MEMBER).unwrap();
+    let mut sender = Sender::with_cipher_suite(
+        epoch.key_id(MLS_MEMBER).unwrap(),
+        CipherSuiteVariant::AesGcm128Sha256,
+    );
+    sender.set_encryption_key(base_key.as_ref()).unwrap();
+    drop(base_key);
+
+    let mut receiver = Receiver::with_cipher_suite(CipherSuiteVariant::AesGcm128Sha256);
+    receiver.set_mls_epoch(&epoch, [MLS_MEMBER]).unwrap();
+    assert!(receiver.remove_mls_epoch(1));
+    drop((epoch, sender, receiver));
+
+    assert!(!freed_secret());
+}